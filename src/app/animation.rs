@@ -1,56 +1,81 @@
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
 use std::fs;
-use anyhow::Result;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
 
-use crate::app::args::Args;
+use crate::app::args::AnimateArgs;
+use crate::app::config::Config;
+use crate::app::run::{bind_coordinator, model_url, scene_by_name, settings};
+use raytracer::io::asset_loader::download_obj_with_assets;
 use raytracer::objects::world::World;
-use raytracer::renderer::scene::Scene;
 use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::distributed::Job;
+use raytracer::renderer::image::Image;
+use raytracer::renderer::progress::{CancelToken, NoProgress};
 
-const NUM_FRAMES: usize = 60;
+// With a coordinator the frames are split into tiles for its workers, which
+// stay connected from one frame to the next
+pub async fn animate(args: AnimateArgs, config: &Config) -> Result<()> {
+    let url = model_url(config, &args.scene.model)?;
+    let scene = scene_by_name(&args.scene.scene)?;
+    let settings = &settings(&args.settings)?;
+    let coordinator = args.coordinator.as_deref().map(bind_coordinator).transpose()?;
+    let (_tmp_dir, obj_path) = download_obj_with_assets(url).await?;
 
-pub async fn render_animation(args: Args, scene: Box<dyn Scene>, obj_path: &PathBuf, width: usize, height: usize) -> Result<()> {
-    let animation_filename = format!("animation/{}_{}.gif", args.scene, args.model);
+    let gif = args.output.clone().unwrap_or_else(|| format!("{}_{}.gif", args.scene.scene, args.scene.model).into());
+    // The palette always goes to a temporary directory, the frames unless kept
+    let tmp_dir = TempDir::new()?;
+    let frame_dir = args.frame_dir.as_deref().unwrap_or(tmp_dir.path());
+    fs::create_dir_all(frame_dir).with_context(|| format!("Failed to create {}", frame_dir.display()))?;
 
-    for frame in 0..NUM_FRAMES {
-        let angle = frame as f32 / NUM_FRAMES as f32 * 360.0;
-        let mut world = World::new();
-        let camera = scene.setup(obj_path, &mut world, angle, height, width).await?;
-        let renderer = Renderer::new(camera, world);
-        let filename = format!("frame_{:03}", frame);
-        renderer.render_scene_to_file(width, height, &filename);
+    for frame in 0..args.frames {
+        let angle = frame as f32 / args.frames as f32 * 360.0;
+        let image = match &coordinator {
+            Some(coordinator) => {
+                let job = Job {
+                    scene: args.scene.scene.clone(),
+                    model: url.to_string(),
+                    angle,
+                    settings: settings.clone(),
+                };
+                let output = coordinator.render(&job, &NoProgress, &CancelToken::new())?;
+                Image::new(settings.width, settings.height, &output.pixels)
+            }
+            None => {
+                let mut world = World::new();
+                let camera = scene.setup(&obj_path, &mut world, angle, settings.width as f32 / settings.height as f32).await?;
+                Renderer::new(camera, world).render(settings)?
+            }
+        };
+        image.save_ppm(&frame_dir.join(format!("frame_{frame:03}.ppm")), settings)?;
     }
 
-    Command::new("ffmpeg")
-        .args(["-y", "-framerate", "24", "-i", "animation/frame_%03d.ppm", "-vf", "palettegen", "palette.png"])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()?;
-
-    Command::new("ffmpeg")
-        .args([
-            "-y",
-            "-framerate", "24",
-            "-i", "animation/frame_%03d.ppm",
-            "-i", "palette.png",
-            "-lavfi", "paletteuse",
-            &animation_filename,
-        ])
+    let frames = frame_dir.join("frame_%03d.ppm");
+    let palette = tmp_dir.path().join("palette.png");
+    ffmpeg(Command::new("ffmpeg").args(["-y", "-framerate", "24", "-i"]).arg(&frames).args(["-vf", "palettegen"]).arg(&palette))?;
+    ffmpeg(
+        Command::new("ffmpeg")
+            .args(["-y", "-framerate", "24", "-i"])
+            .arg(&frames)
+            .arg("-i")
+            .arg(&palette)
+            .args(["-lavfi", "paletteuse"])
+            .arg(&gif),
+    )?;
+
+    println!("Animation saved to {}", gif.display());
+    Ok(())
+}
+
+fn ffmpeg(command: &mut Command) -> Result<()> {
+    let status = command
         .stdout(Stdio::null())
         .stderr(Stdio::null())
-        .status()?;
-
-    for entry in fs::read_dir("animation")? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().and_then(|ext| ext.to_str()) == Some("ppm") {
-            fs::remove_file(path)?;
-        }
+        .status()
+        .context("Failed to run ffmpeg, is it installed?")?;
+    if !status.success() {
+        bail!("ffmpeg failed with {status}");
     }
-
-    fs::remove_file("palette.png").ok();
-
-    println!("Animation saved to {}", animation_filename);
     Ok(())
 }
@@ -1,22 +1,249 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use raytracer::core::sampler::SamplerKind;
+use raytracer::renderer::film::FilterKind;
+use raytracer::renderer::postprocess::{OutputTransform, ToneMapper};
+use raytracer::renderer::settings::IntegratorKind;
 
 #[derive(Parser)]
-pub struct Args {
+pub struct Cli {
+    #[arg(short, long, default_value = "config.toml", global = true)]
+    pub config: String,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render one image
+    Render(RenderArgs),
+
+    /// Render frames circling the model and join them to a GIF with ffmpeg
+    Animate(AnimateArgs),
+
+    /// Print the models in the config
+    ListModels,
+
+    /// Print triangle count, bounds and materials of a model
+    Inspect {
+        model: String,
+    },
+
+    /// Time renders of the built-in scenes and print a JSON report
+    Bench(BenchArgs),
+
+    /// Render jobs submitted over a local HTTP API until stopped
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
+
+    /// Render tiles for the coordinator at this address until it is done
+    Worker {
+        addr: String,
+    },
+}
+
+#[derive(Args)]
+pub struct SceneArgs {
     #[arg(short, long, default_value = "cube-tex")]
     pub model: String,
 
-    #[arg(short, long, default_value = "config.toml")]
-    pub config: String,
-
     #[arg(long, default_value = "required")]
     pub scene: String,
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    #[command(flatten)]
+    pub scene: SceneArgs,
 
     #[arg(long, default_value_t = 0.0)]
     pub angle: f32,
 
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    /// Format follows the extension: ppm, png or exr
+    #[arg(short, long, default_value = "output.ppm")]
+    pub output: PathBuf,
+
+    /// Save the film while rendering so --resume can continue it, next to the
+    /// output unless a path is given
+    #[arg(long, num_args = 0..=1)]
+    pub checkpoint: Option<Option<PathBuf>>,
+
+    /// Seconds between checkpoints
+    #[arg(long, default_value_t = 60)]
+    pub checkpoint_interval: u64,
+
+    /// Continue from the checkpoint, or refine it when asked for more samples
+    #[arg(long)]
+    pub resume: bool,
+
+    /// x0,y0,x1,y1 from the top left, in pixels or as fractions like 0.25
+    #[arg(long)]
+    pub crop: Option<String>,
+
+    /// Write only the cropped rectangle instead of updating the full frame
+    #[arg(long)]
+    pub crop_sub_image: bool,
+
+    /// Also write heatmap.ppm next to the output, showing the samples every
+    /// pixel took
+    #[arg(long)]
+    pub heatmap: bool,
+
+    /// Also write depth, normals, ids and the light passes to aovs.exr next to
+    /// the output
+    #[arg(long)]
+    pub aovs: bool,
+
+    /// Listen there and hand tiles to the workers that connect
+    #[arg(long)]
+    pub coordinator: Option<String>,
+}
+
+#[derive(Args)]
+pub struct AnimateArgs {
+    #[command(flatten)]
+    pub scene: SceneArgs,
+
+    #[command(flatten)]
+    pub settings: SettingsArgs,
+
+    #[arg(long, default_value_t = 60)]
+    pub frames: usize,
+
+    /// GIF to write, {scene}_{model}.gif by default
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Keep the frames there as frame_000.ppm and so on instead of deleting them
+    #[arg(long)]
+    pub frame_dir: Option<PathBuf>,
+
+    /// Listen there and hand the tiles of every frame to the workers that connect
+    #[arg(long)]
+    pub coordinator: Option<String>,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// spheres, mesh, glass or lights, all of them by default
+    #[arg(long)]
+    pub scene: Vec<String>,
+
+    #[arg(short, default_value_t = 256)]
+    pub resolution: usize,
+
+    /// Overrides the sample count of every scene
     #[arg(long)]
-    pub animate: bool,
+    pub spp: Option<i32>,
+
+    #[arg(long, default_value_t = 3)]
+    pub runs: usize,
+
+    /// Write the report there instead of to stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
 
+#[derive(Args)]
+pub struct SettingsArgs {
+    /// Width and height of the square image in pixels
     #[arg(short, default_value_t = 1024)]
     pub resolution: usize,
+
+    /// Trace sampled wavelengths instead of RGB, for dispersion
+    #[arg(long)]
+    pub spectral: bool,
+
+    /// How light is gathered, or a debug view. path and bdpt cannot light
+    /// diffuse surfaces through glass, use photon for caustics.
+    #[arg(long, value_enum, default_value_t = IntegratorKind::Classic)]
+    pub integrator: IntegratorKind,
+
+    /// Photons shot per pass to gather caustics with --integrator photon
+    #[arg(long, default_value_t = 200_000)]
+    pub photons: usize,
+
+    /// Darken the classic integrator's ambient light by ambient occlusion
+    #[arg(long)]
+    pub ambient_occlusion: bool,
+
+    /// Occlusion rays per hit for --integrator ao and --ambient-occlusion
+    #[arg(long, default_value_t = 4)]
+    pub ao_samples: usize,
+
+    /// How far the occlusion rays look for occluders
+    #[arg(long, default_value_t = 1.0)]
+    pub ao_distance: f32,
+
+    /// Glass casts tinted shadows in the classic integrator
+    #[arg(long)]
+    pub transmissive_shadows: bool,
+
+    /// Where the random numbers of the samples come from
+    #[arg(long, value_enum, default_value_t = SamplerKind::Sobol)]
+    pub sampler: SamplerKind,
+
+    /// The same seed renders the same image
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Pixel reconstruction filter, wider ones blur slightly and alias less
+    #[arg(long, value_enum, default_value_t = FilterKind::Box)]
+    pub filter: FilterKind,
+
+    /// Samples per pixel
+    #[arg(long, default_value_t = 20)]
+    pub spp: i32,
+
+    /// Take more samples in noisy pixels and stop early in converged ones
+    #[arg(long)]
+    pub adaptive: bool,
+
+    /// Relative standard error at which --adaptive considers a pixel converged
+    #[arg(long, default_value_t = 0.01)]
+    pub noise_threshold: f32,
+
+    /// Samples every pixel takes before --adaptive checks its noise
+    #[arg(long, default_value_t = 16)]
+    pub min_spp: i32,
+
+    /// Samples --adaptive stops at even in pixels that stay noisy
+    #[arg(long, default_value_t = 256)]
+    pub max_spp: i32,
+
+    /// Smooth the noise of the finished image, keeping edges and textures
+    #[arg(long)]
+    pub denoise: bool,
+
+    /// In EV stops, negative values darken
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub exposure: f32,
+
+    /// Colour temperature in Kelvin that becomes neutral
+    #[arg(long, default_value_t = 6504.0)]
+    pub white_balance: f32,
+
+    /// Curve that maps bright scene colours into the displayable range
+    #[arg(long, value_enum, default_value_t = ToneMapper::Clamp)]
+    pub tonemap: ToneMapper,
+
+    /// Encoding of the written values, linear for data read back as light
+    #[arg(long, value_enum, default_value_t = OutputTransform::Srgb)]
+    pub output_transform: OutputTransform,
+
+    /// Strength of the glow around everything brighter than 1.0
+    #[arg(long, default_value_t = 0.0)]
+    pub bloom: f32,
+
+    /// How much the corners darken
+    #[arg(long, default_value_t = 0.0)]
+    pub vignette: f32,
 }
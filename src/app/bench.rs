@@ -0,0 +1,44 @@
+use std::fs;
+
+use anyhow::{Context, Result};
+
+use crate::app::args::BenchArgs;
+
+use raytracer::renderer::benchmark::{self, BenchmarkReport, SCENES};
+
+// Renders the built-in scenes, which need neither the config nor the network,
+// and prints the report as JSON. A summary of every scene goes to stderr.
+pub fn bench(args: BenchArgs) -> Result<()> {
+    let scenes = if args.scene.is_empty() {
+        SCENES.iter().collect()
+    } else {
+        args.scene.iter().map(|name| benchmark::scene_by_name(name)).collect::<Result<Vec<_>>>()?
+    };
+
+    let mut report = BenchmarkReport {
+        threads: rayon::current_num_threads(),
+        scenes: Vec::new(),
+    };
+    for scene in scenes {
+        let mut settings = scene.settings(args.resolution, args.resolution);
+        if let Some(spp) = args.spp {
+            settings.samples_per_pixel = spp;
+        }
+        eprintln!("{}: {}", scene.name, scene.description);
+        let result = benchmark::run(scene, &settings, args.runs)?;
+        eprintln!(
+            "  render {:.3}s, {:.2}M rays/s, BVH {:.3}s",
+            result.timings.render_seconds,
+            result.rays_per_second / 1e6,
+            result.timings.bvh_seconds
+        );
+        report.scenes.push(result);
+    }
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{json}"),
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::fs;
+use anyhow::Context;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -8,7 +9,7 @@ pub struct Config {
 }
 
 pub fn load_config(path: &str) -> anyhow::Result<Config> {
-    let content = fs::read_to_string(path)?;
-    let config: Config = toml::from_str(&content)?;
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read the config {path}"))?;
+    let config: Config = toml::from_str(&content).with_context(|| format!("Invalid config {path}"))?;
     Ok(config)
 }
@@ -2,3 +2,6 @@ pub mod args;
 pub mod config;
 pub mod run;
 pub mod animation;
+pub mod serve;
+pub mod models;
+pub mod bench;
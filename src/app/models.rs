@@ -0,0 +1,45 @@
+use anyhow::Result;
+
+use crate::app::config::Config;
+use crate::app::run::model_url;
+
+use raytracer::io::asset_loader::download_obj_with_assets;
+use raytracer::io::obj;
+
+pub fn list_models(config: &Config) {
+    let mut models: Vec<_> = config.models.iter().collect();
+    models.sort();
+    let width = models.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, url) in models {
+        println!("{name:width$}  {url}");
+    }
+}
+
+pub async fn inspect(config: &Config, model: &str) -> Result<()> {
+    let url = model_url(config, model)?;
+    let (_tmp_dir, obj_path) = download_obj_with_assets(url).await?;
+    let info = obj::inspect_obj(&obj_path)?;
+
+    let (min, max) = info.bounds;
+    let size = max - min;
+    println!("{model}: {url}");
+    println!("Triangles: {}", info.triangles);
+    println!("Vertices:  {}", info.vertices);
+    println!("Bounds:    ({:.3}, {:.3}, {:.3}) to ({:.3}, {:.3}, {:.3})", min.x(), min.y(), min.z(), max.x(), max.y(), max.z());
+    println!("Size:      {:.3} x {:.3} x {:.3}", size.x(), size.y(), size.z());
+
+    println!("Meshes:");
+    for (name, triangles) in &info.meshes {
+        println!("  {name}: {triangles} triangles");
+    }
+    println!("Materials:");
+    if info.materials.is_empty() {
+        println!("  none, the scene's default material is used");
+    }
+    for material in &info.materials {
+        let diffuse = material.diffuse.map_or(String::from("-"), |c| format!("({:.2}, {:.2}, {:.2})", c.x(), c.y(), c.z()));
+        let texture = material.texture.as_deref().unwrap_or("-");
+        println!("  {}: diffuse {diffuse}, texture {texture}", material.name);
+    }
+    Ok(())
+}
@@ -1,40 +1,193 @@
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::app::args::Args;
+use anyhow::{Result, bail};
+use tempfile::TempDir;
+use tokio::runtime::Handle;
+
+use crate::app::args::{RenderArgs, SettingsArgs};
 use crate::app::config::Config;
-use crate::app::animation::render_animation;
 
 use raytracer::io::asset_loader::download_obj_with_assets;
 use raytracer::objects::world::World;
 use raytracer::renderer::scene::{CustomScene, MuseumScene, RequiredScene, Scene};
-use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::renderer::{RenderOutput, Renderer};
+use raytracer::renderer::adaptive;
+use raytracer::renderer::image::{self, Image};
+use raytracer::renderer::progress::{CancelToken, StderrProgress};
+use raytracer::renderer::distributed::{self, Coordinator, Job};
+use raytracer::renderer::settings::{CropWindow, RenderSettings};
+
+pub async fn render(args: RenderArgs, config: &Config) -> Result<()> {
+    let url = model_url(config, &args.scene.model)?;
+    let scene = scene_by_name(&args.scene.scene)?;
+    let base = settings(&args.settings)?;
+    let (width, height) = (base.width, base.height);
+    let crop = args.crop.as_deref().map(|text| CropWindow::parse(text, width, height)).transpose()?;
+    // Resuming reads the checkpoint and keeps it up to date
+    let checkpoint = (args.checkpoint.is_some() || args.resume)
+        .then(|| args.checkpoint.clone().flatten().unwrap_or_else(|| args.output.with_extension("ckpt")));
+    let settings = RenderSettings {
+        sample_heatmap: args.heatmap,
+        aovs: args.aovs,
+        crop,
+        checkpoint: checkpoint.filter(|_| args.coordinator.is_none()),
+        checkpoint_interval: Duration::from_secs(args.checkpoint_interval),
+        scene: format!("{} {url} angle {}", args.scene.scene, args.angle),
+        resume: args.resume,
+        ..base
+    };
+    settings.validate()?;
 
-pub async fn run_or_animate(args: Args, config: Config) -> Result<()> {
-    let Some(url) = config.models.get(&args.model) else {
-        eprintln!("Model '{}' not found in config", args.model);
-        std::process::exit(1);
+    let cancel = cancel_on_interrupt();
+    let output = match &args.coordinator {
+        // The workers build the scene themselves
+        Some(addr) => {
+            if args.resume {
+                bail!("--resume is not supported with --coordinator");
+            }
+            let coordinator = bind_coordinator(addr)?;
+            let job = Job {
+                scene: args.scene.scene.clone(),
+                model: url.to_string(),
+                angle: args.angle,
+                settings: settings.clone(),
+            };
+            coordinator.render(&job, &StderrProgress, &cancel)?
+        }
+        None => {
+            let (_tmp_dir, obj_path) = download_obj_with_assets(url).await?;
+            let mut world = World::new();
+            let camera = scene.setup(&obj_path, &mut world, args.angle, width as f32 / height as f32).await?;
+            Renderer::new(camera, world).render_with(&settings, &StderrProgress, &cancel)?
+        }
     };
+    save_output(output, &settings, &args.output, args.crop_sub_image)
+}
 
-    let (_tmp_dir, obj_path) = download_obj_with_assets(url).await?;
-    let mut world = World::new();
+pub fn model_url<'a>(config: &'a Config, model: &str) -> Result<&'a str> {
+    match config.models.get(model) {
+        Some(url) => Ok(url),
+        None => bail!("Model '{model}' not found in config, see list-models"),
+    }
+}
 
-    let scene: Box<dyn Scene> = match args.scene.as_str() {
+pub fn scene_by_name(name: &str) -> Result<Box<dyn Scene>> {
+    Ok(match name {
         "custom" => Box::new(CustomScene),
         "required" => Box::new(RequiredScene),
         "museum" => Box::new(MuseumScene),
-        _ => Box::new(CustomScene),
-    };
+        _ => bail!("Unknown scene '{name}', use custom, required or museum"),
+    })
+}
 
+// Everything but the output and the checkpoints, shared by render and animate
+pub fn settings(args: &SettingsArgs) -> Result<RenderSettings> {
     let width = args.resolution;
     let height = width;
+    let settings = RenderSettings {
+        spectral: args.spectral,
+        integrator: args.integrator,
+        sampler: args.sampler,
+        filter: args.filter,
+        seed: args.seed,
+        samples_per_pixel: args.spp,
+        adaptive: args.adaptive,
+        noise_threshold: args.noise_threshold,
+        min_samples: args.min_spp,
+        max_samples: args.max_spp,
+        denoise: args.denoise,
+        exposure: args.exposure,
+        white_balance: args.white_balance,
+        tone_mapper: args.tonemap,
+        output_transform: args.output_transform,
+        bloom: args.bloom,
+        vignette: args.vignette,
+        photons: args.photons,
+        ao_samples: args.ao_samples,
+        ao_distance: args.ao_distance,
+        ambient_occlusion: args.ambient_occlusion,
+        transmissive_shadows: args.transmissive_shadows,
+        ..RenderSettings::new(width, height)
+    };
+    settings.validate()?;
+    Ok(settings)
+}
 
-    if !args.animate {
-        let camera = scene.setup(&obj_path, &mut world, args.angle, width, height).await?;
-        let renderer = Renderer::new(camera, world);
-        renderer.render_scene(width, height);
-    } else {
-        render_animation(args, scene, &obj_path, width, height).await?;
+pub fn bind_coordinator(addr: &str) -> Result<Coordinator> {
+    let coordinator = Coordinator::bind(addr)?;
+    eprintln!("Waiting for workers on {}", coordinator.local_addr()?);
+    Ok(coordinator)
+}
+
+// Renders tiles for a coordinator until it hangs up. Every job names the scene
+// and the model URL, so the worker needs no config of its own.
+pub async fn run_worker(addr: String) -> Result<()> {
+    let handle = Handle::current();
+    tokio::task::spawn_blocking(move || {
+        // Animation frames all use the same model, it is only downloaded once
+        let mut model: Option<(String, TempDir, PathBuf)> = None;
+        distributed::run_worker(addr.as_str(), |job: &Job| {
+            if model.as_ref().is_none_or(|(url, _, _)| *url != job.model) {
+                let (dir, path) = handle.block_on(download_obj_with_assets(&job.model))?;
+                model = Some((job.model.clone(), dir, path));
+            }
+            let (_, _, obj_path) = model.as_ref().unwrap();
+            let aspect_ratio = job.settings.width as f32 / job.settings.height as f32;
+            let mut world = World::new();
+            let camera = handle.block_on(scene_by_name(&job.scene)?.setup(obj_path, &mut world, job.angle, aspect_ratio))?;
+            Ok(Renderer::new(camera, world))
+        })
+    })
+    .await?
+}
+
+// The first Ctrl-C stops after the tiles in flight and saves the checkpoint
+fn cancel_on_interrupt() -> CancelToken {
+    let cancel = CancelToken::new();
+    let on_interrupt = cancel.clone();
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c().await.ok();
+        eprintln!("\nStopping, press Ctrl-C again to quit right away");
+        on_interrupt.cancel();
+        tokio::signal::ctrl_c().await.ok();
+        std::process::exit(130);
+    });
+    cancel
+}
+
+// The image to the chosen path, the heatmap and AOVs next to it when enabled.
+// A crop window only replaces its part of an earlier image at the path.
+fn save_output(render: RenderOutput, settings: &RenderSettings, output: &Path, sub_image: bool) -> Result<()> {
+    let RenderOutput { pixels, samples, aovs, cancelled } = render;
+    if cancelled {
+        match &settings.checkpoint {
+            Some(path) => bail!("Render cancelled, checkpoint saved to {}, continue with --resume", path.display()),
+            None => bail!("Render cancelled"),
+        }
     }
 
+    let image = Image::new(settings.width, settings.height, &pixels);
+    match settings.crop {
+        Some(window) if sub_image => image.save_crop(output, settings, &window)?,
+        Some(window) => image.save_region(output, settings, &window)?,
+        None => image.save(output, settings)?,
+    }
+    eprint!("\nDone. Image saved to {}\n", output.display());
+
+    if settings.adaptive {
+        let total: u64 = samples.iter().map(|&n| n as u64).sum();
+        eprintln!("Average samples per pixel: {:.1}", total as f32 / samples.len() as f32);
+    }
+    if settings.sample_heatmap {
+        let path = output.with_file_name("heatmap.ppm");
+        image::write_ppm(&path, settings.width, settings.height, &adaptive::heatmap(&samples))?;
+        eprintln!("Sample heatmap saved to {}", path.display());
+    }
+    if let (true, Some(aovs)) = (settings.aovs, aovs) {
+        let path = output.with_file_name("aovs.exr");
+        aovs.write_exr(&path, &pixels)?;
+        eprintln!("AOVs saved to {}", path.display());
+    }
     Ok(())
 }
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use tokio::runtime::Handle;
+
+use crate::app::config::Config;
+use crate::app::run::scene_by_name;
+
+use raytracer::io::asset_loader::download_obj_with_assets;
+use raytracer::objects::world::World;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::server::{self, JobRequest};
+
+pub async fn serve(addr: &str, config: Config) -> Result<()> {
+    let listener = TcpListener::bind(addr).await.with_context(|| format!("Failed to listen on {addr}"))?;
+    eprintln!("Accepting render jobs on http://{}/jobs", listener.local_addr()?);
+
+    let handle = Handle::current();
+    // Every model is downloaded once and kept for later jobs
+    let mut downloads: HashMap<String, (TempDir, PathBuf)> = HashMap::new();
+    server::serve(listener, move |request: &JobRequest| {
+        let obj_path = match (&request.model, &request.file) {
+            (Some(model), _) => {
+                let Some(url) = config.models.get(model) else {
+                    bail!("Model '{model}' not found in config");
+                };
+                if !downloads.contains_key(url) {
+                    let download = handle.block_on(download_obj_with_assets(url))?;
+                    downloads.insert(url.clone(), download);
+                }
+                downloads[url].1.clone()
+            }
+            (None, Some(file)) => file.clone(),
+            (None, None) => bail!("A job needs either a model or a file"),
+        };
+        let aspect_ratio = request.settings.width as f32 / request.settings.height as f32;
+        let mut world = World::new();
+        let camera = handle.block_on(scene_by_name(&request.scene)?.setup(&obj_path, &mut world, request.angle, aspect_ratio))?;
+        Ok(Renderer::new(camera, world))
+    })
+    .await
+}
@@ -81,7 +81,10 @@ impl Camera {
         )
     }
 
-    pub fn default() -> Self {
+}
+
+impl Default for Camera {
+    fn default() -> Self {
         let aspect_ratio = 16.0 / 9.0;
         
         Camera::new(aspect_ratio)
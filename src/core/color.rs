@@ -43,6 +43,16 @@ pub fn format_color(pixel_color: Color, samples_per_pixel: i32) -> String {
     )
 }
 
+// 8-bit PPM triple of a colour already encoded for display
+pub fn format_display(encoded: Color) -> String {
+    format!(
+        "{} {} {}",
+        (256.0 * common::clamp(encoded.x(), 0.0, 0.999)) as i32,
+        (256.0 * common::clamp(encoded.y(), 0.0, 0.999)) as i32,
+        (256.0 * common::clamp(encoded.z(), 0.0, 0.999)) as i32,
+    )
+}
+
 pub fn clamp_add(color1: &Color, color2: &Color) -> Color {
     let r = (color1.x() + color2.x()).clamp(0.0, 1.0);
     let g = (color1.y() + color2.y()).clamp(0.0, 1.0);
@@ -50,3 +60,8 @@ pub fn clamp_add(color1: &Color, color2: &Color) -> Color {
     
     Color::new(r, g, b)
 }
+
+// Relative luminance of linear Rec. 709 primaries
+pub fn luminance(c: Color) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
@@ -5,7 +5,7 @@ use crate::core::vec3::Vec3;
 // Constants
  
 pub use std::f32::consts::PI;
-pub use std::f32::INFINITY;
+pub const INFINITY: f32 = f32::INFINITY;
  
 // Utility functions
  
@@ -0,0 +1,151 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::core::color::Color;
+
+static NEXT_MEDIUM_ID: AtomicU32 = AtomicU32::new(1);
+
+// Deepest nesting a path keeps track of, e.g. air bubble in water in glass
+const MAX_MEDIA: usize = 4;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Dispersion {
+    // n(λ) = a + b / λ², λ in micrometres
+    Cauchy { a: f32, b: f32 },
+    // n²(λ) = 1 + Σ bᵢλ² / (λ² - cᵢ), λ in micrometres
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.039_612, 0.231_792_34, 1.010_469_4],
+        c: [0.006_000_699, 0.020_017_914, 103.560_65],
+    };
+
+    pub const SF11: Dispersion = Dispersion::Sellmeier {
+        b: [1.737_597, 0.313_747_35, 1.898_781],
+        c: [0.013_188_707, 0.062_306_814, 155.236_3],
+    };
+
+    pub fn ior(&self, wavelength_nm: f32) -> f32 {
+        let l = wavelength_nm * 1.0e-3;
+        let l2 = l * l;
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f32 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+// Interior of a closed dielectric surface. Where media overlap, the one with
+// the highest priority wins (Schmidt and Budge, "Simple Nested Dielectrics").
+#[derive(Clone, Copy, Debug)]
+pub struct Medium {
+    id: u32,
+    pub ior: f32,
+    pub priority: u32,
+    pub absorption: Color,
+    pub dispersion: Option<Dispersion>,
+}
+
+impl Medium {
+    pub fn new(ior: f32) -> Self {
+        Self {
+            id: NEXT_MEDIUM_ID.fetch_add(1, Ordering::Relaxed),
+            ior,
+            priority: 0,
+            absorption: Color::ZERO,
+            dispersion: None,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn ior_at(&self, wavelength: Option<f32>) -> f32 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(lambda)) => dispersion.ior(lambda),
+            _ => self.ior,
+        }
+    }
+
+    pub fn transmittance(&self, distance: f32) -> Color {
+        Color::new(
+            (-self.absorption.x() * distance).exp(),
+            (-self.absorption.y() * distance).exp(),
+            (-self.absorption.z() * distance).exp(),
+        )
+    }
+}
+
+// Media the path is currently inside of, travels with the ray
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MediumStack {
+    media: [Option<Medium>; MAX_MEDIA],
+    len: usize,
+}
+
+impl MediumStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.iter().any(|m| m.id == id)
+    }
+
+    // Highest priority medium, the most recently entered one on ties
+    pub fn current(&self) -> Option<&Medium> {
+        self.iter().max_by_key(|m| m.priority)
+    }
+
+    pub fn current_ior(&self, wavelength: Option<f32>) -> f32 {
+        self.current().map_or(1.0, |m| m.ior_at(wavelength))
+    }
+
+    pub fn transmittance(&self, distance: f32) -> Color {
+        self.current()
+            .map_or(Color::new(1.0, 1.0, 1.0), |m| m.transmittance(distance))
+    }
+
+    pub fn entered(&self, medium: Medium) -> Self {
+        let mut stack = *self;
+        if stack.contains(medium.id) {
+            return stack;
+        }
+        if stack.len == MAX_MEDIA {
+            // Forget the outermost medium rather than the one being entered
+            stack.media.rotate_left(1);
+            stack.len -= 1;
+        }
+        stack.media[stack.len] = Some(medium);
+        stack.len += 1;
+        stack
+    }
+
+    pub fn exited(&self, id: u32) -> Self {
+        let mut stack = *self;
+        let index = stack.iter().position(|m| m.id == id);
+        if let Some(index) = index {
+            stack.media[index..stack.len].rotate_left(1);
+            stack.len -= 1;
+            stack.media[stack.len] = None;
+        }
+        stack
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Medium> {
+        self.media[..self.len].iter().flatten()
+    }
+}
@@ -1,10 +1,15 @@
+use crate::core::color::Color;
+use crate::core::medium::{Medium, MediumStack};
+use crate::core::spectrum::SampledWavelengths;
 use crate::core::vec3::{Point3, Vec3};
 
 #[derive(Default)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
-    current_ior: f32, // index of retraction
+    media: MediumStack, // media the ray travels through, innermost decides the ior
+    wavelength: Option<f32>, // nanometres, set once a dispersive medium was hit
+    spectral: Option<SampledWavelengths>, // colours are spectral samples when set
 }
 
 impl Ray {
@@ -12,7 +17,9 @@ impl Ray {
         Ray {
             origin,
             direction,
-            current_ior: 1.0,
+            media: MediumStack::new(),
+            wavelength: None,
+            spectral: None,
         }
     }
 
@@ -20,10 +27,35 @@ impl Ray {
         Ray {
             origin,
             direction,
-            current_ior: ior,
+            media: MediumStack::new().entered(Medium::new(ior)),
+            wavelength: None,
+            spectral: None,
         }
     }
 
+    pub fn with_wavelength(mut self, wavelength: Option<f32>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub fn with_spectral(mut self, spectral: Option<SampledWavelengths>) -> Self {
+        self.spectral = spectral;
+        self
+    }
+
+    pub fn with_media(mut self, media: MediumStack) -> Self {
+        self.media = media;
+        self
+    }
+
+    // Secondary ray that keeps the wavelength and media of this path
+    pub fn scattered(&self, origin: Point3, direction: Vec3) -> Ray {
+        Ray::new(origin, direction)
+            .with_wavelength(self.wavelength)
+            .with_spectral(self.spectral)
+            .with_media(self.media)
+    }
+
     pub fn origin(&self) -> Point3 {
         self.origin
     }
@@ -33,7 +65,24 @@ impl Ray {
     }
 
     pub fn current_ior(&self) -> f32 {
-        self.current_ior
+        self.media.current_ior(self.wavelength)
+    }
+
+    pub fn media(&self) -> &MediumStack {
+        &self.media
+    }
+
+    // Absorption along the first t units of the ray inside the current medium
+    pub fn transmittance(&self, t: f32) -> Color {
+        self.media.transmittance(t * self.direction.length())
+    }
+
+    pub fn wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
+
+    pub fn spectral(&self) -> Option<&SampledWavelengths> {
+        self.spectral.as_ref()
     }
 
     pub fn at(&self, t: f32) -> Point3 {
@@ -0,0 +1,379 @@
+use rand::rngs::SmallRng;
+use clap::ValueEnum;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::core::common::PI;
+use crate::core::vec3::Vec3;
+
+// Largest f32 below one, so scaled integers never round up to 1.0
+const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum SamplerKind {
+    // Uncorrelated uniform random numbers
+    Independent,
+    // Jittered strata, shuffled per pixel and dimension
+    Stratified,
+    // Owen-scrambled radical inverses in prime bases
+    Halton,
+    // Owen-scrambled Sobol points, shuffled per dimension (Burley 2020)
+    #[default]
+    Sobol,
+}
+
+// Source of the random numbers for one pixel sample. Every call consumes the
+// next dimension(s), so the n-th decision of a path always sees the n-th
+// dimension of the sequence and strata line up across the samples of a pixel.
+// The numbers depend only on seed, pixel, sample index and dimension, never on
+// which thread asks for them or in which order pixels are rendered.
+pub trait Sampler: Send {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+pub fn create(kind: SamplerKind, samples_per_pixel: usize, seed: u64) -> Box<dyn Sampler> {
+    create_in_rounds(kind, &[samples_per_pixel], seed)
+}
+
+// For renders that may stop taking samples after any of the rounds, given by
+// the sample count each ends at. The stratified sampler stratifies every round
+// on its own, the prefixes of the other sequences are well stratified anyway.
+pub fn create_in_rounds(kind: SamplerKind, rounds: &[usize], seed: u64) -> Box<dyn Sampler> {
+    match kind {
+        SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+        SamplerKind::Stratified => Box::new(StratifiedSampler::in_rounds(rounds, seed)),
+        SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+        SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+    }
+}
+
+// Uniformly distributed direction, the same as vec3::random_unit_vector
+pub fn sample_unit_vector((u, v): (f32, f32)) -> Vec3 {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Uniformly distributed point inside the unit sphere
+pub fn sample_in_unit_sphere(u: (f32, f32), radius: f32) -> Vec3 {
+    sample_unit_vector(u) * radius.cbrt()
+}
+
+pub struct IndependentSampler {
+    state: SampleState,
+    rng: SmallRng,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: SampleState::new(seed),
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+        self.rng = self.state.rng();
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.random()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.random(), self.rng.random())
+    }
+}
+
+// Position of the current sample inside the pixel's sequence
+#[derive(Clone, Copy)]
+struct SampleState {
+    seed: u64,
+    pixel: u64,
+    index: usize,
+    dimension: u64,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, x: usize, y: usize, index: usize) {
+        self.pixel = hash(hash(self.seed, x as u64), y as u64);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    // Generator private to the current pixel sample
+    fn rng(&self) -> SmallRng {
+        SmallRng::seed_from_u64(hash(self.pixel, self.index as u64))
+    }
+
+    // Hash of the pixel and the dimension about to be consumed
+    fn next(&mut self, dimensions: u64) -> u64 {
+        let key = hash(self.pixel, self.dimension);
+        self.dimension += dimensions;
+        key
+    }
+}
+
+pub struct StratifiedSampler {
+    // Sample count at the end of every round, the strata of each round cover
+    // the whole domain. Later samples go round again.
+    rounds: Vec<usize>,
+    // Round of the current sample, its position in there and the round's size
+    round: usize,
+    offset: usize,
+    samples: usize,
+    state: SampleState,
+    // Jitter inside the strata
+    rng: SmallRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self::in_rounds(&[samples_per_pixel], seed)
+    }
+
+    pub fn in_rounds(rounds: &[usize], seed: u64) -> Self {
+        let mut ends: Vec<usize> = rounds.iter().map(|&end| end.max(1)).collect();
+        ends.sort_unstable();
+        ends.dedup();
+        if ends.is_empty() {
+            ends.push(1);
+        }
+        Self {
+            rounds: ends,
+            round: 0,
+            offset: 0,
+            samples: 1,
+            state: SampleState::new(seed),
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+        self.rng = self.state.rng();
+        let index = index % self.rounds[self.rounds.len() - 1];
+        self.round = self.rounds.partition_point(|&end| end <= index);
+        let start = if self.round == 0 { 0 } else { self.rounds[self.round - 1] };
+        self.offset = index - start;
+        self.samples = self.rounds[self.round] - start;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        // Every round shuffles its strata differently
+        let key = self.state.next(1) ^ self.round as u64;
+        let n = self.samples;
+        let stratum = permutation_element(self.offset, n, key);
+        (stratum as f32 + self.rng.random::<f32>()) / n as f32
+    }
+
+    // Strata on a square grid, each sample takes one of a shuffle of all of
+    // them. Where the samples do not fill the grid, a random subset stays empty.
+    fn get_2d(&mut self) -> (f32, f32) {
+        let key = self.state.next(2) ^ self.round as u64;
+        let grid = (self.samples as f32).sqrt().ceil() as usize;
+        let stratum = permutation_element(self.offset, grid * grid, key);
+        let (sx, sy) = (stratum % grid, stratum / grid);
+        (
+            (sx as f32 + self.rng.random::<f32>()) / grid as f32,
+            (sy as f32 + self.rng.random::<f32>()) / grid as f32,
+        )
+    }
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109,
+    113, 127, 131,
+];
+
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { state: SampleState::new(seed) }
+    }
+
+    fn sample(&self, dimension: u64, key: u64) -> f32 {
+        let base = PRIMES[dimension as usize % PRIMES.len()];
+        owen_scrambled_radical_inverse(base, self.state.index as u64, key)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let dimension = self.state.dimension;
+        let key = self.state.next(1);
+        self.sample(dimension, key)
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let dimension = self.state.dimension;
+        let key = self.state.next(2);
+        (self.sample(dimension, key), self.sample(dimension + 1, hash(key, 1)))
+    }
+}
+
+// Radical inverse with the digits Owen-scrambled by a per-pixel key. Without
+// scrambling the higher prime bases line up along diagonals at low sample
+// counts and neighbouring pixels would repeat each other.
+fn owen_scrambled_radical_inverse(base: u32, mut a: u64, key: u64) -> f32 {
+    let base = base as u64;
+    let inv_base = 1.0 / base as f64;
+    let mut reversed = 0u64;
+    let mut inv_base_n = 1.0f64;
+    // Keeps going past the last digit of a, its leading zeros get scrambled too
+    while 1.0 - (inv_base_n as f32) < 1.0 {
+        let next = a / base;
+        let digit = (a - next * base) as usize;
+        let digit = permutation_element(digit, base as usize, mix_bits(key ^ reversed));
+        reversed = reversed * base + digit as u64;
+        inv_base_n *= inv_base;
+        a = next;
+    }
+    ((reversed as f64 * inv_base_n) as f32).min(ONE_MINUS_EPSILON)
+}
+
+// Padded Sobol sampler: every 1D or 2D request uses the first two Sobol
+// dimensions, with the index shuffled and the result Owen-scrambled by a hash
+// of pixel and dimension. Keeps the stratification of every pair without
+// direction number tables for higher dimensions.
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        Self { state: SampleState::new(seed) }
+    }
+
+    fn shuffled_index(&self, key: u64) -> u32 {
+        nested_uniform_scramble(self.state.index as u32, hash(key, 0x73687566) as u32)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: usize, y: usize, index: usize) {
+        self.state.start(x, y, index);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        let key = self.state.next(1);
+        let index = self.shuffled_index(key);
+        to_unit_float(nested_uniform_scramble(index.reverse_bits(), hash(key, 0) as u32))
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        let key = self.state.next(2);
+        let index = self.shuffled_index(key);
+        (
+            to_unit_float(nested_uniform_scramble(index.reverse_bits(), hash(key, 0) as u32)),
+            to_unit_float(nested_uniform_scramble(sobol_second_dimension(index), hash(key, 1) as u32)),
+        )
+    }
+}
+
+// Second Sobol dimension, its generator matrix is the binary Pascal matrix
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Owen scrambling as a hash over reversed bits (Laine and Karras 2011,
+// constants from Burley 2020)
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+fn to_unit_float(x: u32) -> f32 {
+    (x as f32 * (1.0 / 4_294_967_296.0)).min(ONE_MINUS_EPSILON)
+}
+
+// Element i of a random permutation of 0..n chosen by seed, without storing
+// the permutation (Kensler 2013)
+fn permutation_element(mut i: usize, n: usize, seed: u64) -> usize {
+    let (l, p) = (n as u32, seed as u32);
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        let mut x = i as u32;
+        x ^= p;
+        x = x.wrapping_mul(0xe170893d);
+        x ^= p >> 16;
+        x ^= (x & w) >> 4;
+        x ^= p >> 8;
+        x = x.wrapping_mul(0x0929eb3f);
+        x ^= p >> 23;
+        x ^= (x & w) >> 1;
+        x = x.wrapping_mul(1 | p >> 27);
+        x = x.wrapping_mul(0x6935fa69);
+        x ^= (x & w) >> 11;
+        x = x.wrapping_mul(0x74dcb303);
+        x ^= (x & w) >> 2;
+        x = x.wrapping_mul(0x9e501cc3);
+        x ^= (x & w) >> 2;
+        x = x.wrapping_mul(0xc860a3df);
+        x &= w;
+        x ^= x >> 5;
+        if x < l {
+            return (x.wrapping_add(p) % l) as usize;
+        }
+        i = x as usize;
+    }
+}
+
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5d329728ea185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81dadef4bc2dd44d);
+    v ^= v >> 33;
+    v
+}
+
+fn hash(a: u64, b: u64) -> u64 {
+    mix_bits(mix_bits(a) ^ b.wrapping_mul(0x9e3779b97f4a7c15))
+}
@@ -0,0 +1,197 @@
+use std::sync::OnceLock;
+
+use crate::core::color::Color;
+use crate::core::ray::Ray;
+
+// Visible range used when sampling wavelengths (nanometres)
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// Piecewise gaussian used by the CIE 1931 fit of Wyman, Sloan and Shirley (2013)
+fn lobe(lambda: f32, mu: f32, sigma_low: f32, sigma_high: f32) -> f32 {
+    let sigma = if lambda < mu { sigma_low } else { sigma_high };
+    let t = (lambda - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+pub fn cie_x(lambda: f32) -> f32 {
+    1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2)
+}
+
+pub fn cie_y(lambda: f32) -> f32 {
+    0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1)
+}
+
+pub fn cie_z(lambda: f32) -> f32 {
+    1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8)
+}
+
+pub fn xyz_to_linear_srgb(xyz: Color) -> Color {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Color::new(
+        3.240_454 * x - 1.537_139 * y - 0.498_531 * z,
+        -0.969_266 * x + 1.876_011 * y + 0.041_556 * z,
+        0.055_643 * x - 0.204_026 * y + 1.057_225 * z,
+    )
+}
+
+pub fn linear_srgb_to_xyz(rgb: Color) -> Color {
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+    Color::new(
+        0.412_456 * r + 0.357_576 * g + 0.180_438 * b,
+        0.212_673 * r + 0.715_152 * g + 0.072_175 * b,
+        0.019_334 * r + 0.119_192 * g + 0.950_304 * b,
+    )
+}
+
+fn wavelength_to_linear_srgb(lambda: f32) -> Color {
+    let rgb = xyz_to_linear_srgb(Color::new(cie_x(lambda), cie_y(lambda), cie_z(lambda)));
+    Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0))
+}
+
+pub fn sample_wavelength(u: f32) -> f32 {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+// RGB weight of a single uniformly sampled wavelength. The weights average to
+// white over the visible range, so a dispersed path stays unbiased in RGB.
+pub fn wavelength_weight(lambda: f32) -> Color {
+    static MEAN: OnceLock<Color> = OnceLock::new();
+    let mean = MEAN.get_or_init(|| {
+        let steps = 1000;
+        let mut sum = Color::ZERO;
+        for i in 0..steps {
+            sum += wavelength_to_linear_srgb(sample_wavelength((i as f32 + 0.5) / steps as f32));
+        }
+        sum / steps as f32
+    });
+
+    let rgb = wavelength_to_linear_srgb(lambda);
+    Color::new(rgb.x() / mean.x(), rgb.y() / mean.y(), rgb.z() / mean.z())
+}
+
+// Hero wavelength sampling (Wilkie et al. 2014) with one sample per colour
+// channel: in spectral mode a Color holds the values at these wavelengths.
+pub const SPECTRAL_SAMPLES: usize = 3;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SampledWavelengths {
+    lambda: [f32; SPECTRAL_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample(u: f32) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = sample_wavelength(u);
+        let mut lambda = [hero; SPECTRAL_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate().skip(1) {
+            let shifted = hero + i as f32 * range / SPECTRAL_SAMPLES as f32;
+            *l = if shifted > LAMBDA_MAX { shifted - range } else { shifted };
+        }
+        Self { lambda }
+    }
+
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    pub fn lambda(&self, i: usize) -> f32 {
+        self.lambda[i]
+    }
+
+    // Weight that keeps only the hero wavelength, for wavelength-dependent
+    // events such as dispersion that the other samples cannot follow
+    pub fn hero_only() -> Color {
+        Color::new(SPECTRAL_SAMPLES as f32, 0.0, 0.0)
+    }
+
+    pub fn map(&self, f: impl Fn(f32) -> f32) -> Color {
+        Color::new(f(self.lambda[0]), f(self.lambda[1]), f(self.lambda[2]))
+    }
+
+    // Monte Carlo estimate of the spectrum's colour, normalised so that a
+    // constant spectrum of 1 maps to RGB white
+    pub fn to_rgb(&self, values: Color) -> Color {
+        let mut xyz = Color::ZERO;
+        for (i, v) in [values.x(), values.y(), values.z()].into_iter().enumerate() {
+            let l = self.lambda[i];
+            xyz += v * Color::new(cie_x(l), cie_y(l), cie_z(l));
+        }
+        let rgb = xyz_to_linear_srgb(xyz / SPECTRAL_SAMPLES as f32);
+        let white = white_rgb();
+        Color::new(rgb.x() / white.x(), rgb.y() / white.y(), rgb.z() / white.z())
+    }
+}
+
+fn white_rgb() -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    *WHITE.get_or_init(|| {
+        let steps = 1000;
+        let mut xyz = Color::ZERO;
+        for i in 0..steps {
+            let l = sample_wavelength((i as f32 + 0.5) / steps as f32);
+            xyz += Color::new(cie_x(l), cie_y(l), cie_z(l));
+        }
+        xyz_to_linear_srgb(xyz / steps as f32)
+    })
+}
+
+// Smits (1999), "An RGB-to-Spectrum Conversion for Reflectances", ten equal
+// bins over the visible range
+const SMITS_WHITE: [f32; 10] = [1.0, 1.0, 0.9999, 0.9993, 0.9992, 0.9998, 1.0, 1.0, 1.0, 1.0];
+const SMITS_CYAN: [f32; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0, 0.0, 0.0];
+const SMITS_MAGENTA: [f32; 10] = [1.0, 1.0, 0.9685, 0.2229, 0.0, 0.0458, 0.8369, 1.0, 1.0, 0.9959];
+const SMITS_YELLOW: [f32; 10] = [0.0001, 0.0, 0.1088, 0.6651, 1.0, 1.0, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f32; 10] = [0.1012, 0.0515, 0.0, 0.0, 0.0, 0.0, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f32; 10] = [0.0, 0.0, 0.0273, 0.7937, 1.0, 0.9418, 0.1719, 0.0, 0.0, 0.0025];
+const SMITS_BLUE: [f32; 10] = [1.0, 1.0, 0.8916, 0.3323, 0.0, 0.0, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// Smits' spectra are fitted for reflectances up to 1. Brighter colours such as
+// light intensities are upsampled at a maximum of 1 and scaled back.
+pub fn rgb_to_spectrum(rgb: Color, lambda: f32) -> f32 {
+    let scale = rgb.x().max(rgb.y()).max(rgb.z());
+    if scale > 1.0 {
+        scale * smits(rgb / scale, lambda)
+    } else {
+        smits(rgb, lambda)
+    }
+}
+
+fn smits(rgb: Color, lambda: f32) -> f32 {
+    let bin = ((lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN) * 10.0).clamp(0.0, 9.0) as usize;
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+
+    let value = if r <= g && r <= b {
+        let base = r * SMITS_WHITE[bin];
+        if g <= b {
+            base + (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin]
+        } else {
+            base + (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin]
+        }
+    } else if g <= r && g <= b {
+        let base = g * SMITS_WHITE[bin];
+        if r <= b {
+            base + (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin]
+        } else {
+            base + (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin]
+        }
+    } else {
+        let base = b * SMITS_WHITE[bin];
+        if r <= g {
+            base + (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin]
+        } else {
+            base + (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin]
+        }
+    };
+    value.max(0.0)
+}
+
+// Converts an RGB quantity into the channels the ray carries: unchanged for
+// RGB rendering, the upsampled spectrum at the sampled wavelengths otherwise
+pub fn for_ray(rgb: Color, r: &Ray) -> Color {
+    match r.spectral() {
+        Some(wavelengths) => wavelengths.map(|l| rgb_to_spectrum(rgb, l)),
+        None => rgb,
+    }
+}
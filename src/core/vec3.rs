@@ -15,6 +15,10 @@ impl Vec3 {
         Vec3 { e: [x, y, z] }
     }
 
+    pub const fn splat(v: f32) -> Vec3 {
+        Vec3 { e: [v, v, v] }
+    }
+
     pub fn random() -> Vec3 {
         Vec3::new(
             common::random_double(),
@@ -82,6 +86,19 @@ impl Vec3 {
         self.x().max(self.y()).max(self.z())
     }
 
+    // Component 0, 1 or 2
+    pub fn axis(&self, axis: usize) -> f32 {
+        self.e[axis]
+    }
+
+    pub fn min(&self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x().min(other.x()), self.y().min(other.y()), self.z().min(other.z()))
+    }
+
+    pub fn max(&self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x().max(other.x()), self.y().max(other.y()), self.z().max(other.z()))
+    }
+
     pub fn rotate_x(&self, angle: f32) -> Vec3 {
         let (s, c) = angle.sin_cos();
         Vec3::new(
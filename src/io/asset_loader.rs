@@ -31,7 +31,7 @@ pub async fn download_obj_with_assets(obj_url: &str) -> Result<(TempDir, PathBuf
         fs::write(&mtl_path, &mtl_bytes)?;
 
         let mtl_text = String::from_utf8_lossy(&mtl_bytes);
-        let tex_re = Regex::new(r"^map_Kd\s+(.+)$").unwrap();
+        let tex_re = Regex::new(r"^(?:map_Kd|map_d)\s+(.+)$").unwrap();
 
         for line in mtl_text.lines() {
             if let Some(caps) = tex_re.captures(line) {
@@ -1,21 +1,21 @@
 use tobj;
 use std::sync::Arc;
 use anyhow::Result;
-use std::path::PathBuf; 
 use std::path::Path; 
 use std::collections::HashMap;
 
 use crate::core::vec3::{Vec3, Point3};
 use crate::core::color::Color;
 
-use crate::material::material::{Material, TexturedMaterial, Metal};
+use crate::material::material::{AlphaMode, Cutout, Material, TexturedMaterial, Metal};
 use crate::material::texture::Texture;                                                                                                                                                                  
 
+use crate::objects::hittable::Hittable;
 use crate::objects::triangle::Triangle;
 use crate::objects::world::World;
 
 pub async fn load_obj_from_path(
-    path: &PathBuf, 
+    path: &Path, 
     world: &mut World, 
     mat: Arc<dyn Material>, 
     rotation: Vec3,
@@ -36,20 +36,45 @@ pub async fn load_obj_from_path(
     let mut material_map: HashMap<usize, Arc<dyn Material>> = HashMap::new();
 
     for (i, mat) in materials.iter().enumerate() {
+        // Cutouts come from the alpha channel or map_d, a d below one blends the
+        // whole material with what lies behind
+        let opacity = mat.dissolve.unwrap_or(1.0);
+        let alpha_mode = if opacity < 1.0 { AlphaMode::Blend } else { AlphaMode::Test(0.5) };
+        let mut opacity_map = None;
+        if let Some(ref map_d) = mat.dissolve_texture {
+            let opacity_path = base_dir.join(map_d);
+            if opacity_path.exists() {
+                opacity_map = Some(Arc::new(Texture::new(image::open(opacity_path)?.to_rgba8())));
+            } else {
+                eprintln!("Warning: Opacity map not found: {:?}", opacity_path);
+            }
+        }
+
         if let Some(ref map_kd) = mat.diffuse_texture {
             let texture_path = base_dir.join(map_kd);
             if texture_path.exists() {
                 let img = image::open(texture_path)?;
                 let img = img.to_rgba8();
                 let texture = Arc::new(Texture::new(img));
-                let textured_material = Arc::new(TexturedMaterial::new(texture));
-                material_map.insert(i, textured_material);
+                let mut textured_material = TexturedMaterial::new(texture).with_opacity(opacity).with_alpha_mode(alpha_mode);
+                if let Some(opacity_map) = opacity_map {
+                    textured_material = textured_material.with_opacity_map(opacity_map);
+                }
+                material_map.insert(i, Arc::new(textured_material));
             } else {
                 eprintln!("Warning: Texture not found: {:?}", texture_path);
             }
         } else {
-            let default_mat = Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.3));
-            material_map.insert(i, default_mat);
+            let default_mat: Arc<dyn Material> = Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.3));
+            if opacity < 1.0 || opacity_map.is_some() {
+                let mut cutout = Cutout::new(default_mat).with_opacity(opacity).with_alpha_mode(alpha_mode);
+                if let Some(opacity_map) = opacity_map {
+                    cutout = cutout.with_opacity_map(opacity_map);
+                }
+                material_map.insert(i, Arc::new(cutout));
+            } else {
+                material_map.insert(i, default_mat);
+            }
         }
     }
 
@@ -95,6 +120,8 @@ pub async fn load_obj_from_path(
             .and_then(|id| material_map.get(&id).cloned())
             .unwrap_or_else(|| mat.clone());
 
+        // Every model of the file becomes one object
+        let mut triangles: Vec<Box<dyn Hittable>> = Vec::new();
         for triangle in indices.chunks(3) {
             if triangle.len() == 3 {
                 let i0 = triangle[0] as usize;
@@ -109,13 +136,14 @@ pub async fn load_obj_from_path(
                 let uv1 = texcoords.get(i1).cloned().unwrap_or((0.0, 0.0));
                 let uv2 = texcoords.get(i2).cloned().unwrap_or((0.0, 0.0));
 
-                world.add_hittable(Box::new(Triangle::new(
+                triangles.push(Box::new(Triangle::new(
                     a, b, c,
                     uv0, uv1, uv2,
                     selected_material.clone()
                 )));
             }
         }
+        world.add_object(triangles);
     }
 
     // Return transformed bounding box
@@ -123,3 +151,56 @@ pub async fn load_obj_from_path(
     let new_max = ((max - center) * scale).rotate_xyz(rotation) + translation;
     Ok((new_min, new_max))
 }
+
+// What an OBJ file holds, in its own units before a scene scales it
+pub struct ObjInfo {
+    // Name and triangle count of every model in the file
+    pub meshes: Vec<(String, usize)>,
+    pub vertices: usize,
+    pub triangles: usize,
+    pub bounds: (Point3, Point3),
+    pub materials: Vec<MaterialInfo>,
+}
+
+pub struct MaterialInfo {
+    pub name: String,
+    pub diffuse: Option<Color>,
+    pub texture: Option<String>,
+}
+
+pub fn inspect_obj(path: &Path) -> Result<ObjInfo> {
+    let (models, materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            triangulate: true,
+            single_index: true,
+            ..Default::default()
+        },
+    )?;
+    // A missing MTL file only means there are no materials to list
+    let materials = materials.unwrap_or_default();
+
+    let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+    let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+    for p in models.iter().flat_map(|model| model.mesh.positions.chunks(3)) {
+        let p = Point3::new(p[0], p[1], p[2]);
+        min = min.min(p);
+        max = max.max(p);
+    }
+
+    let meshes: Vec<(String, usize)> = models.iter().map(|model| (model.name.clone(), model.mesh.indices.len() / 3)).collect();
+    Ok(ObjInfo {
+        vertices: models.iter().map(|model| model.mesh.positions.len() / 3).sum(),
+        triangles: meshes.iter().map(|(_, triangles)| triangles).sum(),
+        meshes,
+        bounds: (min, max),
+        materials: materials
+            .into_iter()
+            .map(|mat| MaterialInfo {
+                name: mat.name,
+                diffuse: mat.diffuse.map(|[r, g, b]| Color::new(r, g, b)),
+                texture: mat.diffuse_texture,
+            })
+            .collect(),
+    })
+}
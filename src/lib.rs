@@ -4,6 +4,9 @@ pub mod core {
     pub mod ray;
     pub mod camera;
     pub mod common;
+    pub mod spectrum;
+    pub mod medium;
+    pub mod sampler;
 }
 
 pub mod objects {
@@ -12,11 +15,15 @@ pub mod objects {
     pub mod hittable;
     pub mod world;
     pub mod light;
+    pub mod aabb;
+    pub mod bvh;
 }
 
 pub mod material {
+    #[allow(clippy::module_inception)]
     pub mod material;
     pub mod texture;
+    pub mod conductor;
 }
 
 pub mod io {
@@ -26,5 +33,23 @@ pub mod io {
 
 pub mod renderer {
     pub mod scene;
+    #[allow(clippy::module_inception)]
     pub mod renderer;
+    pub mod settings;
+    pub mod integrator;
+    pub mod photon_map;
+    pub mod bdpt;
+    pub mod adaptive;
+    pub mod film;
+    pub mod aov;
+    pub mod denoise;
+    pub mod postprocess;
+    pub mod progress;
+    pub mod image;
+    pub mod checkpoint;
+    pub mod distributed;
+    pub mod server;
+    pub mod benchmark;
+    pub mod compare;
+    pub mod debug;
 }
@@ -1,14 +1,26 @@
 mod app;
 use anyhow::Result;
-use clap::Parser; 
+use clap::Parser;
 
-use app::args::Args;
+use app::args::{Cli, Command};
 use app::config::load_config;
-use app::run::run_or_animate;
 
+// Errors end up here and exit with code 1, printed with their causes by anyhow
 #[tokio::main]
 async fn main() -> Result<()> {
-    let args = Args::parse();
-    let config = load_config(&args.config)?;
-    run_or_animate(args, config).await
+    let cli = Cli::parse();
+    // Workers get everything from the coordinator and need no config
+    let config = || load_config(&cli.config);
+    match cli.command {
+        Command::Render(args) => app::run::render(args, &config()?).await,
+        Command::Animate(args) => app::animation::animate(args, &config()?).await,
+        Command::ListModels => {
+            app::models::list_models(&config()?);
+            Ok(())
+        }
+        Command::Inspect { model } => app::models::inspect(&config()?, &model).await,
+        Command::Bench(args) => app::bench::bench(args),
+        Command::Serve { addr } => app::serve::serve(&addr, config()?).await,
+        Command::Worker { addr } => app::run::run_worker(addr).await,
+    }
 }
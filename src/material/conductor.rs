@@ -0,0 +1,72 @@
+use crate::core::color::Color;
+
+// Measured complex index of refraction η = n + ik, Johnson and Christy (1972),
+// tabulated every 50 nm from 400 nm to 700 nm
+const TABLE_START: f32 = 400.0;
+const TABLE_STEP: f32 = 50.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Conductor {
+    n: [f32; 7],
+    k: [f32; 7],
+}
+
+// Wavelengths standing in for the RGB primaries when rendering without spectra
+const RGB_WAVELENGTHS: [f32; 3] = [610.0, 550.0, 465.0];
+
+impl Conductor {
+    pub const GOLD: Conductor = Conductor {
+        n: [1.47, 1.40, 0.97, 0.43, 0.25, 0.17, 0.16],
+        k: [1.95, 1.88, 1.87, 2.45, 2.98, 3.50, 3.95],
+    };
+
+    pub const COPPER: Conductor = Conductor {
+        n: [1.18, 1.17, 1.13, 1.02, 0.30, 0.21, 0.21],
+        k: [2.21, 2.39, 2.57, 2.58, 3.21, 3.67, 4.20],
+    };
+
+    pub const SILVER: Conductor = Conductor {
+        n: [0.05, 0.04, 0.05, 0.06, 0.06, 0.05, 0.04],
+        k: [2.10, 2.65, 3.09, 3.59, 4.00, 4.48, 4.84],
+    };
+
+    fn eta(&self, lambda: f32) -> (f32, f32) {
+        let last = self.n.len() - 1;
+        let x = ((lambda - TABLE_START) / TABLE_STEP).clamp(0.0, last as f32);
+        let i = (x as usize).min(last - 1);
+        let t = x - i as f32;
+        (
+            self.n[i] * (1.0 - t) + self.n[i + 1] * t,
+            self.k[i] * (1.0 - t) + self.k[i + 1] * t,
+        )
+    }
+
+    // Unpolarised Fresnel reflectance of the conductor seen from air
+    pub fn fresnel(&self, cos_theta: f32, lambda: f32) -> f32 {
+        let (n, k) = self.eta(lambda);
+        let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+
+        let t0 = n * n - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * n * n * k * k).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * cos_theta * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rs + rp)
+    }
+
+    pub fn fresnel_rgb(&self, cos_theta: f32) -> Color {
+        Color::new(
+            self.fresnel(cos_theta, RGB_WAVELENGTHS[0]),
+            self.fresnel(cos_theta, RGB_WAVELENGTHS[1]),
+            self.fresnel(cos_theta, RGB_WAVELENGTHS[2]),
+        )
+    }
+}
@@ -2,11 +2,18 @@ use std::sync::Arc;
 
 use crate::core::color::Color;
 use crate::core::ray::Ray;
-use crate::core::vec3::{self};
+use crate::core::vec3::{self, Vec3};
+use crate::core::common::PI;
+use crate::core::spectrum::{self, SampledWavelengths};
+use crate::core::medium::{Medium, MediumStack};
+use crate::core::sampler::{self, Sampler};
+
+pub use crate::core::medium::Dispersion;
 
 use crate::objects::hittable::HitRecord;
 
 use crate::material::texture::Texture;
+use crate::material::conductor::Conductor;
  
 pub trait Material: Send + Sync {
     fn scatter(
@@ -15,8 +22,55 @@ pub trait Material: Send + Sync {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool;
 
+    // BSDF for light arriving from wi and leaving towards wo, both pointing
+    // away from the surface. Zero for specular surfaces, which only scatter.
+    fn eval(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> Color {
+        Color::ZERO
+    }
+
+    // Solid angle density with which scatter picks wi when leaving towards wo
+    fn pdf(&self, _rec: &HitRecord, _wo: Vec3, _wi: Vec3) -> f32 {
+        0.0
+    }
+
+    // Boundaries of overlapped media that a ray continues through unchanged
+    fn passes_through(&self, _r_in: &Ray, _rec: &HitRecord) -> bool {
+        false
+    }
+
+    // Lets shadow rays carry on straight through the surface, keeping the colour
+    // in attenuation. Opaque surfaces block them.
+    fn transmit_shadow(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _attenuation: &mut Color,
+        _transmitted: &mut Ray,
+    ) -> bool {
+        false
+    }
+
+    // Whether a ray reaching the surface at texture coordinates (u, v) may stop
+    // there. Triangles skip hits where alpha cuts the surface away, for camera
+    // and shadow rays alike.
+    fn is_opaque_at(&self, _u: f32, _v: f32) -> bool {
+        true
+    }
+
+    // Probability that a ray stops at a hit, below one where alpha blends the
+    // surface. World::hit_sampled decides with the pixel's sampler.
+    fn coverage(&self, _rec: &HitRecord) -> f32 {
+        1.0
+    }
+
+    // Mirror-like surfaces that photons and caustic paths continue through
+    fn is_specular(&self) -> bool {
+        false
+    }
+
     fn albedo(&self) -> Color; 
 }
 
@@ -33,31 +87,45 @@ impl Lambertian {
 impl Material for Lambertian {
     fn scatter(
         &self,
-        _r_in: &Ray,
+        r_in: &Ray,
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
-        let mut scatter_direction = rec.normal + vec3::random_unit_vector();
+        let mut scatter_direction = rec.normal + sampler::sample_unit_vector(sampler.get_2d());
         
         if scatter_direction.near_zero() {
             scatter_direction = rec.normal;
         }
 
-        *attenuation = self.albedo;
-        *scattered = Ray::new(rec.p, scatter_direction);
+        *attenuation = spectrum::for_ray(self.albedo, r_in);
+        *scattered = r_in.scattered(rec.p, scatter_direction);
 
         true
     }
 
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        if same_hemisphere(rec, wo, wi) {
+            self.albedo / PI
+        } else {
+            Color::ZERO
+        }
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        cosine_pdf(rec, wo, wi)
+    }
+
     fn albedo(&self) -> Color {
-        return self.albedo;
+        self.albedo
     }
 }
 
 pub struct Metal {
     albedo: Color,
     fuzz: f32,
+    conductor: Option<Conductor>,
 }
  
 impl Metal {
@@ -65,6 +133,16 @@ impl Metal {
         Metal {
             albedo,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
+            conductor: None,
+        }
+    }
+
+    // Reflectance from measured optical constants instead of a fixed albedo
+    pub fn conductor(conductor: Conductor, fuzz: f32) -> Metal {
+        Metal {
+            albedo: conductor.fresnel_rgb(1.0),
+            fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
+            conductor: Some(conductor),
         }
     }
 }
@@ -76,33 +154,85 @@ impl Material for Metal {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
-        let reflected = vec3::reflect(vec3::unit_vector(r_in.direction()), rec.normal);
+        let unit_direction = vec3::unit_vector(r_in.direction());
+        let reflected = vec3::reflect(unit_direction, rec.normal);
 
-        *attenuation = self.albedo;
+        *attenuation = match self.conductor {
+            Some(conductor) => {
+                let cos_theta = vec3::dot(-unit_direction, rec.normal);
+                match r_in.spectral() {
+                    Some(wavelengths) => wavelengths.map(|l| conductor.fresnel(cos_theta, l)),
+                    None => conductor.fresnel_rgb(cos_theta),
+                }
+            }
+            None => spectrum::for_ray(self.albedo, r_in),
+        };
 
-        *scattered = Ray::new(rec.p, reflected + self.fuzz * vec3::random_in_unit_sphere());
+        let fuzz = sampler::sample_in_unit_sphere(sampler.get_2d(), sampler.get_1d());
+        *scattered = r_in.scattered(rec.p, reflected + self.fuzz * fuzz);
 
         vec3::dot(scattered.direction(), rec.normal) > 0.0
     }
 
+    fn is_specular(&self) -> bool {
+        true
+    }
+
     fn albedo(&self) -> Color {
-        return self.albedo;
+        self.albedo
     }
 }
 
 pub struct Glass {
     albedo: Color,
-    refractive_index: f32,
+    medium: Medium,
 }
 
 impl Glass {
     pub fn new(albedo: Color, ior: f32) -> Glass {
         Glass {
             albedo,
-            refractive_index: ior,
+            medium: Medium::new(ior),
         }
     }
+
+    // Beer–Lambert absorption coefficient per unit of distance travelled inside
+    pub fn with_absorption(mut self, absorption: Color) -> Glass {
+        self.medium.absorption = absorption;
+        self
+    }
+
+    // Wavelength-dependent IOR, replaces the constant one for sampled rays
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Glass {
+        self.medium.dispersion = Some(dispersion);
+        self
+    }
+
+    // Decides which medium wins where this one overlaps another, higher wins
+    pub fn with_priority(mut self, priority: u32) -> Glass {
+        self.medium.priority = priority;
+        self
+    }
+
+    pub fn medium(&self) -> &Medium {
+        &self.medium
+    }
+
+    // Media on the far side of the surface and whether the boundary is real,
+    // i.e. the medium deciding the ior changes when crossing it
+    fn crossing(&self, r_in: &Ray, rec: &HitRecord) -> (MediumStack, bool) {
+        let media = r_in.media();
+        let beyond = if rec.front_face {
+            media.entered(self.medium)
+        } else {
+            media.exited(self.medium.id())
+        };
+        let before = media.current().map(|m| m.id());
+        let after = beyond.current().map(|m| m.id());
+        (beyond, before != after)
+    }
 }
 
 impl Material for Glass {
@@ -112,73 +242,304 @@ impl Material for Glass {
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
         let incident = vec3::unit_vector(r_in.direction());
-        let mut normal = rec.normal;
-        let mut eta_i = r_in.current_ior();
-        let mut eta_t = self.refractive_index;
-        let mut cos_theta = vec3::dot(-incident, normal);
-
-        if cos_theta < 0.0 {
-            normal = -normal;
-            std::mem::swap(&mut eta_i, &mut eta_t);
-            cos_theta = vec3::dot(-incident, normal);
+        let (beyond, is_boundary) = self.crossing(r_in, rec);
+
+        if !is_boundary {
+            *scattered = r_in.scattered(rec.p, r_in.direction()).with_media(beyond);
+            *attenuation = Color::new(1.0, 1.0, 1.0);
+            return true;
         }
 
+        // The first dispersive hit picks the wavelength for the rest of the path
+        let mut spectral_weight = Color::new(1.0, 1.0, 1.0);
+        let mut wavelength = r_in.wavelength();
+        if wavelength.is_none() && self.medium.dispersion.is_some() {
+            let lambda = match r_in.spectral() {
+                Some(wavelengths) => {
+                    spectral_weight = SampledWavelengths::hero_only();
+                    wavelengths.hero()
+                }
+                None => {
+                    let lambda = spectrum::sample_wavelength(sampler.get_1d());
+                    spectral_weight = spectrum::wavelength_weight(lambda);
+                    lambda
+                }
+            };
+            wavelength = Some(lambda);
+        }
+
+        // rec.normal always faces the incoming ray
+        let normal = rec.normal;
+        let eta_i = r_in.media().current_ior(wavelength);
+        let eta_t = beyond.current_ior(wavelength);
+        let cos_theta = vec3::dot(-incident, normal).min(1.0);
+
         let eta = eta_i / eta_t;
         let refracted = vec3::refract(incident, normal, eta);
         let reflect_prob = if refracted.is_none() {
             1.0
         } else {
-            vec3::schlick(cos_theta, eta_t)
+            vec3::schlick(cos_theta, eta_t / eta_i)
         };
 
-        let direction = if rand::random::<f32>() < reflect_prob {
-            vec3::reflect(incident, normal)
+        *scattered = if sampler.get_1d() < reflect_prob {
+            r_in.scattered(rec.p, vec3::reflect(incident, normal))
         } else {
-            refracted.unwrap()
-        };
+            r_in.scattered(rec.p, refracted.unwrap()).with_media(beyond)
+        }
+        .with_wavelength(wavelength);
+        *attenuation = spectrum::for_ray(self.albedo, r_in) * spectral_weight;
+        true
+    }
 
-        *scattered = Ray::new(rec.p, direction);
-        *attenuation = self.albedo;
+    fn passes_through(&self, r_in: &Ray, rec: &HitRecord) -> bool {
+        !self.crossing(r_in, rec).1
+    }
+
+    // Refraction is ignored, only the tint and the absorption inside count
+    fn transmit_shadow(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        transmitted: &mut Ray,
+    ) -> bool {
+        let (beyond, is_boundary) = self.crossing(r_in, rec);
+        *attenuation = r_in.transmittance(rec.t);
+        if is_boundary {
+            *attenuation = *attenuation * spectrum::for_ray(self.albedo, r_in);
+        }
+        *transmitted = r_in.scattered(rec.p, r_in.direction()).with_media(beyond);
+        true
+    }
+
+    fn is_specular(&self) -> bool {
         true
     }
 
     fn albedo(&self) -> Color {
-        return self.albedo;
+        self.albedo
+    }
+}
+
+// How the opacity of a material decides where rays hit it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    // Solid where the opacity reaches the cutoff, cut away elsewhere. For
+    // leaves and fences.
+    Test(f32),
+    // Hit with a probability of the opacity, which averages to a blend over
+    // the samples of a pixel. For translucent surfaces and soft decal edges.
+    Blend,
+}
+
+impl AlphaMode {
+    // Whether rays can stop at a surface of this opacity at all
+    pub fn may_hit(self, opacity: f32) -> bool {
+        match self {
+            AlphaMode::Test(cutoff) => opacity >= cutoff,
+            AlphaMode::Blend => opacity > 0.0,
+        }
+    }
+
+    // Probability that they do once they may
+    pub fn coverage(self, opacity: f32) -> f32 {
+        match self {
+            AlphaMode::Test(_) => 1.0,
+            AlphaMode::Blend => opacity.min(1.0),
+        }
     }
 }
 
 pub struct TexturedMaterial {
     texture: Arc<Texture>,
+    // Constant opacity, multiplied with the one from the textures
+    opacity: f32,
+    // Opacity from its own image instead of the texture's alpha channel
+    opacity_map: Option<Arc<Texture>>,
+    alpha_mode: AlphaMode,
 }
 
 impl TexturedMaterial {
     pub fn new(texture: Arc<Texture>) -> Self {
-        Self { texture }
+        Self {
+            texture,
+            opacity: 1.0,
+            opacity_map: None,
+            alpha_mode: AlphaMode::Test(0.5),
+        }
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    // The red channel of the map is the opacity, as with MTL map_d
+    pub fn with_opacity_map(mut self, opacity_map: Arc<Texture>) -> Self {
+        self.opacity_map = Some(opacity_map);
+        self
+    }
+
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    pub fn opacity(&self, u: f32, v: f32) -> f32 {
+        let map = match &self.opacity_map {
+            Some(map) => map.sample(u, v).x(),
+            None => self.texture.alpha(u, v),
+        };
+        self.opacity * map
     }
 }
 
 impl Material for TexturedMaterial {
     fn scatter(
         &self,
-        _r_in: &Ray,
+        r_in: &Ray,
         rec: &HitRecord,
         attenuation: &mut Color,
         scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
     ) -> bool {
-        let scatter_direction = rec.normal + vec3::random_unit_vector();
+        let scatter_direction = rec.normal + sampler::sample_unit_vector(sampler.get_2d());
 
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.texture.sample(rec.u, rec.v);
+        *scattered = r_in.scattered(rec.p, scatter_direction);
+        *attenuation = spectrum::for_ray(self.texture.sample(rec.u, rec.v), r_in);
         true
     }
 
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        if same_hemisphere(rec, wo, wi) {
+            self.texture.sample(rec.u, rec.v) / PI
+        } else {
+            Color::ZERO
+        }
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        cosine_pdf(rec, wo, wi)
+    }
+
+    fn is_opaque_at(&self, u: f32, v: f32) -> bool {
+        self.alpha_mode.may_hit(self.opacity(u, v))
+    }
+
+    fn coverage(&self, rec: &HitRecord) -> f32 {
+        self.alpha_mode.coverage(self.opacity(rec.u, rec.v))
+    }
+
     fn albedo(&self) -> Color {
         Color::new(1.0, 1.0, 1.0)
     }
 }
 
+// Any material with the opacity of MTL d and map_d, for surfaces without a
+// texture whose alpha channel could cut them
+pub struct Cutout {
+    material: Arc<dyn Material>,
+    opacity: f32,
+    opacity_map: Option<Arc<Texture>>,
+    alpha_mode: AlphaMode,
+}
+
+impl Cutout {
+    pub fn new(material: Arc<dyn Material>) -> Self {
+        Self {
+            material,
+            opacity: 1.0,
+            opacity_map: None,
+            alpha_mode: AlphaMode::Test(0.5),
+        }
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    // The red channel of the map is the opacity
+    pub fn with_opacity_map(mut self, opacity_map: Arc<Texture>) -> Self {
+        self.opacity_map = Some(opacity_map);
+        self
+    }
+
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    pub fn opacity(&self, u: f32, v: f32) -> f32 {
+        self.opacity * self.opacity_map.as_ref().map_or(1.0, |map| map.sample(u, v).x())
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+        sampler: &mut dyn Sampler,
+    ) -> bool {
+        self.material.scatter(r_in, rec, attenuation, scattered, sampler)
+    }
+
+    fn eval(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Color {
+        self.material.eval(rec, wo, wi)
+    }
+
+    fn pdf(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+        self.material.pdf(rec, wo, wi)
+    }
+
+    fn passes_through(&self, r_in: &Ray, rec: &HitRecord) -> bool {
+        self.material.passes_through(r_in, rec)
+    }
+
+    fn transmit_shadow(
+        &self,
+        r_in: &Ray,
+        rec: &HitRecord,
+        attenuation: &mut Color,
+        transmitted: &mut Ray,
+    ) -> bool {
+        self.material.transmit_shadow(r_in, rec, attenuation, transmitted)
+    }
+
+    fn is_opaque_at(&self, u: f32, v: f32) -> bool {
+        self.alpha_mode.may_hit(self.opacity(u, v)) && self.material.is_opaque_at(u, v)
+    }
+
+    fn coverage(&self, rec: &HitRecord) -> f32 {
+        self.alpha_mode.coverage(self.opacity(rec.u, rec.v)) * self.material.coverage(rec)
+    }
+
+    fn is_specular(&self) -> bool {
+        self.material.is_specular()
+    }
+
+    fn albedo(&self) -> Color {
+        self.material.albedo()
+    }
+}
+
+fn same_hemisphere(rec: &HitRecord, wo: Vec3, wi: Vec3) -> bool {
+    vec3::dot(wo, rec.normal) * vec3::dot(wi, rec.normal) > 0.0
+}
+
+// Density of the cosine-weighted directions diffuse scatter produces
+fn cosine_pdf(rec: &HitRecord, wo: Vec3, wi: Vec3) -> f32 {
+    let normal = if vec3::dot(wo, rec.normal) < 0.0 { -rec.normal } else { rec.normal };
+    vec3::dot(vec3::unit_vector(wi), normal).max(0.0) / PI
+}
+
 pub struct RoomMaterials {
     pub floor: Arc<dyn Material>,
     pub ceiling: Arc<dyn Material>,
@@ -1,4 +1,4 @@
-use image::{RgbaImage};
+use image::{Rgba, RgbaImage};
 
 use crate::core::color::{Color};
 
@@ -15,17 +15,26 @@ impl Texture {
     }
 
     pub fn sample(&self, u: f32, v: f32) -> Color {
+        let pixel = self.texel(u, v);
+        Color::new(
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+        )
+    }
+
+    // Opacity from the alpha channel, 1 for images without one
+    pub fn alpha(&self, u: f32, v: f32) -> f32 {
+        self.texel(u, v)[3] as f32 / 255.0
+    }
+
+    fn texel(&self, u: f32, v: f32) -> &Rgba<u8> {
         let u = u.clamp(0.0, 1.0);
         let v = 1.0 - v.clamp(0.0, 1.0);
 
         let x = (u * self.width as f32) as u32;
         let y = (v * self.height as f32) as u32;
 
-        let pixel = self.image.get_pixel(x.min(self.width - 1), y.min(self.height - 1));
-        Color::new(
-            pixel[0] as f32 / 255.0,
-            pixel[1] as f32 / 255.0,
-            pixel[2] as f32 / 255.0,
-        )
+        self.image.get_pixel(x.min(self.width - 1), y.min(self.height - 1))
     }
 }
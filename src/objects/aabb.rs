@@ -0,0 +1,67 @@
+use crate::core::vec3::{Point3, Vec3};
+
+// Axis aligned bounding box
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    // Contains nothing, the union with any box is that box
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::splat(f32::INFINITY),
+        max: Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn new(min: Point3, max: Point3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn from_points(points: &[Point3]) -> Aabb {
+        points.iter().fold(Aabb::EMPTY, |aabb, &p| aabb.grow(p))
+    }
+
+    pub fn grow(&self, p: Point3) -> Aabb {
+        Aabb::new(self.min.min(p), self.max.max(p))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    // Grows every side by margin, flat boxes of axis aligned triangles get a thickness
+    pub fn pad(&self, margin: f32) -> Aabb {
+        let margin = Vec3::splat(margin);
+        Aabb::new(self.min - margin, self.max + margin)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.x() < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    // Slab test, the distance where the ray enters the box if it does within the range
+    pub fn hit(&self, origin: Point3, inv_direction: Vec3, t_min: f32, t_max: f32) -> Option<f32> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for axis in 0..3 {
+            let near = (self.min.axis(axis) - origin.axis(axis)) * inv_direction.axis(axis);
+            let far = (self.max.axis(axis) - origin.axis(axis)) * inv_direction.axis(axis);
+            let (near, far) = if near <= far { (near, far) } else { (far, near) };
+            // A NaN from a ray in the plane of a side leaves the range as it is
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t1 < t0 {
+                return None;
+            }
+        }
+        Some(t0)
+    }
+}
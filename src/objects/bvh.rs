@@ -0,0 +1,186 @@
+use crate::core::ray::Ray;
+use crate::core::vec3::Vec3;
+
+use crate::objects::aabb::Aabb;
+
+// Split candidates per axis and node
+const BINS: usize = 12;
+// Nodes with this many primitives or fewer are not split
+const MAX_LEAF_SIZE: usize = 4;
+// Deeper nodes become leaves, which bounds the traversal stack
+const MAX_DEPTH: usize = 48;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Aabb,
+    // Leaves hold count primitives from start in the index list, inner nodes
+    // have count 0 and their two children at start and start + 1
+    start: usize,
+    count: usize,
+}
+
+// Bounding volume hierarchy over the boxes of a list of primitives, built with
+// the surface area heuristic over binned centroids
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn build(boxes: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * boxes.len()),
+            indices: (0..boxes.len()).collect(),
+        };
+        if !boxes.is_empty() {
+            let centroids: Vec<Vec3> = boxes.iter().map(Aabb::centroid).collect();
+            bvh.nodes.push(Node { bounds: Aabb::EMPTY, start: 0, count: boxes.len() });
+            bvh.subdivide(0, 0, boxes, &centroids);
+        }
+        bvh
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // Bytes taken by the nodes and the primitive indices
+    pub fn memory_bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node>() + self.indices.capacity() * std::mem::size_of::<usize>()
+    }
+
+    fn subdivide(&mut self, node: usize, depth: usize, boxes: &[Aabb], centroids: &[Vec3]) {
+        let Node { start, count, .. } = self.nodes[node];
+        let items = &self.indices[start..start + count];
+        self.nodes[node].bounds = items.iter().fold(Aabb::EMPTY, |bounds, &i| bounds.union(&boxes[i]));
+        if count <= MAX_LEAF_SIZE || depth >= MAX_DEPTH {
+            return;
+        }
+
+        let centroid_bounds = items.iter().fold(Aabb::EMPTY, |bounds, &i| bounds.grow(centroids[i]));
+        let Some((axis, split)) = best_split(items, boxes, centroids, &centroid_bounds) else {
+            // All centroids in one place, nothing to split
+            return;
+        };
+
+        // Partition the indices in place, the bins below the split go left
+        let (lo, scale) = bin_scale(&centroid_bounds, axis);
+        let items = &mut self.indices[start..start + count];
+        let mut left = 0;
+        for i in 0..count {
+            if bin(centroids[items[i]].axis(axis), lo, scale) < split {
+                items.swap(i, left);
+                left += 1;
+            }
+        }
+
+        let children = self.nodes.len();
+        self.nodes.push(Node { bounds: Aabb::EMPTY, start, count: left });
+        self.nodes.push(Node { bounds: Aabb::EMPTY, start: start + left, count: count - left });
+        self.nodes[node].start = children;
+        self.nodes[node].count = 0;
+        self.subdivide(children, depth + 1, boxes, centroids);
+        self.subdivide(children + 1, depth + 1, boxes, centroids);
+    }
+
+    // Calls hit with the index of every primitive whose box the ray enters before
+    // the closest hit so far, near boxes first. hit returns the distance of a new
+    // closest hit, which shortens the ray. Returns the number of box and
+    // primitive tests, a measure of the work the ray took.
+    pub fn traverse(&self, ray: &Ray, t_min: f32, mut t_max: f32, mut hit: impl FnMut(usize, f32) -> Option<f32>) -> usize {
+        let Some(root) = self.nodes.first() else {
+            return 0;
+        };
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let inv_direction = Vec3::new(1.0 / direction.x(), 1.0 / direction.y(), 1.0 / direction.z());
+        let Some(t_root) = root.bounds.hit(origin, inv_direction, t_min, t_max) else {
+            return 1;
+        };
+
+        let mut stack = [(0, 0.0); MAX_DEPTH + 2];
+        stack[0] = (0, t_root);
+        let mut len = 1;
+        let mut cost = 1;
+        while len > 0 {
+            len -= 1;
+            let (index, t_enter) = stack[len];
+            // The box starts behind a hit found since it was pushed
+            if t_enter > t_max {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                cost += node.count;
+                for &primitive in &self.indices[node.start..node.start + node.count] {
+                    if let Some(t) = hit(primitive, t_max) {
+                        t_max = t;
+                    }
+                }
+                continue;
+            }
+
+            cost += 2;
+            let near = self.nodes[node.start].bounds.hit(origin, inv_direction, t_min, t_max);
+            let far = self.nodes[node.start + 1].bounds.hit(origin, inv_direction, t_min, t_max);
+            let (first, second) = match (near, far) {
+                (Some(a), Some(b)) if b < a => ((node.start + 1, Some(b)), (node.start, Some(a))),
+                _ => ((node.start, near), (node.start + 1, far)),
+            };
+            // Pushed last, popped first
+            for (child, t) in [second, first] {
+                if let Some(t) = t {
+                    stack[len] = (child, t);
+                    len += 1;
+                }
+            }
+        }
+        cost
+    }
+}
+
+fn bin_scale(centroid_bounds: &Aabb, axis: usize) -> (f32, f32) {
+    let lo = centroid_bounds.min.axis(axis);
+    (lo, BINS as f32 / (centroid_bounds.max.axis(axis) - lo))
+}
+
+fn bin(value: f32, lo: f32, scale: f32) -> usize {
+    (((value - lo) * scale) as usize).min(BINS - 1)
+}
+
+// The axis and the first bin of the right side with the lowest SAH cost
+fn best_split(items: &[usize], boxes: &[Aabb], centroids: &[Vec3], centroid_bounds: &Aabb) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize, f32)> = None;
+    for axis in 0..3 {
+        if centroid_bounds.max.axis(axis) - centroid_bounds.min.axis(axis) <= 0.0 {
+            continue;
+        }
+        let (lo, scale) = bin_scale(centroid_bounds, axis);
+        let mut bins = [(Aabb::EMPTY, 0usize); BINS];
+        for &i in items {
+            let b = &mut bins[bin(centroids[i].axis(axis), lo, scale)];
+            b.0 = b.0.union(&boxes[i]);
+            b.1 += 1;
+        }
+
+        // Cost of the left side of every split, then add the right side sweeping back
+        let mut costs = [0.0; BINS];
+        let (mut bounds, mut count) = (Aabb::EMPTY, 0);
+        for split in 1..BINS {
+            bounds = bounds.union(&bins[split - 1].0);
+            count += bins[split - 1].1;
+            costs[split] = if count == 0 { f32::INFINITY } else { count as f32 * bounds.surface_area() };
+        }
+        let (mut bounds, mut count) = (Aabb::EMPTY, 0);
+        for split in (1..BINS).rev() {
+            bounds = bounds.union(&bins[split].0);
+            count += bins[split].1;
+            let cost = if count == 0 { f32::INFINITY } else { costs[split] + count as f32 * bounds.surface_area() };
+            if cost.is_finite() && best.is_none_or(|(_, _, best)| cost < best) {
+                best = Some((axis, split, cost));
+            }
+        }
+    }
+    best.map(|(axis, split, _)| (axis, split))
+}
@@ -4,16 +4,24 @@ use crate::core::ray::Ray;
 use crate::core::vec3::{self, Point3, Vec3};
 
 use crate::material::material::Material;
+use crate::objects::aabb::Aabb;
  
 #[derive(Clone, Default)]
 pub struct HitRecord {
     pub p: Point3,
+    // Shading normal, facing the ray
     pub normal: Vec3,
+    // Normal of the surface itself, pointing outwards whichever side was hit
+    pub geometric_normal: Vec3,
     pub mat: Option<Arc<dyn Material>>,
     pub t: f32,
     pub front_face: bool,
     pub u: f32,
     pub v: f32,
+    // Weights of the second and third vertex of a triangle, 0 for spheres
+    pub barycentrics: (f32, f32),
+    // Set by the world, shared by all parts of one object, 0 for nothing
+    pub object_id: u32,
 }
  
 impl HitRecord {
@@ -22,6 +30,7 @@ impl HitRecord {
     }
 
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: Vec3) {
+        self.geometric_normal = outward_normal;
         self.front_face = vec3::dot(r.direction(), outward_normal) < 0.0;
         self.normal = if self.front_face {
             outward_normal
@@ -34,6 +43,8 @@ impl HitRecord {
 pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool;
 
+    fn bounding_box(&self) -> Aabb;
+
     fn box_clone(&self) -> Box<dyn Hittable>;
 }
 
@@ -10,8 +10,8 @@ pub struct Light {
 impl Light {
     pub fn new(p: Point3, intensity: Color) -> Self {
         Self {
-            p: p,
-            intensity: intensity,
+            p,
+            intensity,
         }
     }
 
@@ -24,7 +24,7 @@ impl Light {
 
         Self {
             p: position,
-            intensity: intensity
+            intensity
         }
     }
 
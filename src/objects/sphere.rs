@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use crate::core::ray::Ray;
-use crate::core::vec3::{self, Point3};
+use crate::core::vec3::{self, Point3, Vec3};
 
+use crate::objects::aabb::Aabb;
 use crate::objects::hittable::{HitRecord, Hittable};
 use crate::material::material::Material;
 
@@ -18,7 +19,7 @@ impl Sphere {
         Sphere {
             center: cen,
             radius: r,
-            mat: mat
+            mat
         }
     }
 }
@@ -48,10 +49,16 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center) / self.radius;
         rec.set_face_normal(r, outward_normal);
+        rec.barycentrics = (0.0, 0.0);
         rec.mat = Some(self.mat.clone());
         true
     }
 
+    fn bounding_box(&self) -> Aabb {
+        let r = Vec3::splat(self.radius.abs());
+        Aabb::new(self.center - r, self.center + r)
+    }
+
     fn box_clone(&self) -> Box<dyn Hittable> {
         Box::new(self.clone())
     }
@@ -5,6 +5,7 @@ use std::sync::Arc;
 use crate::core::ray::Ray;
 use crate::core::vec3::{self, Vec3, dot, Point3};
 
+use crate::objects::aabb::Aabb;
 use crate::objects::hittable::{HitRecord, Hittable};
 use crate::material::material::{Material, RoomMaterials};
 
@@ -26,38 +27,40 @@ impl Triangle {
         uv0: (f32, f32), uv1: (f32, f32), uv2: (f32, f32),
         mat: Arc<dyn Material>
     ) -> Self {
-        let mut normal = vec3::cross(p1 - p0, p2 - p0);
+        // Unit length, otherwise the parallel test in hit drops small triangles
+        let mut normal = vec3::cross(p1 - p0, p2 - p0).normalize();
 
         if dot(normal, Vec3::new(0.0, 0.0, 1.0)) < 0.0 {
             normal = -normal;
         }
 
         Self {
-            p0: p0,
-            p1: p1,
-            p2: p2,
-            uv0: uv0,
-            uv1: uv1,
-            uv2: uv2,
-            normal: normal,
-            mat: mat
+            p0,
+            p1,
+            p2,
+            uv0,
+            uv1,
+            uv2,
+            normal,
+            mat
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_normal(
         p0: Vec3, p1: Vec3, p2: Vec3,
         uv0: (f32, f32), uv1: (f32, f32), uv2: (f32, f32),
         normal: Vec3, mat: Arc<dyn Material>
     ) -> Self {
         Self {
-            p0: p0,
-            p1: p1,
-            p2: p2,
-            uv0: uv0,
-            uv1: uv1,
-            uv2: uv2,
-            normal: normal,
-            mat: mat
+            p0,
+            p1,
+            p2,
+            uv0,
+            uv1,
+            uv2,
+            normal,
+            mat
         }
     }
 
@@ -119,10 +122,14 @@ impl Hittable for Triangle {
 
         let u = u_0 * w + u_1 * lambda + u_2 * mu;
         let v = v_0 * w + v_1 * lambda + v_2 * mu;
+        if !self.mat.is_opaque_at(u, v) {
+            return false;
+        }
 
         // println!("UV0: {:?}, UV1: {:?}, UV2: {:?}", self.uv0, self.uv1, self.uv2);
         rec.u = u;
         rec.v = v;
+        rec.barycentrics = (lambda, mu);
         rec.t = t;
         rec.p = hit_point;
         let edge1 = self.p1 - self.p0;
@@ -133,6 +140,10 @@ impl Hittable for Triangle {
         true
     }
 
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&[self.p0, self.p1, self.p2]).pad(1e-4)
+    }
+
     fn box_clone(&self) -> Box<dyn Hittable> {
         Box::new(self.clone())
     }
@@ -191,27 +202,25 @@ pub fn cube(center: Point3, size: f32, rotation: Vec3, mat: Arc<dyn Material>) -
 
     let [p000, p001, p010, p011, p100, p101, p110, p111] = points;
 
-    let mut tris = vec![];
-
-    tris.push(Triangle::new_untextured(p001, p101, p111, mat.clone()));
-    tris.push(Triangle::new_untextured(p001, p111, p011, mat.clone()));
-
-    tris.push(Triangle::new_untextured(p100, p000, p010, mat.clone()));
-    tris.push(Triangle::new_untextured(p100, p010, p110, mat.clone()));
+    vec![
+        Triangle::new_untextured(p001, p101, p111, mat.clone()),
+        Triangle::new_untextured(p001, p111, p011, mat.clone()),
 
-    tris.push(Triangle::new_untextured(p000, p001, p011, mat.clone()));
-    tris.push(Triangle::new_untextured(p000, p011, p010, mat.clone()));
+        Triangle::new_untextured(p100, p000, p010, mat.clone()),
+        Triangle::new_untextured(p100, p010, p110, mat.clone()),
 
-    tris.push(Triangle::new_untextured(p101, p100, p110, mat.clone()));
-    tris.push(Triangle::new_untextured(p101, p110, p111, mat.clone()));
+        Triangle::new_untextured(p000, p001, p011, mat.clone()),
+        Triangle::new_untextured(p000, p011, p010, mat.clone()),
 
-    tris.push(Triangle::new_untextured(p010, p011, p111, mat.clone()));
-    tris.push(Triangle::new_untextured(p010, p111, p110, mat.clone()));
+        Triangle::new_untextured(p101, p100, p110, mat.clone()),
+        Triangle::new_untextured(p101, p110, p111, mat.clone()),
 
-    tris.push(Triangle::new_untextured(p000, p100, p101, mat.clone()));
-    tris.push(Triangle::new_untextured(p000, p101, p001, mat.clone()));
+        Triangle::new_untextured(p010, p011, p111, mat.clone()),
+        Triangle::new_untextured(p010, p111, p110, mat.clone()),
 
-    tris
+        Triangle::new_untextured(p000, p100, p101, mat.clone()),
+        Triangle::new_untextured(p000, p101, p001, mat.clone()),
+    ]
 }
 
 pub fn make_room_box(
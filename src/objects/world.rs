@@ -1,12 +1,37 @@
+use std::cell::Cell;
+use std::sync::OnceLock;
+
 use crate::core::ray::Ray;
+use crate::core::sampler::Sampler;
 
+use crate::objects::bvh::Bvh;
 use crate::objects::hittable::{Hittable, HitRecord};
 use crate::objects::light::Light;
 
+thread_local! {
+    // Calls of World::hit on this thread, counted for the benchmark
+    static RAYS_TRACED: Cell<u64> = const { Cell::new(0) };
+}
+
+// Returns the rays traced on the calling thread since the last call
+pub fn take_rays_traced() -> u64 {
+    RAYS_TRACED.with(Cell::take)
+}
+
 #[derive(Clone)]
 pub struct World {
-    pub hittables: Vec<Box<dyn Hittable>>,
+    hittables: Vec<Box<dyn Hittable>>,
     pub lights: Vec<Light>,
+    // Object id of every hittable, counting from 1
+    object_ids: Vec<u32>,
+    // Built on the first hit after objects were added
+    bvh: OnceLock<Bvh>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl World {
@@ -14,31 +39,80 @@ impl World {
         World {
             hittables: Vec::new(),
             lights: Vec::new(),
+            object_ids: Vec::new(),
+            bvh: OnceLock::new(),
         }
     }
 
     pub fn add_hittable(&mut self, hittable: Box<dyn Hittable>) {
-        self.hittables.push(hittable);
+        self.add_object(std::iter::once(hittable));
+    }
+
+    // Adds hittables that together form one object, e.g. the triangles of a mesh
+    pub fn add_object(&mut self, parts: impl IntoIterator<Item = Box<dyn Hittable>>) {
+        let id = self.object_ids.last().map_or(1, |id| id + 1);
+        for part in parts {
+            self.hittables.push(part);
+            self.object_ids.push(id);
+        }
+        self.bvh = OnceLock::new();
     }
 
     pub fn add_light(&mut self, light: Light) {
         self.lights.push(light);
     }
 
+    // Number of spheres and triangles
+    pub fn primitive_count(&self) -> usize {
+        self.hittables.len()
+    }
+
+    // Builds the BVH now instead of on the first hit
+    pub fn build_bvh(&self) -> &Bvh {
+        self.bvh.get_or_init(|| {
+            let boxes: Vec<_> = self.hittables.iter().map(|hittable| hittable.bounding_box()).collect();
+            Bvh::build(&boxes)
+        })
+    }
+
     pub fn hit(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> bool {
+        self.hit_with_cost(ray, t_min, t_max, rec).0
+    }
+
+    // Like hit, with surfaces that alpha only partly covers hit with the
+    // probability of their coverage. The pixel's sampler decides, the rays
+    // that miss such a surface carry on behind it. Each of those restarts the
+    // traversal past the rejected hit, so a ray through n blended layers costs
+    // n + 1 traversals. Deciding inside the traversal would draw the sampler in
+    // the order the BVH visits the surfaces instead of front to back.
+    pub fn hit_sampled(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord, sampler: &mut dyn Sampler) -> bool {
+        let mut t_min = t_min;
+        while self.hit(ray, t_min, t_max, rec) {
+            let coverage = rec.mat.as_ref().map_or(1.0, |mat| mat.coverage(rec));
+            if coverage >= 1.0 || sampler.get_1d() < coverage {
+                return true;
+            }
+            t_min = rec.t.next_up();
+        }
+        false
+    }
+
+    // Like hit, plus the number of box and primitive tests it took
+    pub fn hit_with_cost(&self, ray: &Ray, t_min: f32, t_max: f32, rec: &mut HitRecord) -> (bool, usize) {
+        RAYS_TRACED.with(|rays| rays.set(rays.get() + 1));
         let mut temp_rec = HitRecord::new();
         let mut hit_anything = false;
-        let mut closest_so_far = t_max;
 
-        for object in &self.hittables {
-            if object.hit(ray, t_min, closest_so_far, &mut temp_rec) {
-                hit_anything = true;
-                closest_so_far = temp_rec.t;
-                *rec = temp_rec.clone();
+        let cost = self.build_bvh().traverse(ray, t_min, t_max, |i, closest_so_far| {
+            if !self.hittables[i].hit(ray, t_min, closest_so_far, &mut temp_rec) {
+                return None;
             }
-        }
+            hit_anything = true;
+            *rec = temp_rec.clone();
+            rec.object_id = self.object_ids[i];
+            Some(temp_rec.t)
+        });
 
-        hit_anything
+        (hit_anything, cost)
     }
 }
-
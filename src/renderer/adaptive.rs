@@ -0,0 +1,75 @@
+use crate::core::color::{self, Color};
+
+// Luminance below which noise is judged against this floor instead of the
+// pixel itself, otherwise nearly black pixels never converge
+const DARK_FLOOR: f32 = 0.02;
+
+// Running mean and variance of a pixel's sample luminance (Welford)
+#[derive(Clone, Copy, Default)]
+pub struct PixelStats {
+    samples: u32,
+    mean: f32,
+    m2: f32,
+}
+
+impl PixelStats {
+    // Samples taken so far with the running mean and squared deviation sum
+    pub fn from_parts(samples: u32, mean: f32, m2: f32) -> Self {
+        Self { samples, mean, m2 }
+    }
+
+    pub fn parts(&self) -> (u32, f32, f32) {
+        (self.samples, self.mean, self.m2)
+    }
+
+    pub fn add(&mut self, sample: Color) {
+        let value = color::luminance(sample);
+        self.samples += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.samples as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn variance(&self) -> f32 {
+        if self.samples < 2 {
+            return f32::INFINITY;
+        }
+        self.m2 / (self.samples - 1) as f32
+    }
+
+    // Standard error of the mean relative to the pixel's brightness
+    pub fn relative_error(&self) -> f32 {
+        (self.variance() / self.samples as f32).sqrt() / self.mean.abs().max(DARK_FLOOR)
+    }
+
+    pub fn converged(&self, threshold: f32) -> bool {
+        self.relative_error() <= threshold
+    }
+}
+
+// Blue for the fewest samples through green and yellow to red for the most
+pub fn heatmap_color(samples: u32, min: u32, max: u32) -> Color {
+    let t = if max > min { (samples - min) as f32 / (max - min) as f32 } else { 0.0 };
+    let stops = [
+        Color::new(0.0, 0.0, 0.5),
+        Color::new(0.0, 0.4, 1.0),
+        Color::new(0.0, 0.9, 0.3),
+        Color::new(1.0, 0.9, 0.0),
+        Color::new(0.9, 0.0, 0.0),
+    ];
+    let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+    let i = (x as usize).min(stops.len() - 2);
+    let f = x - i as f32;
+    stops[i] * (1.0 - f) + stops[i + 1] * f
+}
+
+// Display colours for a whole image of sample counts
+pub fn heatmap(samples: &[u32]) -> Vec<Color> {
+    let min = samples.iter().copied().min().unwrap_or(0);
+    let max = samples.iter().copied().max().unwrap_or(0);
+    samples.iter().map(|&n| heatmap_color(n, min, max)).collect()
+}
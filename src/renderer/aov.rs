@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::ops::{AddAssign, Mul};
+use std::path::Path;
+use std::sync::Arc;
+
+use exr::prelude::*;
+
+use crate::core::color::{Color, luminance};
+use crate::core::vec3::Vec3;
+use crate::core::sampler::Sampler;
+
+use crate::material::material::Material;
+use crate::objects::hittable::HitRecord;
+use crate::objects::world::World;
+
+use crate::renderer::adaptive::PixelStats;
+use crate::renderer::integrator;
+
+// Radiance arriving along a camera ray, split by the way it got there. Direct
+// light reached the first surface straight from a light, anything that took
+// further bounces is indirect. Whether it counts as diffuse or specular is
+// decided by that first surface. Rays leaving the scene at once see emission.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LightPasses {
+    pub emission: Color,
+    pub direct_diffuse: Color,
+    pub indirect_diffuse: Color,
+    pub direct_specular: Color,
+    pub indirect_specular: Color,
+}
+
+impl LightPasses {
+    pub fn emission(c: Color) -> Self {
+        Self {
+            emission: c,
+            ..Default::default()
+        }
+    }
+
+    // The beauty image is the sum of all passes
+    pub fn total(&self) -> Color {
+        self.emission + self.direct_diffuse + self.indirect_diffuse + self.direct_specular + self.indirect_specular
+    }
+
+    pub fn add_direct(&mut self, specular: bool, c: Color) {
+        if specular {
+            self.direct_specular += c;
+        } else {
+            self.direct_diffuse += c;
+        }
+    }
+
+    pub fn add_indirect(&mut self, specular: bool, c: Color) {
+        if specular {
+            self.indirect_specular += c;
+        } else {
+            self.indirect_diffuse += c;
+        }
+    }
+
+    pub fn map(self, f: impl Fn(Color) -> Color) -> Self {
+        Self {
+            emission: f(self.emission),
+            direct_diffuse: f(self.direct_diffuse),
+            indirect_diffuse: f(self.indirect_diffuse),
+            direct_specular: f(self.direct_specular),
+            indirect_specular: f(self.indirect_specular),
+        }
+    }
+}
+
+impl AddAssign for LightPasses {
+    fn add_assign(&mut self, other: Self) {
+        self.emission += other.emission;
+        self.direct_diffuse += other.direct_diffuse;
+        self.indirect_diffuse += other.indirect_diffuse;
+        self.direct_specular += other.direct_specular;
+        self.indirect_specular += other.indirect_specular;
+    }
+}
+
+impl Mul<Color> for LightPasses {
+    type Output = Self;
+
+    fn mul(self, c: Color) -> Self {
+        self.map(|pass| pass * c)
+    }
+}
+
+// Share of the light power that reaches the point unblocked, 1 when fully lit
+pub fn shadow(world: &World, rec: &HitRecord, sampler: &mut dyn Sampler) -> f32 {
+    let total: f32 = world.lights.iter().map(|l| luminance(l.intensity())).sum();
+    if total <= 0.0 {
+        return 1.0;
+    }
+    let lit: f32 = world
+        .lights
+        .iter()
+        .filter(|l| integrator::visible(world, rec.p, l.position(), sampler))
+        .map(|l| luminance(l.intensity()))
+        .sum();
+    lit / total
+}
+
+// Running sums of one pixel's AOVs. They are averaged over the pixel's own
+// samples only, a wider filter would blend ids and normals across edges.
+#[derive(Clone)]
+pub struct AovPixel {
+    stats: PixelStats,
+    passes: LightPasses,
+    depth: f32,
+    normal: Vec3,
+    albedo: Color,
+    uv: (f32, f32),
+    shadow: f32,
+    object_id: u32,
+    material: Option<Arc<dyn Material>>,
+}
+
+impl Default for AovPixel {
+    fn default() -> Self {
+        Self {
+            stats: PixelStats::default(),
+            passes: LightPasses::default(),
+            depth: f32::INFINITY,
+            normal: Vec3::ZERO,
+            albedo: Color::ZERO,
+            uv: (0.0, 0.0),
+            shadow: 0.0,
+            object_id: 0,
+            material: None,
+        }
+    }
+}
+
+impl AovPixel {
+    // One sample with the primary hit and its distance from the camera, or
+    // None when the camera ray left the scene
+    pub fn add(&mut self, passes: LightPasses, hit: Option<(&HitRecord, f32)>, shadow: f32) {
+        self.passes += passes;
+        self.shadow += shadow;
+        if let Some((rec, distance)) = hit {
+            self.depth = self.depth.min(distance);
+            self.normal += rec.normal;
+            self.uv = (self.uv.0 + rec.u, self.uv.1 + rec.v);
+            if let Some(mat) = &rec.mat {
+                self.albedo += mat.albedo();
+            }
+            // Ids cannot be averaged, the first sample decides
+            if self.stats.samples() == 0 {
+                self.object_id = rec.object_id;
+                self.material = rec.mat.clone();
+            }
+        }
+        self.stats.add(passes.total());
+    }
+}
+
+// Per pixel output variables, top row first like the beauty image
+pub struct Aovs {
+    pub width: usize,
+    pub height: usize,
+    pub passes: Vec<LightPasses>,
+    // Distance from the camera to the nearest surface, infinite for the sky
+    pub depth: Vec<f32>,
+    pub normal: Vec<Vec3>,
+    pub albedo: Vec<Color>,
+    pub uv: Vec<(f32, f32)>,
+    pub shadow: Vec<f32>,
+    // 0 where nothing was hit
+    pub object_id: Vec<u32>,
+    pub material_id: Vec<u32>,
+    // Variance of the pixel's mean luminance, i.e. how noisy it still is
+    pub variance: Vec<f32>,
+}
+
+impl Aovs {
+    pub fn resolve(width: usize, height: usize, pixels: &[AovPixel]) -> Self {
+        // Materials are numbered from 1 in the order they first show up,
+        // scanning from the top left
+        let mut materials: HashMap<*const (), u32> = HashMap::new();
+        let material_id = pixels
+            .iter()
+            .map(|p| match &p.material {
+                Some(mat) => {
+                    let next = materials.len() as u32 + 1;
+                    *materials.entry(Arc::as_ptr(mat) as *const ()).or_insert(next)
+                }
+                None => 0,
+            })
+            .collect();
+
+        let mean = |p: &AovPixel| 1.0 / p.stats.samples().max(1) as f32;
+        Self {
+            width,
+            height,
+            passes: pixels.iter().map(|p| p.passes.map(|c| c * mean(p))).collect(),
+            depth: pixels.iter().map(|p| p.depth).collect(),
+            normal: pixels.iter().map(|p| p.normal * mean(p)).collect(),
+            albedo: pixels.iter().map(|p| p.albedo * mean(p)).collect(),
+            uv: pixels.iter().map(|p| (p.uv.0 * mean(p), p.uv.1 * mean(p))).collect(),
+            shadow: pixels.iter().map(|p| p.shadow * mean(p)).collect(),
+            object_id: pixels.iter().map(|p| p.object_id).collect(),
+            material_id,
+            // Unknown below two samples, counted as very noisy
+            variance: pixels.iter().map(|p| (p.stats.variance() * mean(p)).min(1e6)).collect(),
+        }
+    }
+
+    // Single part EXR holding the beauty image as R, G, B and every AOV as
+    // channels prefixed by its layer name, the way compositors expect them
+    pub fn write_exr(&self, path: &Path, beauty: &[Color]) -> exr::error::UnitResult {
+        let mut channels = SmallVec::<[AnyChannel<FlatSamples>; 4]>::new();
+        let mut rgb = |layer: &str, colors: &[Color]| {
+            let prefix = if layer.is_empty() { String::new() } else { format!("{layer}.") };
+            for (name, channel) in [("R", 0), ("G", 1), ("B", 2)] {
+                let samples = colors.iter().map(|c| [c.x(), c.y(), c.z()][channel]).collect();
+                channels.push(AnyChannel::new(format!("{prefix}{name}").as_str(), FlatSamples::F32(samples)));
+            }
+        };
+        rgb("", beauty);
+        rgb("albedo", &self.albedo);
+        rgb("emission", &self.passes.iter().map(|p| p.emission).collect::<Vec<_>>());
+        rgb("diffuse_direct", &self.passes.iter().map(|p| p.direct_diffuse).collect::<Vec<_>>());
+        rgb("diffuse_indirect", &self.passes.iter().map(|p| p.indirect_diffuse).collect::<Vec<_>>());
+        rgb("specular_direct", &self.passes.iter().map(|p| p.direct_specular).collect::<Vec<_>>());
+        rgb("specular_indirect", &self.passes.iter().map(|p| p.indirect_specular).collect::<Vec<_>>());
+
+        let float = |name: &str, samples: Vec<f32>| AnyChannel::new(name, FlatSamples::F32(samples));
+        channels.push(float("Z", self.depth.clone()));
+        channels.push(float("N.X", self.normal.iter().map(|n| n.x()).collect()));
+        channels.push(float("N.Y", self.normal.iter().map(|n| n.y()).collect()));
+        channels.push(float("N.Z", self.normal.iter().map(|n| n.z()).collect()));
+        channels.push(float("uv.U", self.uv.iter().map(|uv| uv.0).collect()));
+        channels.push(float("uv.V", self.uv.iter().map(|uv| uv.1).collect()));
+        channels.push(float("shadow.Y", self.shadow.clone()));
+        channels.push(AnyChannel::new("objectId", FlatSamples::U32(self.object_id.clone())));
+        channels.push(AnyChannel::new("materialId", FlatSamples::U32(self.material_id.clone())));
+
+        let layer = Layer::new(
+            (self.width, self.height),
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(channels),
+        );
+        Image::from_layer(layer).write().to_file(path)
+    }
+}
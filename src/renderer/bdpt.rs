@@ -0,0 +1,311 @@
+use crate::core::color::{Color, luminance};
+use crate::core::vec3::{self, Point3, Vec3};
+use crate::core::ray::Ray;
+use crate::core::common::{self, PI};
+use crate::core::spectrum;
+use crate::core::sampler::{self, Sampler};
+
+use crate::objects::world::World;
+use crate::objects::hittable::HitRecord;
+use crate::objects::light::Light;
+
+use crate::renderer::aov::LightPasses;
+use crate::renderer::integrator::{self, Integrator};
+
+// A path vertex on the camera, a light or a surface. Densities are per unit
+// area: pdf_fwd as the vertex was sampled by its own walk, pdf_rev as if the
+// walk had come from the other end of the path.
+#[derive(Clone)]
+struct Vertex {
+    p: Point3,
+    // Zero for the camera and the point lights, which have no surface
+    n: Vec3,
+    rec: Option<HitRecord>,
+    beta: Color,
+    delta: bool,
+    pdf_fwd: f32,
+    pdf_rev: f32,
+}
+
+impl Vertex {
+    fn endpoint(p: Point3, beta: Color, delta: bool, pdf_fwd: f32) -> Self {
+        Self {
+            p,
+            n: Vec3::ZERO,
+            rec: None,
+            beta,
+            delta,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    // Turns a solid angle density at this vertex into an area density at next
+    fn convert_density(&self, pdf: f32, next: &Vertex) -> f32 {
+        let w = next.p - self.p;
+        let dist_sq = w.length_squared();
+        if dist_sq == 0.0 {
+            return 0.0;
+        }
+        let cos = vec3::dot(next.n, w / dist_sq.sqrt()).abs();
+        if next.rec.is_some() {
+            pdf * cos / dist_sq
+        } else {
+            pdf / dist_sq
+        }
+    }
+
+    // Area density of sampling next from this vertex when arriving from prev.
+    // Only surfaces and the lights ever get asked, so no record means a light.
+    fn pdf(&self, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        let pdf = match (&self.rec, prev) {
+            (Some(rec), Some(prev)) => {
+                let mat = rec.mat.as_ref().unwrap();
+                mat.pdf(rec, prev.p - self.p, next.p - self.p)
+            }
+            (Some(_), None) => 0.0,
+            (None, _) => 1.0 / (4.0 * PI),
+        };
+        self.convert_density(pdf, next)
+    }
+
+    fn f(&self, wo_to: &Vertex, wi_from: &Vertex) -> Color {
+        let rec = self.rec.as_ref().unwrap();
+        let mat = rec.mat.as_ref().unwrap();
+        mat.eval(rec, vec3::unit_vector(wo_to.p - self.p), vec3::unit_vector(wi_from.p - self.p))
+    }
+}
+
+// Bidirectional path tracer (Veach 1997): a camera and a light subpath are
+// traced for every sample and all their prefixes get connected, weighted by
+// the balance heuristic. Follows the formulation of pbrt-v3.
+//
+// The pinhole camera and the point lights are both delta, which leaves out the
+// strategies that hit a light by chance or splat light vertices to the film.
+// Connections need a diffuse vertex at both ends, so light reaching a diffuse
+// surface through glass (a caustic) is never found. Use photon mapping for it.
+#[derive(Default)]
+pub struct BdptIntegrator;
+
+impl Integrator for BdptIntegrator {
+    fn passes(&self, r: &Ray, world: &World, depth: i32, sampler: &mut dyn Sampler) -> LightPasses {
+        if depth <= 0 {
+            return LightPasses::default();
+        }
+
+        let mut passes = LightPasses::default();
+
+        let mut camera_path = vec![Vertex::endpoint(r.origin(), Color::new(1.0, 1.0, 1.0), true, 1.0)];
+        let camera_ray = r.scattered(r.origin(), r.direction());
+        // Only escaping camera paths see the sky, so they need no weighting
+        let escaped = random_walk(world, camera_ray, Color::new(1.0, 1.0, 1.0), 1.0, depth, &mut camera_path, sampler);
+        // The first surface decides between the diffuse and specular passes
+        let specular = camera_path.get(1).is_some_and(|v| v.delta);
+        if let Some((escaped, beta)) = escaped {
+            let sky = beta * integrator::background(&escaped);
+            if camera_path.len() == 1 {
+                passes.emission += sky;
+            } else {
+                passes.add_indirect(specular, sky);
+            }
+        }
+
+        let mut light_path = Vec::new();
+        if let Some((light, light_pdf)) = pick_light(world, sampler.get_1d()) {
+            let intensity = spectrum::for_ray(light.intensity(), r);
+            light_path.push(Vertex::endpoint(light.position(), intensity / light_pdf, false, light_pdf));
+            let light_ray = Ray::new(light.position(), sampler::sample_unit_vector(sampler.get_2d())).with_spectral(r.spectral().copied());
+            let beta = intensity * (4.0 * PI / light_pdf);
+            random_walk(world, light_ray, beta, 1.0 / (4.0 * PI), depth - 1, &mut light_path, sampler);
+        }
+
+        for t in 2..=camera_path.len() {
+            for s in 1..=light_path.len() {
+                if (t - 1) + (s - 1) > depth as usize {
+                    break;
+                }
+                let contribution = connect(world, r, &camera_path, &light_path, s, t, sampler);
+                if t == 2 && s == 1 {
+                    passes.add_direct(specular, contribution);
+                } else {
+                    passes.add_indirect(specular, contribution);
+                }
+            }
+        }
+
+        passes
+    }
+}
+
+// Chooses one of the lights proportional to its power
+fn pick_light(world: &World, u: f32) -> Option<(&Light, f32)> {
+    let total: f32 = world.lights.iter().map(|l| luminance(l.intensity())).sum();
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut u = u * total;
+    for light in &world.lights {
+        let weight = luminance(light.intensity());
+        if u < weight {
+            return Some((light, weight / total));
+        }
+        u -= weight;
+    }
+    world.lights.last().map(|l| (l, luminance(l.intensity()) / total))
+}
+
+// Extends the path by up to max_vertices surface vertices, returning the ray
+// and its throughput if the walk left the scene
+fn random_walk(
+    world: &World,
+    mut ray: Ray,
+    mut beta: Color,
+    mut pdf_dir: f32,
+    max_vertices: i32,
+    path: &mut Vec<Vertex>,
+    sampler: &mut dyn Sampler,
+) -> Option<(Ray, Color)> {
+    let mut rec = HitRecord::new();
+
+    for bounce in 0..max_vertices {
+        if !world.hit_sampled(&ray, 0.001, common::INFINITY, &mut rec, sampler) {
+            return Some((ray, beta));
+        }
+
+        let mat = rec.mat.clone().unwrap();
+        beta = beta * ray.transmittance(rec.t);
+        let mut vertex = Vertex {
+            p: rec.p,
+            n: rec.normal,
+            rec: Some(rec.clone()),
+            beta,
+            delta: mat.is_specular(),
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        };
+        let prev = path.len() - 1;
+        vertex.pdf_fwd = path[prev].convert_density(pdf_dir, &vertex);
+        path.push(vertex);
+
+        if bounce + 1 == max_vertices {
+            break;
+        }
+
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+        if !mat.scatter(&ray, &rec, &mut attenuation, &mut scattered, sampler) {
+            break;
+        }
+
+        let wo = -vec3::unit_vector(ray.direction());
+        let wi = vec3::unit_vector(scattered.direction());
+        let pdf_rev = if mat.is_specular() {
+            pdf_dir = 0.0;
+            0.0
+        } else {
+            pdf_dir = mat.pdf(&rec, wo, wi);
+            mat.pdf(&rec, wi, wo)
+        };
+        path[prev].pdf_rev = path[prev + 1].convert_density(pdf_rev, &path[prev]);
+
+        beta = beta * attenuation;
+        ray = scattered;
+    }
+
+    None
+}
+
+// Contribution of the path made of the first t camera and s light vertices
+fn connect(
+    world: &World,
+    r: &Ray,
+    camera_path: &[Vertex],
+    light_path: &[Vertex],
+    s: usize,
+    t: usize,
+    sampler: &mut dyn Sampler,
+) -> Color {
+    let pt = &camera_path[t - 1];
+    if pt.delta {
+        return Color::ZERO;
+    }
+
+    if s == 1 {
+        // Next event estimation with a freshly chosen light
+        let Some((light, light_pdf)) = pick_light(world, sampler.get_1d()) else {
+            return Color::ZERO;
+        };
+        let intensity = spectrum::for_ray(light.intensity(), r);
+        let sampled = Vertex::endpoint(light.position(), intensity / light_pdf, false, light_pdf);
+
+        let to_light = sampled.p - pt.p;
+        let f = spectrum::for_ray(pt.f(&camera_path[t - 2], &sampled), r);
+        if f.near_zero() || !integrator::visible(world, pt.p, sampled.p, sampler) {
+            return Color::ZERO;
+        }
+        let cos = vec3::dot(vec3::unit_vector(to_light), pt.n).abs();
+        let contribution = pt.beta * f * sampled.beta * cos / to_light.length_squared();
+        return contribution * mis_weight(camera_path, &[sampled], s, t);
+    }
+
+    let qs = &light_path[s - 1];
+    if qs.delta {
+        return Color::ZERO;
+    }
+
+    let f = spectrum::for_ray(qs.f(pt, &light_path[s - 2]), r) * spectrum::for_ray(pt.f(&camera_path[t - 2], qs), r);
+    if f.near_zero() || !integrator::visible(world, pt.p, qs.p, sampler) {
+        return Color::ZERO;
+    }
+    let d = pt.p - qs.p;
+    let dist_sq = d.length_squared();
+    let w = d / dist_sq.sqrt();
+    let g = vec3::dot(qs.n, w).abs() * vec3::dot(pt.n, w).abs() / dist_sq;
+
+    qs.beta * f * pt.beta * g * mis_weight(camera_path, light_path, s, t)
+}
+
+// Balance heuristic weight of the strategy (s, t) among all strategies that
+// could have produced the same path, computed from ratios of vertex densities
+fn mis_weight(camera_path: &[Vertex], light_path: &[Vertex], s: usize, t: usize) -> f32 {
+    let pt = &camera_path[t - 1];
+    let qs = &light_path[s - 1];
+    let pt_minus = &camera_path[t - 2];
+    let qs_minus = (s > 1).then(|| &light_path[s - 2]);
+
+    // (pdf_fwd, pdf_rev, delta) as they are once the two subpaths are joined
+    let mut camera: Vec<(f32, f32, bool)> = camera_path[..t].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+    let mut light: Vec<(f32, f32, bool)> = light_path[..s].iter().map(|v| (v.pdf_fwd, v.pdf_rev, v.delta)).collect();
+
+    camera[t - 1].1 = qs.pdf(qs_minus, pt);
+    camera[t - 1].2 = false;
+    camera[t - 2].1 = pt.pdf(Some(qs), pt_minus);
+    light[s - 1].1 = pt.pdf(Some(pt_minus), qs);
+    light[s - 1].2 = false;
+    if let Some(qs_minus) = qs_minus {
+        light[s - 2].1 = qs.pdf(Some(pt), qs_minus);
+    }
+
+    let remap = |f: f32| if f != 0.0 { f } else { 1.0 };
+    let mut sum = 0.0;
+
+    let mut ri = 1.0;
+    for i in (1..t).rev() {
+        ri *= remap(camera[i].1) / remap(camera[i].0);
+        if !camera[i].2 && !camera[i - 1].2 {
+            sum += ri;
+        }
+    }
+
+    // The point light cannot be hit, so the walk never reaches i = 0
+    let mut ri = 1.0;
+    for i in (1..s).rev() {
+        ri *= remap(light[i].1) / remap(light[i].0);
+        if !light[i].2 && !light[i - 1].2 {
+            sum += ri;
+        }
+    }
+
+    1.0 / (1.0 + sum)
+}
@@ -0,0 +1,309 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::core::camera::Camera;
+use crate::core::color::Color;
+use crate::core::vec3::{Point3, Vec3};
+use crate::material::material::{Glass, Lambertian, Material, Metal};
+use crate::objects::hittable::Hittable;
+use crate::objects::light::Light;
+use crate::objects::sphere::Sphere;
+use crate::objects::triangle::{self, Triangle};
+use crate::objects::world::{self, World};
+use crate::renderer::renderer::Renderer;
+use crate::renderer::settings::{IntegratorKind, RenderSettings};
+
+// A procedural scene that stresses one part of the renderer. Everything is
+// generated from a fixed seed, so every machine renders the same scene.
+pub struct BenchmarkScene {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub integrator: IntegratorKind,
+    pub samples_per_pixel: i32,
+    build: fn(&mut World, f32) -> Camera,
+}
+
+pub const SCENES: [BenchmarkScene; 4] = [
+    BenchmarkScene {
+        name: "spheres",
+        description: "2500 small diffuse, metal and glass spheres",
+        integrator: IntegratorKind::Classic,
+        samples_per_pixel: 16,
+        build: many_spheres,
+    },
+    BenchmarkScene {
+        name: "mesh",
+        description: "a displaced sphere of 260k triangles",
+        integrator: IntegratorKind::Classic,
+        samples_per_pixel: 8,
+        build: high_poly_mesh,
+    },
+    BenchmarkScene {
+        name: "glass",
+        description: "nested glass spheres and cubes, path traced",
+        integrator: IntegratorKind::Path,
+        samples_per_pixel: 16,
+        build: heavy_glass,
+    },
+    BenchmarkScene {
+        name: "lights",
+        description: "64 point lights over a field of spheres, every hit shades all of them",
+        integrator: IntegratorKind::Classic,
+        samples_per_pixel: 2,
+        build: many_lights,
+    },
+];
+
+pub fn scene_by_name(name: &str) -> Result<&'static BenchmarkScene> {
+    match SCENES.iter().find(|scene| scene.name == name) {
+        Some(scene) => Ok(scene),
+        None => bail!("Unknown benchmark scene '{name}', use spheres, mesh, glass or lights"),
+    }
+}
+
+impl BenchmarkScene {
+    // The scene's integrator and sample count at the given resolution
+    pub fn settings(&self, width: usize, height: usize) -> RenderSettings {
+        RenderSettings {
+            integrator: self.integrator,
+            samples_per_pixel: self.samples_per_pixel,
+            ..RenderSettings::new(width, height)
+        }
+    }
+
+    pub fn build(&self, world: &mut World, aspect_ratio: f32) -> Camera {
+        (self.build)(world, aspect_ratio)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub threads: usize,
+    pub scenes: Vec<SceneReport>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneReport {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: i32,
+    pub integrator: IntegratorKind,
+    pub primitives: usize,
+    pub lights: usize,
+    pub bvh_nodes: usize,
+    pub timings: PhaseTimings,
+    // Rays traced against the scene in the fastest run, camera, bounce and shadow rays alike
+    pub rays: u64,
+    pub rays_per_second: f64,
+    pub memory: MemoryReport,
+}
+
+// Seconds spent in every phase. The render is run several times and the
+// fastest run counts, it is the least disturbed by the rest of the machine.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    pub scene_seconds: f64,
+    pub bvh_seconds: f64,
+    pub render_seconds: f64,
+    pub render_runs_seconds: Vec<f64>,
+    // Tone mapping and PNG encoding
+    pub postprocess_seconds: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MemoryReport {
+    pub bvh_bytes: usize,
+    // Of the whole process, only known on Linux
+    pub resident_bytes: Option<u64>,
+    pub peak_resident_bytes: Option<u64>,
+}
+
+pub fn run(scene: &BenchmarkScene, settings: &RenderSettings, runs: usize) -> Result<SceneReport> {
+    settings.validate()?;
+    let start = Instant::now();
+    let mut world = World::new();
+    let camera = scene.build(&mut world, settings.width as f32 / settings.height as f32);
+    let scene_time = start.elapsed();
+
+    let start = Instant::now();
+    let bvh = world.build_bvh();
+    let bvh_time = start.elapsed();
+    let (bvh_nodes, bvh_bytes) = (bvh.node_count(), bvh.memory_bytes());
+    let (primitives, lights) = (world.primitive_count(), world.lights.len());
+    let renderer = Renderer::new(camera, world);
+
+    let mut render_runs = Vec::new();
+    let mut fastest: Option<(Duration, u64)> = None;
+    let mut image = None;
+    for _ in 0..runs.max(1) {
+        take_rays_traced();
+        let start = Instant::now();
+        image = Some(renderer.render(settings)?);
+        let time = start.elapsed();
+        let rays = take_rays_traced();
+        render_runs.push(time.as_secs_f64());
+        if fastest.is_none_or(|(best, _)| time < best) {
+            fastest = Some((time, rays));
+        }
+    }
+    let (render_time, rays) = fastest.unwrap();
+
+    let start = Instant::now();
+    image.unwrap().to_png(settings)?;
+    let postprocess_time = start.elapsed();
+
+    let (resident_bytes, peak_resident_bytes) = process_memory();
+    Ok(SceneReport {
+        name: scene.name.to_string(),
+        width: settings.width,
+        height: settings.height,
+        samples_per_pixel: settings.samples_per_pixel,
+        integrator: settings.integrator,
+        primitives,
+        lights,
+        bvh_nodes,
+        timings: PhaseTimings {
+            scene_seconds: scene_time.as_secs_f64(),
+            bvh_seconds: bvh_time.as_secs_f64(),
+            render_seconds: render_time.as_secs_f64(),
+            render_runs_seconds: render_runs,
+            postprocess_seconds: postprocess_time.as_secs_f64(),
+        },
+        rays,
+        rays_per_second: rays as f64 / render_time.as_secs_f64(),
+        memory: MemoryReport {
+            bvh_bytes,
+            resident_bytes,
+            peak_resident_bytes,
+        },
+    })
+}
+
+// Sum of the rays counted on every thread of the pool and the calling thread
+fn take_rays_traced() -> u64 {
+    rayon::broadcast(|_| world::take_rays_traced()).into_iter().sum::<u64>() + world::take_rays_traced()
+}
+
+// VmRSS and VmHWM from /proc
+fn process_memory() -> (Option<u64>, Option<u64>) {
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return (None, None);
+    };
+    let field = |name: &str| {
+        let line = status.lines().find(|line| line.starts_with(name))?;
+        let kb: u64 = line[name.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+        Some(kb * 1024)
+    };
+    (field("VmRSS:"), field("VmHWM:"))
+}
+
+fn add_triangles(world: &mut World, triangles: Vec<Triangle>) {
+    world.add_object(triangles.into_iter().map(|tri| Box::new(tri) as Box<dyn Hittable>));
+}
+
+fn ground(world: &mut World, size: f32) {
+    let grey = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let (a, b) = Triangle::make_quad(
+        Point3::new(-size, 0.0, -size),
+        Point3::new(size, 0.0, -size),
+        Point3::new(size, 0.0, size),
+        Point3::new(-size, 0.0, size),
+        grey,
+    );
+    add_triangles(world, vec![a, b]);
+}
+
+fn random_color(rng: &mut SmallRng) -> Color {
+    Color::new(rng.random(), rng.random(), rng.random())
+}
+
+fn many_spheres(world: &mut World, aspect_ratio: f32) -> Camera {
+    let mut rng = SmallRng::seed_from_u64(1);
+    ground(world, 100.0);
+    for i in 0..50 {
+        for j in 0..50 {
+            let radius = rng.random_range(0.1..0.2);
+            let center = Point3::new(i as f32 - 24.5 + rng.random_range(-0.3..0.3), radius, j as f32 - 24.5 + rng.random_range(-0.3..0.3));
+            let mat: Arc<dyn Material> = match rng.random_range(0..10) {
+                0..7 => Arc::new(Lambertian::new(random_color(&mut rng))),
+                7..9 => Arc::new(Metal::new(random_color(&mut rng), rng.random_range(0.0..0.5))),
+                _ => Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5)),
+            };
+            world.add_hittable(Box::new(Sphere::new(center, radius, mat)));
+        }
+    }
+    world.add_light(Light::new(Point3::new(10.0, 20.0, 10.0), Color::new(800.0, 800.0, 800.0)));
+    Camera::perspective(Point3::new(0.0, 6.0, 26.0), Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 50.0, aspect_ratio)
+}
+
+fn high_poly_mesh(world: &mut World, aspect_ratio: f32) -> Camera {
+    let (rings, segments) = (256, 512);
+    let point = |ring: usize, segment: usize| {
+        let theta = PI * ring as f32 / rings as f32;
+        let phi = 2.0 * PI * segment as f32 / segments as f32;
+        let radius = 1.0 + 0.08 * (12.0 * theta).sin() * (9.0 * phi).sin();
+        Point3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin()) * radius + Vec3::new(0.0, 1.2, 0.0)
+    };
+    let clay = Arc::new(Lambertian::new(Color::new(0.8, 0.4, 0.3)));
+    let mut triangles = Vec::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let (p00, p01) = (point(ring, segment), point(ring, segment + 1));
+            let (p10, p11) = (point(ring + 1, segment), point(ring + 1, segment + 1));
+            triangles.push(Triangle::new_untextured(p00, p10, p11, clay.clone()));
+            triangles.push(Triangle::new_untextured(p00, p11, p01, clay.clone()));
+        }
+    }
+    add_triangles(world, triangles);
+    ground(world, 20.0);
+    world.add_light(Light::new(Point3::new(3.0, 6.0, 4.0), Color::new(60.0, 60.0, 60.0)));
+    Camera::perspective(Point3::new(0.0, 2.0, 4.0), Point3::new(0.0, 1.1, 0.0), Vec3::new(0.0, 1.0, 0.0), 45.0, aspect_ratio)
+}
+
+fn heavy_glass(world: &mut World, aspect_ratio: f32) -> Camera {
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    let tinted = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5).with_absorption(Color::new(0.1, 0.4, 0.6)));
+    let air = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.0));
+    for i in 0..5 {
+        for j in 0..3 {
+            let center = Point3::new(i as f32 * 1.1 - 2.2, 0.5, -(j as f32) * 1.1);
+            if (i + j) % 2 == 0 {
+                // Hollow spheres, the rays pass from the glass into air inside
+                world.add_hittable(Box::new(Sphere::new(center, 0.5, glass.clone())));
+                world.add_hittable(Box::new(Sphere::new(center, 0.4, air.clone())));
+            } else {
+                let rotation = Vec3::new(0.3 * i as f32, 0.5 * j as f32, 0.0);
+                add_triangles(world, triangle::cube(center, 0.7, rotation, tinted.clone()));
+            }
+        }
+    }
+    ground(world, 20.0);
+    world.add_light(Light::new(Point3::new(-2.0, 5.0, 3.0), Color::new(60.0, 60.0, 60.0)));
+    Camera::perspective(Point3::new(0.0, 2.0, 4.0), Point3::new(0.0, 0.3, -1.0), Vec3::new(0.0, 1.0, 0.0), 50.0, aspect_ratio)
+}
+
+fn many_lights(world: &mut World, aspect_ratio: f32) -> Camera {
+    let mut rng = SmallRng::seed_from_u64(4);
+    ground(world, 50.0);
+    for i in 0..20 {
+        for j in 0..20 {
+            let center = Point3::new(i as f32 - 9.5, 0.3, j as f32 - 9.5);
+            let mat = Arc::new(Lambertian::new(random_color(&mut rng)));
+            world.add_hittable(Box::new(Sphere::new(center, 0.3, mat)));
+        }
+    }
+    for i in 0..8 {
+        for j in 0..8 {
+            let position = Point3::new(i as f32 * 2.6 - 9.1, rng.random_range(1.0..3.0), j as f32 * 2.6 - 9.1);
+            world.add_light(Light::new(position, random_color(&mut rng) * 2.0));
+        }
+    }
+    Camera::perspective(Point3::new(0.0, 8.0, 14.0), Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 55.0, aspect_ratio)
+}
@@ -0,0 +1,134 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, ensure};
+
+use crate::core::color::Color;
+use crate::renderer::adaptive::PixelStats;
+use crate::renderer::film::FilmPixel;
+use crate::renderer::image;
+use crate::renderer::settings::RenderSettings;
+
+const MAGIC: &[u8; 8] = b"RTCKPT01";
+
+// Everything needed to continue a render: the raw film sums and how far every
+// pixel got. Only whole tiles from the front of the queue are included.
+pub struct Checkpoint {
+    // Settings the film depends on, a checkpoint only continues a render with the same key
+    pub key: String,
+    pub width: usize,
+    pub height: usize,
+    // Bottom row first, like the film
+    pub film: Vec<FilmPixel>,
+    // Top row first, like the rendered pixels
+    pub stats: Vec<PixelStats>,
+}
+
+impl Checkpoint {
+    // Sample counts, thresholds and the output pipeline are left out, so more
+    // samples or a different tone mapper can still build on the same film
+    pub fn key(settings: &RenderSettings) -> String {
+        format!(
+            "{}x{} crop {:?} {:?} {:?} {:?} seed {} depth {} spectral {} photons {}x{} radius {} ao {} {}x{} shadows {} scene {}",
+            settings.width,
+            settings.height,
+            settings.crop,
+            settings.integrator,
+            settings.sampler,
+            settings.filter,
+            settings.seed,
+            settings.max_depth,
+            settings.spectral,
+            settings.photons,
+            settings.photon_passes,
+            settings.photon_radius,
+            settings.ambient_occlusion,
+            settings.ao_samples,
+            settings.ao_distance,
+            settings.transmissive_shadows,
+            settings.scene,
+        )
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        image::write_atomically(path, |tmp| {
+            let mut writer = BufWriter::new(File::create(tmp)?);
+            writer.write_all(MAGIC)?;
+            writer.write_all(&(self.key.len() as u32).to_le_bytes())?;
+            writer.write_all(self.key.as_bytes())?;
+            writer.write_all(&(self.width as u32).to_le_bytes())?;
+            writer.write_all(&(self.height as u32).to_le_bytes())?;
+            for p in &self.film {
+                for v in [p.sum.x(), p.sum.y(), p.sum.z(), p.weight] {
+                    writer.write_all(&v.to_le_bytes())?;
+                }
+            }
+            for stats in &self.stats {
+                let (samples, mean, m2) = stats.parts();
+                writer.write_all(&samples.to_le_bytes())?;
+                writer.write_all(&mean.to_le_bytes())?;
+                writer.write_all(&m2.to_le_bytes())?;
+            }
+            writer.flush()?;
+            Ok(())
+        })
+    }
+
+    // Reads the checkpoint of the render with the given key. The header is
+    // checked before anything is allocated for the pixels.
+    pub fn load(path: &Path, expected_key: &str) -> Result<Self> {
+        let read = || -> Result<Self> {
+            let file = File::open(path)?;
+            let file_len = file.metadata()?.len();
+            let mut reader = BufReader::new(file);
+            let mut word = [0u8; 4];
+            let mut next = |reader: &mut BufReader<File>| -> Result<[u8; 4]> {
+                reader.read_exact(&mut word)?;
+                Ok(word)
+            };
+
+            let mut magic = [0u8; 8];
+            reader.read_exact(&mut magic)?;
+            ensure!(&magic == MAGIC, "Not a checkpoint file");
+            let key_len = u32::from_le_bytes(next(&mut reader)?) as usize;
+            ensure!(key_len == expected_key.len(), "Checkpoint belongs to other settings");
+            let mut key = vec![0u8; key_len];
+            reader.read_exact(&mut key)?;
+            let key = String::from_utf8(key)?;
+            ensure!(key == expected_key, "Checkpoint belongs to other settings: {key} instead of {expected_key}");
+            let width = u32::from_le_bytes(next(&mut reader)?) as usize;
+            let height = u32::from_le_bytes(next(&mut reader)?) as usize;
+            // Film sums and weight, then sample count, mean and M2, four bytes each
+            let header = (MAGIC.len() + 12 + key_len) as u64;
+            let expected_len = (width as u64 * height as u64).checked_mul(4 * 4 + 3 * 4).and_then(|n| n.checked_add(header));
+            ensure!(expected_len == Some(file_len), "Checkpoint size does not match its {width}x{height} pixels");
+
+            let mut film = Vec::with_capacity(width * height);
+            for _ in 0..width * height {
+                let [r, g, b, weight] = [(); 4].map(|_| next(&mut reader).map(f32::from_le_bytes));
+                film.push(FilmPixel {
+                    sum: Color::new(r?, g?, b?),
+                    weight: weight?,
+                });
+            }
+            let mut stats = Vec::with_capacity(width * height);
+            for _ in 0..width * height {
+                let samples = u32::from_le_bytes(next(&mut reader)?);
+                let mean = f32::from_le_bytes(next(&mut reader)?);
+                let m2 = f32::from_le_bytes(next(&mut reader)?);
+                stats.push(PixelStats::from_parts(samples, mean, m2));
+            }
+            ensure!(reader.read(&mut [0])? == 0, "Trailing data after the pixels");
+
+            Ok(Self {
+                key,
+                width,
+                height,
+                film,
+                stats,
+            })
+        };
+        read().with_context(|| format!("Failed to read checkpoint {}", path.display()))
+    }
+}
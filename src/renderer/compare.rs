@@ -0,0 +1,94 @@
+use crate::core::color::Color;
+
+// Standard deviation of the Gaussian window in pixels, it spans 11×11
+const SIGMA: f32 = 1.5;
+const RADIUS: usize = 5;
+// Stabilizers for dark and flat regions, for values from 0 to 1
+const C1: f32 = 0.01 * 0.01;
+const C2: f32 = 0.03 * 0.03;
+
+// Structural similarity (Wang et al. 2004) of two images, per pixel and the mean
+pub struct Similarity {
+    pub width: usize,
+    pub height: usize,
+    // 1 for identical images, lower the more they differ
+    pub ssim: f32,
+    pub map: Vec<f32>,
+}
+
+// Compares the luminance of two images encoded for display, top row first
+pub fn ssim(width: usize, height: usize, a: &[Color], b: &[Color]) -> Similarity {
+    assert_eq!(a.len(), width * height, "pixel count does not match the size");
+    assert_eq!(b.len(), width * height, "pixel count does not match the size");
+    let a: Vec<f32> = a.iter().map(luminance).collect();
+    let b: Vec<f32> = b.iter().map(luminance).collect();
+    let product = |x: &[f32], y: &[f32]| x.iter().zip(y).map(|(x, y)| x * y).collect::<Vec<f32>>();
+
+    let mean_a = blur(&a, width, height);
+    let mean_b = blur(&b, width, height);
+    let square_a = blur(&product(&a, &a), width, height);
+    let square_b = blur(&product(&b, &b), width, height);
+    let ab = blur(&product(&a, &b), width, height);
+
+    let map: Vec<f32> = (0..width * height)
+        .map(|i| {
+            let (ma, mb) = (mean_a[i], mean_b[i]);
+            let variance_a = square_a[i] - ma * ma;
+            let variance_b = square_b[i] - mb * mb;
+            let covariance = ab[i] - ma * mb;
+            ((2.0 * ma * mb + C1) * (2.0 * covariance + C2)) / ((ma * ma + mb * mb + C1) * (variance_a + variance_b + C2))
+        })
+        .collect();
+    Similarity {
+        width,
+        height,
+        ssim: map.iter().sum::<f32>() / map.len().max(1) as f32,
+        map,
+    }
+}
+
+impl Similarity {
+    // Black where the images match, through red to yellow where they differ most
+    pub fn diff_image(&self) -> Vec<Color> {
+        self.map
+            .iter()
+            .map(|&s| {
+                let d = (2.0 * (1.0 - s)).clamp(0.0, 1.0);
+                Color::new((2.0 * d).min(1.0), (2.0 * d - 1.0).max(0.0), 0.0)
+            })
+            .collect()
+    }
+}
+
+fn luminance(c: &Color) -> f32 {
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
+// Separable Gaussian, renormalized where the window leaves the image
+fn blur(values: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let weights: Vec<f32> = (0..=2 * RADIUS)
+        .map(|i| {
+            let x = i as f32 - RADIUS as f32;
+            (-x * x / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+    let pass = |values: &[f32], along_x: bool| {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                let (position, length) = if along_x { (x, width) } else { (y, height) };
+                let (mut sum, mut total) = (0.0, 0.0);
+                for (k, weight) in weights.iter().enumerate() {
+                    let Some(p) = (position + k).checked_sub(RADIUS).filter(|&p| p < length) else {
+                        continue;
+                    };
+                    let index = if along_x { y * width + p } else { p * width + x };
+                    sum += weight * values[index];
+                    total += weight;
+                }
+                sum / total
+            })
+            .collect::<Vec<f32>>()
+    };
+    pass(&pass(values, true), false)
+}
@@ -0,0 +1,88 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::core::color::Color;
+use crate::core::common;
+use crate::core::ray::Ray;
+use crate::core::sampler::Sampler;
+use crate::core::spectrum;
+use crate::core::vec3::Vec3;
+
+use crate::objects::hittable::HitRecord;
+use crate::objects::world::World;
+
+use crate::renderer::adaptive;
+use crate::renderer::aov::LightPasses;
+use crate::renderer::integrator::Integrator;
+
+// Checker squares along u and v
+const UV_CHECKS: f32 = 8.0;
+// Box and primitive tests shown as the hottest colour
+const MAX_BVH_COST: u32 = 128;
+
+// What the debug integrator shows of the first surface a camera ray hits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum DebugView {
+    // Normal used for shading, facing the camera, mapped from -1..1 to 0..1
+    #[value(name = "normals")]
+    ShadingNormals,
+    // Outward normal of the surface whichever side was hit
+    GeometricNormals,
+    // u and v as red and green over a checkerboard, flat blue without UVs
+    #[value(name = "uv")]
+    UvChecker,
+    // Triangle vertex weights as red, green and blue
+    Barycentrics,
+    // Brighter the closer, 1 / (1 + distance)
+    Depth,
+    // Green for front faces, red for back faces
+    FrontFace,
+    // A colour per material, the same within one render
+    MaterialId,
+    // Intersection tests of the camera ray from blue (none) to red (MAX_BVH_COST)
+    BvhCost,
+}
+
+pub struct DebugIntegrator {
+    pub view: DebugView,
+}
+
+impl Integrator for DebugIntegrator {
+    fn passes(&self, r: &Ray, world: &World, _depth: i32, _sampler: &mut dyn Sampler) -> LightPasses {
+        let mut rec = HitRecord::new();
+        let (hit, cost) = world.hit_with_cost(r, 0.001, common::INFINITY, &mut rec);
+        let color = match self.view {
+            DebugView::BvhCost => adaptive::heatmap_color((cost as u32).min(MAX_BVH_COST), 0, MAX_BVH_COST),
+            _ if !hit => Color::ZERO,
+            DebugView::ShadingNormals => normal_color(rec.normal),
+            DebugView::GeometricNormals => normal_color(rec.geometric_normal),
+            DebugView::UvChecker => {
+                let (u, v) = (rec.u, rec.v);
+                let check = ((u * UV_CHECKS).floor() + (v * UV_CHECKS).floor()).rem_euclid(2.0);
+                Color::new(u.fract().abs(), v.fract().abs(), 0.5) * (0.4 + 0.6 * check)
+            }
+            DebugView::Barycentrics => {
+                let (b1, b2) = rec.barycentrics;
+                Color::new(1.0 - b1 - b2, b1, b2)
+            }
+            DebugView::Depth => Color::new(1.0, 1.0, 1.0) / (1.0 + rec.t * r.direction().length()),
+            DebugView::FrontFace if rec.front_face => Color::new(0.0, 1.0, 0.0),
+            DebugView::FrontFace => Color::new(1.0, 0.0, 0.0),
+            DebugView::MaterialId => rec.mat.as_ref().map_or(Color::ZERO, |mat| {
+                let mut hasher = DefaultHasher::new();
+                (Arc::as_ptr(mat) as *const () as usize).hash(&mut hasher);
+                let bits = hasher.finish();
+                let channel = |shift: u64| 0.2 + 0.8 * ((bits >> shift) & 0xff) as f32 / 255.0;
+                Color::new(channel(0), channel(8), channel(16))
+            }),
+        };
+        LightPasses::emission(spectrum::for_ray(color, r))
+    }
+}
+
+fn normal_color(n: Vec3) -> Color {
+    0.5 * (n + Color::new(1.0, 1.0, 1.0))
+}
@@ -0,0 +1,96 @@
+use rayon::prelude::*;
+
+use crate::core::color::{Color, luminance};
+
+use crate::renderer::aov::Aovs;
+
+// Filter passes, the footprint doubles with each of them
+const ITERATIONS: usize = 5;
+// B3 spline, the same 1D kernel at every scale
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// How far the guides may differ before two pixels stop mixing
+const SIGMA_NORMAL: f32 = 0.3;
+const SIGMA_ALBEDO: f32 = 0.1;
+// Luminance differences are judged in standard deviations of the noise. SVGF
+// uses 4, half of it keeps the contact shadows that no guide can see.
+const SIGMA_LUMINANCE: f32 = 2.0;
+
+// Edge-avoiding À-trous wavelet filter (Dammertz et al. 2010) with the
+// luminance weight scaled by the per pixel noise like SVGF (Schied et al.
+// 2017). Pixels only blend when their normals and albedos agree, so geometric
+// and texture edges stay sharp while the noise in flat regions averages out.
+// Every output pixel depends only on the input, the result is deterministic.
+pub fn denoise(pixels: &[Color], aovs: &Aovs) -> Vec<Color> {
+    let (width, height) = (aovs.width, aovs.height);
+    let mut color = pixels.to_vec();
+    let mut variance = blur_variance(&aovs.variance, width, height);
+
+    for iteration in 0..ITERATIONS {
+        let step = 1 << iteration;
+        let filtered: Vec<(Color, f32)> = (0..width * height)
+            .into_par_iter()
+            .map(|p| {
+                let (x, y) = (p % width, p / width);
+                let (c_p, n_p, a_p) = (color[p], aovs.normal[p], aovs.albedo[p]);
+                let l_p = luminance(c_p);
+                let scale = SIGMA_LUMINANCE * variance[p].sqrt() + 1e-4;
+
+                let mut sum = Color::ZERO;
+                let mut sum_variance = 0.0;
+                let mut total = 0.0;
+                for (dy, ky) in KERNEL.iter().enumerate() {
+                    let qy = y as isize + (dy as isize - 2) * step;
+                    if qy < 0 || qy >= height as isize {
+                        continue;
+                    }
+                    for (dx, kx) in KERNEL.iter().enumerate() {
+                        let qx = x as isize + (dx as isize - 2) * step;
+                        if qx < 0 || qx >= width as isize {
+                            continue;
+                        }
+                        let q = qy as usize * width + qx as usize;
+                        let w_normal = (-(n_p - aovs.normal[q]).length_squared() / (SIGMA_NORMAL * SIGMA_NORMAL)).exp();
+                        let w_albedo = (-(a_p - aovs.albedo[q]).length_squared() / (SIGMA_ALBEDO * SIGMA_ALBEDO)).exp();
+                        let w_luminance = (-(l_p - luminance(color[q])).abs() / scale).exp();
+                        let w = kx * ky * w_normal * w_albedo * w_luminance;
+
+                        sum += color[q] * w;
+                        sum_variance += w * w * variance[q];
+                        total += w;
+                    }
+                }
+                // The centre tap always has a positive weight
+                (sum / total, sum_variance / (total * total))
+            })
+            .collect();
+
+        (color, variance) = filtered.into_iter().unzip();
+    }
+
+    color
+}
+
+// Variance estimates from few samples are noisy themselves, a small blur keeps
+// single outliers from switching the filter off
+fn blur_variance(variance: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let kernel = [0.25, 0.5, 0.25];
+    (0..width * height)
+        .map(|p| {
+            let (x, y) = (p % width, p / width);
+            let mut sum = 0.0;
+            let mut total = 0.0;
+            for (dy, ky) in kernel.iter().enumerate() {
+                for (dx, kx) in kernel.iter().enumerate() {
+                    let (qx, qy) = ((x + dx).wrapping_sub(1), (y + dy).wrapping_sub(1));
+                    if qx < width && qy < height {
+                        sum += kx * ky * variance[qy * width + qx];
+                        total += kx * ky;
+                    }
+                }
+            }
+            sum / total
+        })
+        .collect()
+}
+
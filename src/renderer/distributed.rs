@@ -0,0 +1,485 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::ops::Range;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use serde::{Deserialize, Serialize};
+
+use crate::core::color::Color;
+use crate::renderer::adaptive::PixelStats;
+use crate::renderer::film::{Film, FilmPixel, FilmTile, Filter};
+use crate::renderer::integrator;
+use crate::renderer::progress::{CancelToken, Progress, ProgressReport};
+use crate::renderer::renderer::{self, RenderOutput, Renderer};
+use crate::renderer::settings::RenderSettings;
+
+// Message tags, every message is a tag, a payload length and the payload
+const JOB: u8 = 1;
+const TILE: u8 = 2;
+const TILE_DONE: u8 = 3;
+const FAILED: u8 = 4;
+
+// Far more than a job or a tile takes. Longer messages are refused before
+// their payload is allocated.
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+// A tile that failed on this many workers fails the render
+const MAX_ATTEMPTS: u32 = 3;
+// How often the coordinator looks for new workers
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(20);
+
+// Everything a worker needs to build and render the same image
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    // Handed to the workers' scene builder as is, e.g. a scene name and model URL
+    pub scene: String,
+    pub model: String,
+    pub angle: f32,
+    pub settings: RenderSettings,
+}
+
+// Splits renders into tiles for the workers connected to it. Tiles of a worker
+// that disconnects or stops answering go to the next free one. The result is
+// bit-identical to rendering on one machine, tiles are merged in the same order.
+pub struct Coordinator {
+    listener: TcpListener,
+    // Workers stay connected between renders, e.g. for animation frames
+    idle: Mutex<Vec<TcpStream>>,
+    // A worker that takes longer than this for one tile is given up on
+    pub tile_timeout: Duration,
+}
+
+impl Coordinator {
+    pub fn bind(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr).context("Failed to listen for workers")?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            idle: Mutex::new(Vec::new()),
+            tile_timeout: Duration::from_secs(600),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    // Waits for workers as needed, new ones can join at any time
+    pub fn render(&self, job: &Job, progress: &dyn Progress, cancel: &CancelToken) -> Result<RenderOutput> {
+        let settings = &job.settings;
+        settings.validate()?;
+        ensure!(!settings.aovs && !settings.denoise, "AOVs and denoising are not supported by distributed renders");
+        ensure!(settings.checkpoint.is_none(), "Checkpoints are not supported by distributed renders");
+
+        let job_text = toml::to_string(job).context("Failed to serialize the job")?;
+        let tiles = renderer::tile_grid(settings);
+        let (width, height) = (settings.width, settings.height);
+        let queue = TileQueue::new(tiles.len());
+        let merger = Mutex::new(Merger {
+            film: Film::new(width, height, Filter::new(settings.filter)),
+            next: 0,
+            pending: BTreeMap::new(),
+            stats: vec![PixelStats::default(); width * height],
+            tiles_done: 0,
+            pixels_done: 0,
+        });
+        let start = Instant::now();
+        let reusable = Mutex::new(Vec::new());
+
+        let run = Run {
+            settings,
+            job_text: &job_text,
+            tiles: &tiles,
+            queue: &queue,
+            merger: &merger,
+            progress,
+            start,
+            tile_timeout: self.tile_timeout,
+        };
+        thread::scope(|scope| {
+            let spawn = |stream: TcpStream| {
+                let (run, reusable) = (&run, &reusable);
+                scope.spawn(move || {
+                    if let Some(stream) = run.serve(stream) {
+                        reusable.lock().unwrap().push(stream);
+                    }
+                });
+            };
+            for stream in self.idle.lock().unwrap().drain(..) {
+                spawn(stream);
+            }
+            while !queue.finished() {
+                if cancel.is_cancelled() {
+                    queue.cancel();
+                    break;
+                }
+                match self.listener.accept() {
+                    Ok((stream, _)) => spawn(stream),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+                    Err(err) => queue.fail(anyhow!(err).context("Failed to accept a worker")),
+                }
+            }
+        });
+        self.idle.lock().unwrap().extend(reusable.into_inner().unwrap());
+
+        let (remaining, error) = queue.into_result();
+        if let Some(err) = error {
+            return Err(err);
+        }
+        let mut merger = merger.into_inner().unwrap();
+        merger.merge_rest();
+
+        let crop = settings.crop_window();
+        let mut pixels = merger.film.resolve();
+        let mut samples: Vec<u32> = merger.stats.iter().map(|s| s.samples()).collect();
+        if settings.crop.is_some() {
+            for p in (0..width * height).filter(|p| !crop.contains(p % width, p / width)) {
+                pixels[p] = Color::ZERO;
+                samples[p] = 0;
+            }
+        }
+        Ok(RenderOutput {
+            pixels,
+            samples,
+            aovs: None,
+            cancelled: remaining > 0,
+        })
+    }
+}
+
+// Connects to a coordinator and renders the tiles it hands out until it hangs
+// up. build turns a job into the scene and runs again for every new job.
+pub fn run_worker(addr: impl ToSocketAddrs, mut build: impl FnMut(&Job) -> Result<Renderer>) -> Result<()> {
+    let stream = TcpStream::connect(addr).context("Failed to connect to the coordinator")?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut current = None;
+
+    loop {
+        let (tag, payload) = match receive(&mut reader) {
+            Ok(message) => message,
+            // The coordinator is done with us
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        match tag {
+            JOB => {
+                let job: Job = toml::from_str(std::str::from_utf8(&payload)?).context("Failed to read the job")?;
+                let renderer = match build(&job) {
+                    Ok(renderer) => renderer,
+                    Err(err) => {
+                        send(&mut writer, FAILED, format!("{err:#}").as_bytes())?;
+                        return Err(err);
+                    }
+                };
+                // Tiles are rendered from scratch here, so keep the integrator of every pass
+                let integrators: Vec<_> =
+                    (0..job.settings.render_passes()).map(|pass| integrator::create(&job.settings, &renderer.world, pass)).collect();
+                let film = Film::new(job.settings.width, job.settings.height, Filter::new(job.settings.filter));
+                current = Some((job, renderer, integrators, film));
+            }
+            TILE => {
+                let Some((job, renderer, integrators, film)) = &current else {
+                    bail!("Coordinator sent a tile before the job");
+                };
+                let mut payload = Payload::new(&payload);
+                let index = payload.u32()?;
+                let columns = payload.u32()? as usize..payload.u32()? as usize;
+                let rows = payload.u32()? as usize..payload.u32()? as usize;
+                ensure!(columns.end <= film.width() && rows.end <= film.height(), "Tile outside the image");
+
+                let mut tile = film.tile(columns.clone(), rows.clone());
+                let stats = renderer.render_tile(&job.settings, integrators, &mut tile, &columns, &rows);
+                send(&mut writer, TILE_DONE, &encode_tile(index, &tile, &stats))?;
+            }
+            _ => bail!("Unknown message {tag} from the coordinator"),
+        }
+    }
+}
+
+// State of one distributed render shared by the threads serving the workers
+struct Run<'a> {
+    settings: &'a RenderSettings,
+    job_text: &'a str,
+    tiles: &'a [(Range<usize>, Range<usize>)],
+    queue: &'a TileQueue,
+    merger: &'a Mutex<Merger>,
+    progress: &'a dyn Progress,
+    start: Instant,
+    tile_timeout: Duration,
+}
+
+impl Run<'_> {
+    // Hands tiles to one worker until there are none left. Gives the
+    // connection back if the worker is still healthy.
+    fn serve(&self, stream: TcpStream) -> Option<TcpStream> {
+        stream.set_nonblocking(false).ok()?;
+        stream.set_read_timeout(Some(self.tile_timeout)).ok()?;
+        let mut reader = BufReader::new(stream.try_clone().ok()?);
+        let mut writer = BufWriter::new(stream);
+        // Nothing to retry yet if the worker is gone already
+        send(&mut writer, JOB, self.job_text.as_bytes()).ok()?;
+
+        while let Some(index) = self.queue.next() {
+            let (columns, rows) = &self.tiles[index];
+            let mut request = Vec::new();
+            for v in [index, columns.start, columns.end, rows.start, rows.end] {
+                request.extend_from_slice(&(v as u32).to_le_bytes());
+            }
+            // A connection that died while idle is no fault of the tile
+            if send(&mut writer, TILE, &request).is_err() {
+                self.queue.put_back(index);
+                return None;
+            }
+            match self.receive_tile(&mut reader, index) {
+                Ok(Some((tile, stats))) => self.finish(index, tile, stats),
+                Ok(None) => return None,
+                Err(err) => {
+                    self.queue.retry(index, err);
+                    return None;
+                }
+            }
+        }
+        writer.into_inner().ok()
+    }
+
+    // None if the worker could not render the job at all, which fails the render
+    fn receive_tile(&self, reader: &mut impl Read, index: usize) -> Result<Option<(FilmTile, Vec<PixelStats>)>> {
+        let (columns, rows) = &self.tiles[index];
+        let (tag, payload) = receive(reader)?;
+        if tag == FAILED {
+            let message = String::from_utf8_lossy(&payload);
+            self.queue.fail(anyhow!("A worker could not render the job: {message}"));
+            return Ok(None);
+        }
+        ensure!(tag == TILE_DONE, "Unexpected message {tag} from a worker");
+
+        let mut tile = self.merger.lock().unwrap().film.tile(columns.clone(), rows.clone());
+        let stats = decode_tile(&payload, index, &mut tile)?;
+        ensure!(stats.len() == columns.len() * rows.len(), "Worker sent {} pixels instead of {}", stats.len(), columns.len() * rows.len());
+        Ok(Some((tile, stats)))
+    }
+
+    fn finish(&self, index: usize, tile: FilmTile, stats: Vec<PixelStats>) {
+        let (columns, rows) = &self.tiles[index];
+        let (width, height) = (self.settings.width, self.settings.height);
+        let pixels = rows.clone().rev().flat_map(|j| columns.clone().map(move |i| (height - 1 - j) * width + i));
+
+        let mut merger = self.merger.lock().unwrap();
+        merger.pending.insert(index, (tile, pixels.zip(stats).collect()));
+        merger.merge_ready();
+        merger.tiles_done += 1;
+        merger.pixels_done += columns.len() * rows.len();
+        self.progress.update(&ProgressReport {
+            tiles_done: merger.tiles_done,
+            tiles_total: self.tiles.len(),
+            pixels_done: merger.pixels_done,
+            pixels_total: width * height,
+            elapsed: self.start.elapsed(),
+        });
+        self.progress.film_updated(&merger.film);
+        drop(merger);
+        self.queue.done();
+    }
+}
+
+// Merges tiles strictly in queue order, like a local render
+struct Merger {
+    film: Film,
+    next: usize,
+    pending: BTreeMap<usize, (FilmTile, Vec<(usize, PixelStats)>)>,
+    stats: Vec<PixelStats>,
+    tiles_done: usize,
+    pixels_done: usize,
+}
+
+impl Merger {
+    fn merge_ready(&mut self) {
+        while let Some(tile) = self.pending.remove(&self.next) {
+            self.merge(tile);
+            self.next += 1;
+        }
+    }
+
+    fn merge_rest(&mut self) {
+        for tile in std::mem::take(&mut self.pending).into_values() {
+            self.merge(tile);
+        }
+    }
+
+    fn merge(&mut self, (tile, stats): (FilmTile, Vec<(usize, PixelStats)>)) {
+        self.film.merge(&tile);
+        for (p, stats) in stats {
+            self.stats[p] = stats;
+        }
+    }
+}
+
+struct TileQueue {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+struct QueueState {
+    todo: VecDeque<usize>,
+    attempts: Vec<u32>,
+    // Tiles not rendered yet, including those a worker is busy with
+    remaining: usize,
+    cancelled: bool,
+    error: Option<anyhow::Error>,
+}
+
+impl TileQueue {
+    fn new(tiles: usize) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                todo: (0..tiles).collect(),
+                attempts: vec![0; tiles],
+                remaining: tiles,
+                cancelled: false,
+                error: None,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    // Waits while other workers hold the last tiles, they might still fail
+    fn next(&self) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.remaining == 0 || state.cancelled || state.error.is_some() {
+                return None;
+            }
+            if let Some(index) = state.todo.pop_front() {
+                return Some(index);
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn done(&self) {
+        self.state.lock().unwrap().remaining -= 1;
+        self.changed.notify_all();
+    }
+
+    // Puts the tile of a lost worker back at the front of the queue
+    fn retry(&self, index: usize, err: anyhow::Error) {
+        let mut state = self.state.lock().unwrap();
+        state.attempts[index] += 1;
+        if state.attempts[index] >= MAX_ATTEMPTS {
+            state.error.get_or_insert(err.context(format!("Tile {index} failed on {MAX_ATTEMPTS} workers")));
+        } else {
+            state.todo.push_front(index);
+        }
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    fn put_back(&self, index: usize) {
+        self.state.lock().unwrap().todo.push_front(index);
+        self.changed.notify_all();
+    }
+
+    fn fail(&self, err: anyhow::Error) {
+        self.state.lock().unwrap().error.get_or_insert(err);
+        self.changed.notify_all();
+    }
+
+    fn cancel(&self) {
+        self.state.lock().unwrap().cancelled = true;
+        self.changed.notify_all();
+    }
+
+    fn finished(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.remaining == 0 || state.cancelled || state.error.is_some()
+    }
+
+    fn into_result(self) -> (usize, Option<anyhow::Error>) {
+        let state = self.state.into_inner().unwrap();
+        (state.remaining, state.error)
+    }
+}
+
+fn send(writer: &mut impl Write, tag: u8, payload: &[u8]) -> io::Result<()> {
+    writer.write_all(&[tag])?;
+    writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()
+}
+
+fn receive(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 5];
+    reader.read_exact(&mut header)?;
+    let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(ErrorKind::InvalidData, format!("Message of {len} bytes, at most {MAX_MESSAGE_SIZE} are allowed")));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+// Tile index, the raw film sums and the statistics of every pixel
+fn encode_tile(index: u32, tile: &FilmTile, stats: &[PixelStats]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(12 + tile.pixels().len() * 16 + stats.len() * 12);
+    bytes.extend_from_slice(&index.to_le_bytes());
+    bytes.extend_from_slice(&(tile.pixels().len() as u32).to_le_bytes());
+    for p in tile.pixels() {
+        for v in [p.sum.x(), p.sum.y(), p.sum.z(), p.weight] {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(&(stats.len() as u32).to_le_bytes());
+    for s in stats {
+        let (samples, mean, m2) = s.parts();
+        bytes.extend_from_slice(&samples.to_le_bytes());
+        bytes.extend_from_slice(&mean.to_le_bytes());
+        bytes.extend_from_slice(&m2.to_le_bytes());
+    }
+    bytes
+}
+
+fn decode_tile(bytes: &[u8], index: usize, tile: &mut FilmTile) -> Result<Vec<PixelStats>> {
+    let mut payload = Payload::new(bytes);
+    ensure!(payload.u32()? as usize == index, "Worker answered with another tile");
+    ensure!(payload.u32()? as usize == tile.pixels().len(), "Worker sent a tile of the wrong size");
+    for p in tile.pixels_mut() {
+        let sum = Color::new(payload.f32()?, payload.f32()?, payload.f32()?);
+        *p = FilmPixel { sum, weight: payload.f32()? };
+    }
+    let count = payload.u32()? as usize;
+    let mut stats = Vec::with_capacity(count);
+    for _ in 0..count {
+        stats.push(PixelStats::from_parts(payload.u32()?, payload.f32()?, payload.f32()?));
+    }
+    Ok(stats)
+}
+
+struct Payload<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Payload<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn word(&mut self) -> Result<[u8; 4]> {
+        ensure!(self.bytes.len() >= 4, "Message ends too early");
+        let (word, rest) = self.bytes.split_at(4);
+        self.bytes = rest;
+        Ok([word[0], word[1], word[2], word[3]])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.word()?))
+    }
+
+    fn f32(&mut self) -> Result<f32> {
+        Ok(f32::from_le_bytes(self.word()?))
+    }
+}
@@ -0,0 +1,249 @@
+use std::ops::Range;
+
+use anyhow::{Result, ensure};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::core::color::Color;
+use crate::core::common::PI;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum FilterKind {
+    // Every sample counts only for its own pixel
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+    BlackmanHarris,
+}
+
+// Pixel reconstruction filter, separable in x and y
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    kind: FilterKind,
+    radius: f32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind) -> Self {
+        let radius = match kind {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+            FilterKind::BlackmanHarris => 1.5,
+        };
+        Self { kind, radius }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    // Weight of a sample at offset (x, y) from the pixel centre
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f32) -> f32 {
+        let r = self.radius;
+        match self.kind {
+            // Half open, so a sample on a pixel border lands in exactly one pixel
+            FilterKind::Box => {
+                if -r < x && x <= r {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            FilterKind::Tent => (r - x.abs()).max(0.0),
+            FilterKind::Gaussian => {
+                let sigma = 0.5;
+                let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+                (gaussian(x) - gaussian(r)).max(0.0)
+            }
+            // B = C = 1/3 as recommended by Mitchell and Netravali (1988)
+            FilterKind::Mitchell => {
+                let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+                let x = x.abs() * 2.0 / r;
+                if x < 1.0 {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x * x * x + (-18.0 + 12.0 * b + 6.0 * c) * x * x + (6.0 - 2.0 * b))
+                        / 6.0
+                } else if x < 2.0 {
+                    ((-b - 6.0 * c) * x * x * x + (6.0 * b + 30.0 * c) * x * x + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    0.0
+                }
+            }
+            // Sinc windowed by a wider sinc
+            FilterKind::Lanczos => {
+                if x.abs() >= r {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / r)
+                }
+            }
+            FilterKind::BlackmanHarris => {
+                if x.abs() >= r {
+                    return 0.0;
+                }
+                let t = 2.0 * PI * (x / (2.0 * r) + 0.5);
+                0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos()
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    let x = x.abs();
+    if x < 1e-5 {
+        return 1.0;
+    }
+    (PI * x).sin() / (PI * x)
+}
+
+// Filter weighted sample sum and total weight of one pixel
+#[derive(Clone, Copy, Default)]
+pub struct FilmPixel {
+    pub sum: Color,
+    pub weight: f32,
+}
+
+// Filter weighted sum of all samples per pixel. Film coordinates have y = 0
+// at the bottom like the camera's v, pixel (i, j) covers [i, i + 1) x [j, j + 1).
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        Self {
+            width,
+            height,
+            filter,
+            pixels: vec![FilmPixel::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Raw sums, bottom row first, e.g. to continue the render later
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    pub fn restore(&mut self, pixels: Vec<FilmPixel>) -> Result<()> {
+        ensure!(
+            pixels.len() == self.width * self.height,
+            "Film of {} pixels does not fit a {}x{} image",
+            pixels.len(),
+            self.width,
+            self.height
+        );
+        self.pixels = pixels;
+        Ok(())
+    }
+
+    // Private buffer for the samples of a block of pixels and every pixel
+    // their filter reaches, so tiles can be rendered in parallel
+    pub fn tile(&self, columns: Range<usize>, rows: Range<usize>) -> FilmTile {
+        let reach = self.filter.radius().ceil() as usize;
+        let x0 = columns.start.saturating_sub(reach);
+        let x1 = (columns.end + reach).min(self.width);
+        let y0 = rows.start.saturating_sub(reach);
+        let y1 = (rows.end + reach).min(self.height);
+        FilmTile {
+            x0,
+            x1,
+            y0,
+            y1,
+            filter: self.filter,
+            pixels: vec![FilmPixel::default(); (x1 - x0) * (y1 - y0)],
+        }
+    }
+
+    // Tiles must be merged in a fixed order for bit-identical results, float
+    // addition is not associative
+    pub fn merge(&mut self, tile: &FilmTile) {
+        let tile_width = tile.x1 - tile.x0;
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let src = tile.pixels[(y - tile.y0) * tile_width + x - tile.x0];
+                let dst = &mut self.pixels[y * self.width + x];
+                dst.sum += src.sum;
+                dst.weight += src.weight;
+            }
+        }
+    }
+
+    // Reconstructed colours, top row first. Filters with negative lobes can
+    // ring below zero next to bright edges, which is clamped away.
+    pub fn resolve(&self) -> Vec<Color> {
+        (0..self.height)
+            .rev()
+            .flat_map(|y| self.pixels[y * self.width..(y + 1) * self.width].iter())
+            .map(|p| {
+                if p.weight == 0.0 {
+                    return Color::ZERO;
+                }
+                let c = p.sum / p.weight;
+                Color::new(c.x().max(0.0), c.y().max(0.0), c.z().max(0.0))
+            })
+            .collect()
+    }
+}
+
+pub struct FilmTile {
+    x0: usize,
+    x1: usize,
+    y0: usize,
+    y1: usize,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl FilmTile {
+    // Raw sums of every pixel the tile reaches, bottom row first
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [FilmPixel] {
+        &mut self.pixels
+    }
+
+    // Splats a sample at film position (x, y) into every pixel in filter reach
+    pub fn add_sample(&mut self, x: f32, y: f32, color: Color) {
+        let r = self.filter.radius();
+        let x0 = ((x - 0.5 - r).ceil().max(self.x0 as f32)) as usize;
+        let x1 = ((x - 0.5 + r).floor() as isize).min(self.x1 as isize - 1);
+        let y0 = ((y - 0.5 - r).ceil().max(self.y0 as f32)) as usize;
+        let y1 = ((y - 0.5 + r).floor() as isize).min(self.y1 as isize - 1);
+
+        for py in y0 as isize..=y1 {
+            for px in x0 as isize..=x1 {
+                let weight = self.filter.evaluate(px as f32 + 0.5 - x, py as f32 + 0.5 - y);
+                if weight == 0.0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[(py as usize - self.y0) * (self.x1 - self.x0) + px as usize - self.x0];
+                pixel.sum += color * weight;
+                pixel.weight += weight;
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::fs::{self, File, remove_file, rename};
+use std::io::{BufWriter, Cursor, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use image::{ColorType, ImageFormat};
+
+use crate::core::color::{self, Color};
+use crate::core::vec3::Vec3;
+use crate::renderer::postprocess;
+use crate::renderer::settings::{CropWindow, RenderSettings};
+
+// A rendered image in linear scene-referred RGBA, top row first. Alpha is 1
+// everywhere since the background is part of the picture.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, colors: &[Color]) -> Self {
+        assert_eq!(colors.len(), width * height, "pixel count does not match the size");
+        Self {
+            width,
+            height,
+            pixels: colors.iter().map(|c| [c.x(), c.y(), c.z(), 1.0]).collect(),
+        }
+    }
+
+    // Column x of row y, counted from the top left
+    pub fn pixel(&self, x: usize, y: usize) -> [f32; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    // Copy of just the pixels in the window
+    pub fn crop(&self, window: &CropWindow) -> Image {
+        Image {
+            width: window.width(),
+            height: window.height(),
+            pixels: crop_rows(&self.pixels, self.width, window),
+        }
+    }
+
+    pub fn colors(&self) -> Vec<Color> {
+        self.pixels.iter().map(|&[r, g, b, _]| Vec3::new(r, g, b)).collect()
+    }
+
+    // Exposure, tone mapping and the output transform of the settings applied
+    pub fn display(&self, settings: &RenderSettings) -> Vec<Color> {
+        let settings = RenderSettings {
+            width: self.width,
+            height: self.height,
+            ..settings.clone()
+        };
+        postprocess::apply(&self.colors(), &settings)
+    }
+
+    // Picks the format from the extension: ppm, png or exr
+    pub fn save(&self, path: &Path, settings: &RenderSettings) -> Result<()> {
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("ppm") => self.save_ppm(path, settings),
+            Some("png") => self.save_png(path, settings),
+            Some("exr") => self.save_exr(path),
+            _ => bail!("Unsupported image format: {}", path.display()),
+        }
+    }
+
+    pub fn save_ppm(&self, path: &Path, settings: &RenderSettings) -> Result<()> {
+        write_ppm(path, self.width, self.height, &self.display(settings))
+    }
+
+    pub fn save_png(&self, path: &Path, settings: &RenderSettings) -> Result<()> {
+        write_png(path, self.width, self.height, &self.display(settings))
+    }
+
+    // Replaces only the window in the image already saved at path, for re-rendering
+    // part of a frame. Starts from black when there is no earlier image yet.
+    pub fn save_region(&self, path: &Path, settings: &RenderSettings, window: &CropWindow) -> Result<()> {
+        if !path.exists() {
+            return self.save(path, settings);
+        }
+        let keep = |p: usize| !window.contains(p % self.width, p / self.width);
+
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("exr")) {
+            let mut previous = read_exr(path)?;
+            self.check_size(path, previous.width, previous.height)?;
+            for (p, pixel) in previous.pixels.iter_mut().enumerate() {
+                if !keep(p) {
+                    *pixel = self.pixels[p];
+                }
+            }
+            return previous.save_exr(path);
+        }
+
+        let (width, height, previous) = read_display(path)?;
+        self.check_size(path, width, height)?;
+        let mut encoded = self.display(settings);
+        for (p, &color) in previous.iter().enumerate().filter(|&(p, _)| keep(p)) {
+            encoded[p] = color;
+        }
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("png") => write_png(path, self.width, self.height, &encoded),
+            _ => write_ppm(path, self.width, self.height, &encoded),
+        }
+    }
+
+    // Saves the window as an image of its own. Vignette and bloom still follow
+    // the full frame, so it matches the same part of a full render.
+    pub fn save_crop(&self, path: &Path, settings: &RenderSettings, window: &CropWindow) -> Result<()> {
+        let (width, height) = (window.width(), window.height());
+        match path.extension().and_then(|ext| ext.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("ppm") => write_ppm(path, width, height, &crop_rows(&self.display(settings), self.width, window)),
+            Some("png") => write_png(path, width, height, &crop_rows(&self.display(settings), self.width, window)),
+            Some("exr") => self.crop(window).save_exr(path),
+            _ => bail!("Unsupported image format: {}", path.display()),
+        }
+    }
+
+    fn check_size(&self, path: &Path, width: usize, height: usize) -> Result<()> {
+        if (width, height) != (self.width, self.height) {
+            bail!("{} is {width}x{height}, not {}x{}", path.display(), self.width, self.height);
+        }
+        Ok(())
+    }
+
+    // Linear floats, untouched by the display settings
+    pub fn save_exr(&self, path: &Path) -> Result<()> {
+        let bytes = self.to_exr()?;
+        write_atomically(path, |tmp| Ok(fs::write(tmp, &bytes)?))
+    }
+
+    // The file contents save_png would write, kept in memory
+    pub fn to_png(&self, settings: &RenderSettings) -> Result<Vec<u8>> {
+        encode_png(self.width, self.height, &self.display(settings))
+    }
+
+    pub fn to_exr(&self) -> Result<Vec<u8>> {
+        use exr::prelude::{Image as ExrImage, SpecificChannels, Vec2, WritableImage};
+
+        let channels = SpecificChannels::rgba(|Vec2(x, y)| {
+            let [r, g, b, a] = self.pixel(x, y);
+            (r, g, b, a)
+        });
+        let mut bytes = Cursor::new(Vec::new());
+        ExrImage::from_channels((self.width, self.height), channels).write().to_buffered(&mut bytes)?;
+        Ok(bytes.into_inner())
+    }
+}
+
+// Writes colours already encoded for display as a plain PPM
+pub fn write_ppm(path: &Path, width: usize, height: usize, encoded: &[Color]) -> Result<()> {
+    write_atomically(path, |tmp| {
+        let mut writer = BufWriter::new(File::create(tmp)?);
+        writeln!(writer, "P3")?;
+        writeln!(writer, "{width} {height}")?;
+        writeln!(writer, "255")?;
+        for pixel in encoded {
+            writeln!(writer, "{}", color::format_display(*pixel))?;
+        }
+        writer.flush()?;
+        Ok(())
+    })
+}
+
+pub fn write_png(path: &Path, width: usize, height: usize, encoded: &[Color]) -> Result<()> {
+    let bytes = encode_png(width, height, encoded)?;
+    write_atomically(path, |tmp| Ok(fs::write(tmp, &bytes)?))
+}
+
+pub fn encode_png(width: usize, height: usize, encoded: &[Color]) -> Result<Vec<u8>> {
+    let rgb: Vec<u8> = encoded
+        .iter()
+        .flat_map(|c| [c.x(), c.y(), c.z()])
+        .map(|v| (256.0 * v.clamp(0.0, 0.999)) as u8)
+        .collect();
+    let mut bytes = Cursor::new(Vec::new());
+    image::write_buffer_with_format(&mut bytes, &rgb, width as u32, height as u32, ColorType::Rgb8, ImageFormat::Png)?;
+    Ok(bytes.into_inner())
+}
+
+// Colours encoded for display from a PNG or PPM, with its width and height
+pub fn read_display(path: &Path) -> Result<(usize, usize, Vec<Color>)> {
+    let image = image::open(path).with_context(|| format!("Failed to read {}", path.display()))?.to_rgb8();
+    let colors = image
+        .pixels()
+        // Lands on the same 8-bit value again when written
+        .map(|rgb| rgb.0.map(|v| (v as f32 + 0.5) / 256.0))
+        .map(|[r, g, b]| Color::new(r, g, b))
+        .collect();
+    Ok((image.width() as usize, image.height() as usize, colors))
+}
+
+pub fn read_exr(path: &Path) -> Result<Image> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| Image {
+            width: resolution.width(),
+            height: resolution.height(),
+            pixels: vec![[0.0, 0.0, 0.0, 1.0]; resolution.width() * resolution.height()],
+        },
+        |image: &mut Image, position, (r, g, b, a): (f32, f32, f32, f32)| {
+            let width = image.width;
+            image.pixels[position.y() * width + position.x()] = [r, g, b, a];
+        },
+    )
+    .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+fn crop_rows<T: Copy>(pixels: &[T], width: usize, window: &CropWindow) -> Vec<T> {
+    (window.y0..window.y1)
+        .flat_map(|y| pixels[y * width + window.x0..y * width + window.x1].iter().copied())
+        .collect()
+}
+
+// Goes through a temporary file next to the target, so viewers never pick up
+// a half written image
+pub fn write_atomically(path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    let mut tmp_name = path.file_name().context("Output path has no file name")?.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    if let Err(err) = write(&tmp_path) {
+        remove_file(&tmp_path).ok();
+        return Err(err.context(format!("Failed to write {}", path.display())));
+    }
+    rename(&tmp_path, path).with_context(|| format!("Failed to rename {} to {}", tmp_path.display(), path.display()))
+}
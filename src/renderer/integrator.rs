@@ -0,0 +1,322 @@
+use crate::core::color::Color;
+use crate::core::vec3::{self, Point3};
+use crate::core::ray::Ray;
+use crate::core::common;
+use crate::core::spectrum;
+use crate::core::sampler::{self, Sampler};
+
+use crate::objects::world::World;
+use crate::objects::hittable::HitRecord;
+
+use crate::renderer::aov::LightPasses;
+use crate::renderer::bdpt::BdptIntegrator;
+use crate::renderer::debug::DebugIntegrator;
+use crate::renderer::photon_map::Caustics;
+use crate::renderer::settings::{IntegratorKind, RenderSettings};
+
+pub trait Integrator: Send + Sync {
+    // Radiance arriving along the ray, in the colour space the ray carries,
+    // split into the render passes
+    fn passes(&self, r: &Ray, world: &World, depth: i32, sampler: &mut dyn Sampler) -> LightPasses;
+
+    fn li(&self, r: &Ray, world: &World, depth: i32, sampler: &mut dyn Sampler) -> Color {
+        self.passes(r, world, depth, sampler).total()
+    }
+}
+
+// The integrator for one pass of the render. Only photon mapping changes from
+// pass to pass, every pass traces new photons.
+pub fn create(settings: &RenderSettings, world: &World, pass: usize) -> Box<dyn Integrator> {
+    let mut classic = ClassicIntegrator::new();
+    if settings.ambient_occlusion {
+        classic = classic.with_ambient_occlusion(settings.ao_samples, settings.ao_distance);
+    }
+    match settings.integrator {
+        IntegratorKind::Classic if settings.transmissive_shadows => Box::new(classic.with_transmissive_shadows()),
+        IntegratorKind::Classic => Box::new(classic),
+        IntegratorKind::PhotonMap => {
+            let caustics = Caustics::build(world, settings, pass);
+            Box::new(classic.with_caustics(caustics))
+        }
+        IntegratorKind::Path => Box::new(PathIntegrator),
+        IntegratorKind::Bdpt => Box::new(BdptIntegrator),
+        IntegratorKind::AmbientOcclusion => Box::new(AmbientOcclusionIntegrator {
+            samples: settings.ao_samples,
+            distance: settings.ao_distance,
+        }),
+        IntegratorKind::Debug(view) => Box::new(DebugIntegrator { view }),
+    }
+}
+
+// Whitted-style shading with point lights, a constant ambient term and one
+// scattered ray per hit, optionally adding photon mapped caustics and darkening
+// the ambient term where the surroundings occlude it
+#[derive(Default)]
+pub struct ClassicIntegrator {
+    caustics: Option<Caustics>,
+    // Rays and their length
+    ambient_occlusion: Option<(usize, f32)>,
+    transmissive_shadows: bool,
+}
+
+impl ClassicIntegrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_caustics(mut self, caustics: Caustics) -> Self {
+        self.caustics = Some(caustics);
+        self
+    }
+
+    pub fn with_ambient_occlusion(mut self, samples: usize, distance: f32) -> Self {
+        self.ambient_occlusion = Some((samples, distance));
+        self
+    }
+
+    // Shadows of glass let tinted light through instead of blocking it. Not
+    // for photon mapping, whose caustics already bring that light.
+    pub fn with_transmissive_shadows(mut self) -> Self {
+        self.transmissive_shadows = true;
+        self
+    }
+}
+
+impl Integrator for ClassicIntegrator {
+    fn passes(&self, r: &Ray, world: &World, depth: i32, sampler: &mut dyn Sampler) -> LightPasses {
+        if depth <= 0 {
+            return LightPasses::default();
+        }
+
+        let mut rec = HitRecord::new();
+        if world.hit_sampled(r, 0.001, common::INFINITY, &mut rec, sampler) {
+            let mat = rec.mat.as_ref().unwrap();
+            // Absorption along the way here, whatever the surface is
+            let segment = r.transmittance(rec.t);
+            if mat.passes_through(r, &rec) {
+                let mut attenuation = Color::default();
+                let mut scattered = Ray::default();
+                mat.scatter(r, &rec, &mut attenuation, &mut scattered, sampler);
+                return self.passes(&scattered, world, depth - 1, sampler) * (attenuation * segment);
+            }
+
+            let mut passes = LightPasses::default();
+
+            for light in &world.lights {
+                let light_dir = (light.position() - rec.p).normalize();
+                let light_distance = (light.position() - rec.p).length();
+                let transmitted = if self.transmissive_shadows {
+                    shadow_transmittance(world, r, &rec, light.position(), sampler)
+                } else {
+                    let shadow_ray = Ray::new(rec.p, light_dir);
+                    let mut shadow_rec = HitRecord::new();
+                    let in_shadow = world.hit_sampled(&shadow_ray, 0.001, light_distance, &mut shadow_rec, sampler);
+                    if in_shadow { Color::ZERO } else { Color::new(1.0, 1.0, 1.0) }
+                };
+
+                if !transmitted.near_zero() {
+                    // Diffuse shading (Lambert)
+                    let diffuse_intensity = vec3::dot(light_dir, rec.normal).max(0.0);
+                    let diffuse = spectrum::for_ray(mat.albedo(), r) * diffuse_intensity;
+
+                    // Specular
+                    let view_dir = -r.direction().normalize();
+                    let reflect_dir = vec3::reflect(-light_dir, rec.normal).normalize();
+                    let spec_strength = vec3::dot(reflect_dir, view_dir).max(0.0).powf(32.0);
+                    let specular_color = Color::new(1.0, 1.0, 1.0);
+                    let specular = specular_color * spec_strength;
+
+                    // Light attenuation
+                    let attenuation = 1.0 / (light_distance * light_distance + 1.0);
+                    let light_color = spectrum::for_ray(light.intensity(), r) * attenuation * transmitted;
+                    passes.add_direct(false, diffuse * light_color);
+                    passes.add_direct(true, specular * light_color);
+                }
+            }
+
+            // Caustics arrive via specular paths the shadow rays cannot follow
+            if let Some(caustics) = &self.caustics {
+                if !mat.is_specular() {
+                    let irradiance = caustics.irradiance(rec.p, rec.normal);
+                    passes.indirect_diffuse += spectrum::for_ray(mat.albedo() * irradiance, r);
+                }
+            }
+
+            // Ambient Light (konstantes Grundlicht)
+            let ambient = match self.ambient_occlusion {
+                Some((samples, distance)) => 0.1 * unoccluded(world, &rec, samples, distance, sampler),
+                None => 0.1,
+            };
+            passes.indirect_diffuse += spectrum::for_ray(mat.albedo(), r) * ambient;
+
+            // Recursive scattering (reflection, refraction)
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            if mat.scatter(r, &rec, &mut attenuation, &mut scattered, sampler) {
+                let indirect = attenuation * self.li(&scattered, world, depth - 1, sampler);
+                passes.add_indirect(mat.is_specular(), indirect * 0.8);
+            }
+
+            return passes * segment;
+        }
+
+        LightPasses::emission(background(r))
+    }
+}
+
+// White where nothing is within reach of the first surface, darker in creases
+// and contact areas. Materials are ignored, which gives a clay look.
+pub struct AmbientOcclusionIntegrator {
+    pub samples: usize,
+    pub distance: f32,
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn passes(&self, r: &Ray, world: &World, _depth: i32, sampler: &mut dyn Sampler) -> LightPasses {
+        let mut rec = HitRecord::new();
+        if !world.hit_sampled(r, 0.001, common::INFINITY, &mut rec, sampler) {
+            return LightPasses::emission(spectrum::for_ray(Color::new(1.0, 1.0, 1.0), r));
+        }
+        let visibility = unoccluded(world, &rec, self.samples, self.distance, sampler);
+        LightPasses {
+            indirect_diffuse: spectrum::for_ray(Color::new(1.0, 1.0, 1.0), r) * visibility,
+            ..Default::default()
+        }
+    }
+}
+
+// Share of cosine-weighted rays from the hit that travel the distance
+// without hitting anything, 1 in the open
+pub fn unoccluded(world: &World, rec: &HitRecord, samples: usize, distance: f32, sampler: &mut dyn Sampler) -> f32 {
+    let mut open = 0;
+    for _ in 0..samples {
+        let direction = rec.normal + sampler::sample_unit_vector(sampler.get_2d());
+        let direction = if direction.near_zero() { rec.normal } else { vec3::unit_vector(direction) };
+        let mut occluder = HitRecord::new();
+        if !world.hit_sampled(&Ray::new(rec.p, direction), 0.001, distance, &mut occluder, sampler) {
+            open += 1;
+        }
+    }
+    open as f32 / samples as f32
+}
+
+// Sky gradient seen by rays leaving the scene
+pub fn background(r: &Ray) -> Color {
+    let unit_direction = vec3::unit_vector(r.direction());
+    let t = 0.5 * (unit_direction.y() + 1.0);
+    let background = (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0);
+    spectrum::for_ray(background, r)
+}
+
+// True when nothing blocks the segment between two points. The sampler
+// decides about surfaces alpha only partly covers.
+pub fn visible(world: &World, from: Point3, to: Point3, sampler: &mut dyn Sampler) -> bool {
+    let shadow_ray = Ray::new(from, to - from);
+    let mut rec = HitRecord::new();
+    !world.hit_sampled(&shadow_ray, 0.001, 0.999, &mut rec, sampler)
+}
+
+// Surfaces a shadow ray passes at most before it counts as blocked
+const MAX_SHADOW_SURFACES: usize = 32;
+
+// Share of the light from `to` that arrives at the hit `from`, the product of
+// the colours of the transmissive surfaces in between and black behind an
+// opaque one. r is the ray that hit `from`, its wavelengths and media carry on.
+pub fn shadow_transmittance(world: &World, r: &Ray, from: &HitRecord, to: Point3, sampler: &mut dyn Sampler) -> Color {
+    let mut remaining = (to - from.p).length();
+    let mut ray = r.scattered(from.p, vec3::unit_vector(to - from.p));
+    let mut transmittance = Color::new(1.0, 1.0, 1.0);
+    // Towards the far side the ray crosses its own surface first, leaving the
+    // glass r travelled in or entering the one it hit
+    let towards_r = if from.front_face { from.geometric_normal } else { -from.geometric_normal };
+    if vec3::dot(ray.direction(), towards_r) < 0.0 {
+        let start = HitRecord { t: 0.0, ..from.clone() };
+        let mut transmitted = Ray::default();
+        let mat = from.mat.as_ref().unwrap();
+        if !mat.transmit_shadow(&ray, &start, &mut transmittance, &mut transmitted) {
+            return Color::ZERO;
+        }
+        ray = transmitted;
+    }
+    for _ in 0..MAX_SHADOW_SURFACES {
+        let mut rec = HitRecord::new();
+        if !world.hit_sampled(&ray, 0.001, remaining, &mut rec, sampler) {
+            return transmittance * ray.transmittance(remaining);
+        }
+        let mut attenuation = Color::default();
+        let mut transmitted = Ray::default();
+        let mat = rec.mat.as_ref().unwrap();
+        if !mat.transmit_shadow(&ray, &rec, &mut attenuation, &mut transmitted) {
+            return Color::ZERO;
+        }
+        transmittance = transmittance * attenuation;
+        if transmittance.near_zero() {
+            return Color::ZERO;
+        }
+        remaining -= rec.t;
+        ray = transmitted;
+    }
+    Color::ZERO
+}
+
+// Unidirectional path tracer with next event estimation towards the point
+// lights. Physically based, so it serves as reference for the other integrators.
+#[derive(Default)]
+pub struct PathIntegrator;
+
+impl Integrator for PathIntegrator {
+    fn passes(&self, r: &Ray, world: &World, depth: i32, sampler: &mut dyn Sampler) -> LightPasses {
+        let mut passes = LightPasses::default();
+        // Whether the first surface reflects specularly, decides the passes
+        let mut specular = false;
+        let mut beta = Color::new(1.0, 1.0, 1.0);
+        let mut ray = r.scattered(r.origin(), r.direction());
+        let mut rec = HitRecord::new();
+
+        for bounce in 0..depth {
+            if !world.hit_sampled(&ray, 0.001, common::INFINITY, &mut rec, sampler) {
+                if bounce == 0 {
+                    passes.emission += background(&ray);
+                } else {
+                    passes.add_indirect(specular, beta * background(&ray));
+                }
+                break;
+            }
+
+            let mat = rec.mat.clone().unwrap();
+            let wo = -vec3::unit_vector(ray.direction());
+            beta = beta * ray.transmittance(rec.t);
+            if bounce == 0 {
+                specular = mat.is_specular();
+            }
+
+            if !mat.is_specular() {
+                for light in &world.lights {
+                    if !visible(world, rec.p, light.position(), sampler) {
+                        continue;
+                    }
+                    let to_light = light.position() - rec.p;
+                    let wi = vec3::unit_vector(to_light);
+                    let f = spectrum::for_ray(mat.eval(&rec, wo, wi), &ray);
+                    let irradiance = spectrum::for_ray(light.intensity(), &ray) / to_light.length_squared();
+                    let contribution = beta * f * irradiance * vec3::dot(wi, rec.normal).abs();
+                    if bounce == 0 {
+                        passes.add_direct(specular, contribution);
+                    } else {
+                        passes.add_indirect(specular, contribution);
+                    }
+                }
+            }
+
+            let mut attenuation = Color::default();
+            let mut scattered = Ray::default();
+            if !mat.scatter(&ray, &rec, &mut attenuation, &mut scattered, sampler) {
+                break;
+            }
+            beta = beta * attenuation;
+            ray = scattered;
+        }
+
+        passes
+    }
+}
@@ -0,0 +1,215 @@
+use rayon::prelude::*;
+
+use crate::core::color::{Color, luminance};
+use crate::core::vec3::{self, Point3, Vec3};
+use crate::core::ray::Ray;
+use crate::core::common::{self, PI};
+use crate::core::sampler::{self, IndependentSampler, Sampler};
+
+use crate::objects::world::World;
+use crate::objects::hittable::HitRecord;
+
+use crate::renderer::settings::RenderSettings;
+
+// Radius reduction of progressive photon mapping, smaller keeps more photons
+const ALPHA: f32 = 0.7;
+
+// Keeps the photon random numbers apart from the camera samples of the same seed
+const PHOTON_STREAM: u64 = 0x70686f746f6e;
+
+#[derive(Clone, Copy)]
+pub struct Photon {
+    pub position: Point3,
+    pub direction: Vec3,
+    pub power: Color,
+}
+
+// Photons in a left/right balanced kd-tree stored implicitly: the median of
+// every range is the node, the halves before and after it are its subtrees
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<u8>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        Self::build(&mut photons, &mut axes);
+        Self { photons, axes }
+    }
+
+    fn build(photons: &mut [Photon], axes: &mut [u8]) {
+        if photons.len() <= 1 {
+            return;
+        }
+
+        let mut min = Point3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Point3::new(f32::MIN, f32::MIN, f32::MIN);
+        for p in photons.iter() {
+            min = Point3::new(min.x().min(p.position.x()), min.y().min(p.position.y()), min.z().min(p.position.z()));
+            max = Point3::new(max.x().max(p.position.x()), max.y().max(p.position.y()), max.z().max(p.position.z()));
+        }
+        let extent = max - min;
+        let axis = if extent.x() >= extent.y() && extent.x() >= extent.z() {
+            0
+        } else if extent.y() >= extent.z() {
+            1
+        } else {
+            2
+        };
+
+        let mid = photons.len() / 2;
+        photons.select_nth_unstable_by(mid, |a, b| {
+            component(a.position, axis).total_cmp(&component(b.position, axis))
+        });
+        axes[mid] = axis as u8;
+
+        let (left, right) = photons.split_at_mut(mid);
+        let (left_axes, right_axes) = axes.split_at_mut(mid);
+        Self::build(left, left_axes);
+        Self::build(&mut right[1..], &mut right_axes[1..]);
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    pub fn for_each_within(&self, p: Point3, radius: f32, mut f: impl FnMut(&Photon)) {
+        self.query(0, self.photons.len(), p, radius * radius, &mut f);
+    }
+
+    fn query(&self, lo: usize, hi: usize, p: Point3, radius_sq: f32, f: &mut impl FnMut(&Photon)) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let photon = &self.photons[mid];
+        if (photon.position - p).length_squared() <= radius_sq {
+            f(photon);
+        }
+
+        let axis = self.axes[mid] as usize;
+        let delta = component(p, axis) - component(photon.position, axis);
+        let (near, far) = if delta < 0.0 { ((lo, mid), (mid + 1, hi)) } else { ((mid + 1, hi), (lo, mid)) };
+        self.query(near.0, near.1, p, radius_sq, f);
+        if delta * delta <= radius_sq {
+            self.query(far.0, far.1, p, radius_sq, f);
+        }
+    }
+}
+
+fn component(v: Vec3, axis: usize) -> f32 {
+    match axis {
+        0 => v.x(),
+        1 => v.y(),
+        _ => v.z(),
+    }
+}
+
+// Caustic photons of one pass of a progressive render (Knaus and Zwicker 2011).
+// Every pass traces fresh photons and gathers with a smaller radius, averaging
+// the samples of all passes in the film converges to the correct caustic.
+pub struct Caustics {
+    map: PhotonMap,
+    radius: f32,
+}
+
+impl Caustics {
+    pub fn build(world: &World, settings: &RenderSettings, pass: usize) -> Self {
+        let photons = trace_caustic_photons(world, settings.photons, settings.max_depth, settings.seed, pass);
+        Self {
+            map: PhotonMap::new(photons),
+            radius: gather_radius(settings.photon_radius, pass),
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    // Density estimate of the irradiance caustic photons deliver at p
+    pub fn irradiance(&self, p: Point3, normal: Vec3) -> Color {
+        let mut flux = Color::ZERO;
+        self.map.for_each_within(p, self.radius, |photon| {
+            if vec3::dot(photon.direction, normal) < 0.0 {
+                flux += photon.power;
+            }
+        });
+        flux / (PI * self.radius * self.radius)
+    }
+}
+
+// Every pass shrinks the area of the previous one by (i + ALPHA) / (i + 1)
+fn gather_radius(first: f32, pass: usize) -> f32 {
+    (0..pass).fold(first, |radius, i| radius * ((i as f32 + ALPHA) / (i as f32 + 1.0)).sqrt())
+}
+
+fn trace_caustic_photons(world: &World, count: usize, max_depth: i32, seed: u64, pass: usize) -> Vec<Photon> {
+    let total_power: f32 = world.lights.iter().map(|l| luminance(l.intensity())).sum();
+    if total_power <= 0.0 || count == 0 {
+        return Vec::new();
+    }
+
+    world
+        .lights
+        .iter()
+        .enumerate()
+        .flat_map(|(l, light)| {
+            let emitted = (count as f32 * luminance(light.intensity()) / total_power).round() as usize;
+            // Point light of radiant intensity I emits 4πI in total
+            let power = light.intensity() * (4.0 * PI / emitted.max(1) as f32);
+            (0..emitted)
+                .into_par_iter()
+                .filter_map(|i| {
+                    // Photon i of light l in this pass stands in for a pixel sample
+                    let mut sampler = IndependentSampler::new(seed ^ PHOTON_STREAM);
+                    sampler.start_pixel_sample(i, l, pass);
+                    let direction = sampler::sample_unit_vector(sampler.get_2d());
+                    trace_photon(world, Ray::new(light.position(), direction), power, max_depth, &mut sampler)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Follows a photon through specular surfaces, keeping it where it lands on a
+// diffuse surface after at least one specular bounce
+fn trace_photon(
+    world: &World,
+    mut ray: Ray,
+    mut power: Color,
+    max_depth: i32,
+    sampler: &mut dyn Sampler,
+) -> Option<Photon> {
+    let mut rec = HitRecord::new();
+
+    for bounce in 0..max_depth {
+        if !world.hit_sampled(&ray, 0.001, common::INFINITY, &mut rec, sampler) {
+            return None;
+        }
+
+        let mat = rec.mat.clone().unwrap();
+        power = power * ray.transmittance(rec.t);
+        if !mat.is_specular() {
+            return (bounce > 0).then_some(Photon {
+                position: rec.p,
+                direction: vec3::unit_vector(ray.direction()),
+                power,
+            });
+        }
+
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+        if !mat.scatter(&ray, &rec, &mut attenuation, &mut scattered, sampler) {
+            return None;
+        }
+        power = power * attenuation;
+        ray = scattered;
+    }
+
+    None
+}
@@ -0,0 +1,299 @@
+use clap::ValueEnum;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::core::color::{Color, luminance};
+use crate::core::spectrum;
+
+use crate::renderer::settings::RenderSettings;
+
+// Colour temperature that white balance leaves untouched, close to D65
+pub const NEUTRAL_TEMPERATURE: f32 = 6504.0;
+
+// Scene-linear luminance above which pixels start to bloom
+const BLOOM_THRESHOLD: f32 = 1.0;
+
+// Maps scene-linear colour to display-linear colour in [0, 1]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum ToneMapper {
+    // Hard clip at 1, the look of the plain renderer
+    #[default]
+    Clamp,
+    // Reinhard on luminance, keeps the hue
+    Reinhard,
+    // Hable's filmic curve from Uncharted 2
+    Filmic,
+    // Narkowicz' fit of the ACES RRT and sRGB ODT by Stephen Hill
+    Aces,
+    // Sobotka's AgX with the default contrast look
+    #[value(name = "agx")]
+    AgX,
+    // Khronos PBR Neutral, keeps base colours unchanged up to 0.76
+    PbrNeutral,
+}
+
+// Encodes display-linear values for the output file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum OutputTransform {
+    // Piecewise sRGB OETF (IEC 61966-2-1)
+    #[default]
+    Srgb,
+    // Square root, what the renderer wrote before the pipeline existed
+    Gamma2,
+    // No encoding, for files read back as linear data
+    Linear,
+}
+
+// Turns the scene-linear render into display-encoded colours in [0, 1]:
+// exposure, white balance, bloom and vignette on scene-linear values, then
+// the tone mapper and the output transform
+pub fn apply(pixels: &[Color], settings: &RenderSettings) -> Vec<Color> {
+    let (width, height) = (settings.width, settings.height);
+    let exposure = 2f32.powf(settings.exposure);
+    // Skipped when neutral, the round trip through cone space is not exact
+    let balance = (settings.white_balance != NEUTRAL_TEMPERATURE).then(|| white_balance(settings.white_balance));
+    let mut linear: Vec<Color> = pixels
+        .iter()
+        .map(|&c| balance.as_ref().map_or(c * exposure, |balance| balance(c * exposure)))
+        .collect();
+
+    if settings.bloom > 0.0 {
+        let glow = bloom(&linear, width, height);
+        for (c, g) in linear.iter_mut().zip(glow) {
+            *c += g * settings.bloom;
+        }
+    }
+    if settings.vignette > 0.0 {
+        vignette(&mut linear, width, height, settings.vignette);
+    }
+
+    linear
+        .into_iter()
+        .map(|c| {
+            let c = Color::new(c.x().max(0.0), c.y().max(0.0), c.z().max(0.0));
+            let display = tone_map(settings.tone_mapper, c);
+            let display = Color::new(display.x().clamp(0.0, 1.0), display.y().clamp(0.0, 1.0), display.z().clamp(0.0, 1.0));
+            encode(settings.output_transform, display)
+        })
+        .collect()
+}
+
+pub fn tone_map(mapper: ToneMapper, c: Color) -> Color {
+    match mapper {
+        ToneMapper::Clamp => c,
+        ToneMapper::Reinhard => {
+            let l = luminance(c);
+            if l <= 0.0 {
+                return c;
+            }
+            c * (1.0 / (1.0 + l))
+        }
+        ToneMapper::Filmic => {
+            let white = hable(11.2);
+            map_channels(c, |x| hable(2.0 * x) / white)
+        }
+        ToneMapper::Aces => {
+            let v = mat_mul(&ACES_INPUT, c);
+            let v = map_channels(v, |x| (x * (x + 0.024_578_6) - 0.000_090_537) / (x * (0.983_729 * x + 0.432_951) + 0.238_081));
+            mat_mul(&ACES_OUTPUT, v)
+        }
+        ToneMapper::AgX => {
+            let (min_ev, max_ev) = (-12.473_93, 4.026_069);
+            let v = mat_mul(&AGX_INSET, c);
+            let v = map_channels(v, |x| {
+                let x = (x.max(1e-10).log2().clamp(min_ev, max_ev) - min_ev) / (max_ev - min_ev);
+                agx_contrast(x)
+            });
+            // The curve ends in a 2.2 gamma encoding, undone to stay display-linear
+            map_channels(mat_mul(&AGX_OUTSET, v), |x| x.max(0.0).powf(2.2))
+        }
+        ToneMapper::PbrNeutral => pbr_neutral(c),
+    }
+}
+
+pub fn encode(transform: OutputTransform, c: Color) -> Color {
+    match transform {
+        OutputTransform::Srgb => map_channels(c, |x| {
+            if x <= 0.003_130_8 {
+                12.92 * x
+            } else {
+                1.055 * x.powf(1.0 / 2.4) - 0.055
+            }
+        }),
+        OutputTransform::Gamma2 => map_channels(c, f32::sqrt),
+        OutputTransform::Linear => c,
+    }
+}
+
+fn map_channels(c: Color, f: impl Fn(f32) -> f32) -> Color {
+    Color::new(f(c.x()), f(c.y()), f(c.z()))
+}
+
+fn mat_mul(m: &[[f32; 3]; 3], c: Color) -> Color {
+    let row = |r: &[f32; 3]| r[0] * c.x() + r[1] * c.y() + r[2] * c.z();
+    Color::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
+
+fn hable(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+// sRGB into the working space of the fit, with the RRT's saturation
+const ACES_INPUT: [[f32; 3]; 3] = [
+    [0.597_19, 0.354_58, 0.048_23],
+    [0.076_00, 0.908_34, 0.015_66],
+    [0.028_40, 0.133_83, 0.837_77],
+];
+
+const ACES_OUTPUT: [[f32; 3]; 3] = [
+    [1.604_75, -0.531_08, -0.073_67],
+    [-0.102_08, 1.108_13, -0.006_05],
+    [-0.003_27, -0.072_76, 1.076_02],
+];
+
+const AGX_INSET: [[f32; 3]; 3] = [
+    [0.842_479_06, 0.078_433_6, 0.079_223_745],
+    [0.042_328_242, 0.878_468_64, 0.079_166_13],
+    [0.042_375_655, 0.078_433_6, 0.879_143],
+];
+
+const AGX_OUTSET: [[f32; 3]; 3] = [
+    [1.196_879, -0.098_020_88, -0.099_029_74],
+    [-0.052_896_85, 1.151_903_1, -0.098_961_18],
+    [-0.052_971_635, -0.098_043_45, 1.151_073_7],
+];
+
+// Polynomial fit of AgX's default sigmoid
+fn agx_contrast(x: f32) -> f32 {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.002_32
+}
+
+fn pbr_neutral(c: Color) -> Color {
+    let start_compression = 0.8 - 0.04;
+    let desaturation = 0.15;
+
+    let x = c.x().min(c.y()).min(c.z());
+    let offset = if x < 0.08 { x - 6.25 * x * x } else { 0.04 };
+    let c = c - Color::new(offset, offset, offset);
+
+    let peak = c.x().max(c.y()).max(c.z());
+    if peak < start_compression {
+        return c;
+    }
+    let d = 1.0 - start_compression;
+    let new_peak = 1.0 - d * d / (peak + d - start_compression);
+    let c = c * (new_peak / peak);
+    let g = 1.0 - 1.0 / (desaturation * (peak - new_peak) + 1.0);
+    c * (1.0 - g) + Color::new(new_peak, new_peak, new_peak) * g
+}
+
+// Von Kries adaptation in Bradford cone space, so that a white lit at the
+// given colour temperature comes out neutral
+fn white_balance(temperature: f32) -> impl Fn(Color) -> Color {
+    let cone = |t: f32| {
+        let (x, y) = planckian_xy(t);
+        mat_mul(&BRADFORD, Color::new(x / y, 1.0, (1.0 - x - y) / y))
+    };
+    let (source, target) = (cone(temperature), cone(NEUTRAL_TEMPERATURE));
+    let scale = Color::new(target.x() / source.x(), target.y() / source.y(), target.z() / source.z());
+    move |c| {
+        let lms = mat_mul(&BRADFORD, spectrum::linear_srgb_to_xyz(c)) * scale;
+        spectrum::xyz_to_linear_srgb(mat_mul(&BRADFORD_INVERSE, lms))
+    }
+}
+
+const BRADFORD: [[f32; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const BRADFORD_INVERSE: [[f32; 3]; 3] = [
+    [0.986_993, -0.147_054_3, 0.159_962_7],
+    [0.432_305_3, 0.518_360_3, 0.049_291_2],
+    [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+];
+
+// Chromaticity of a black body, cubic fit by Kim et al. (2002) for 1667 K to 25000 K
+fn planckian_xy(temperature: f32) -> (f32, f32) {
+    let t = temperature.clamp(1667.0, 25000.0) as f64;
+    let (t2, t3) = (t * t, t * t * t);
+    let x = if t <= 4000.0 {
+        -0.266_123_9e9 / t3 - 0.234_358_9e6 / t2 + 0.877_695_6e3 / t + 0.179_910
+    } else {
+        -3.025_846_9e9 / t3 + 2.107_037_9e6 / t2 + 0.222_634_7e3 / t + 0.240_390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.106_381_4 * x3 - 1.348_110_20 * x2 + 2.185_558_32 * x - 0.202_196_83
+    } else if t <= 4000.0 {
+        -0.954_947_6 * x3 - 1.374_185_93 * x2 + 2.091_370_15 * x - 0.167_488_67
+    } else {
+        3.081_758_0 * x3 - 5.873_386_70 * x2 + 3.751_129_97 * x - 0.370_014_83
+    };
+    (x as f32, y as f32)
+}
+
+// Glow around everything brighter than the threshold, the sum of a narrow and
+// a wide Gaussian relative to the image size
+fn bloom(pixels: &[Color], width: usize, height: usize) -> Vec<Color> {
+    let bright: Vec<Color> = pixels
+        .iter()
+        .map(|&c| {
+            let l = luminance(c);
+            if l > BLOOM_THRESHOLD {
+                c * ((l - BLOOM_THRESHOLD) / l)
+            } else {
+                Color::ZERO
+            }
+        })
+        .collect();
+
+    let size = width.max(height) as f32;
+    let narrow = gaussian_blur(&bright, width, height, size * 0.01);
+    let wide = gaussian_blur(&bright, width, height, size * 0.04);
+    narrow.into_iter().zip(wide).map(|(n, w)| (n + w) * 0.5).collect()
+}
+
+fn gaussian_blur(pixels: &[Color], width: usize, height: usize, sigma: f32) -> Vec<Color> {
+    let sigma = sigma.max(0.5);
+    let radius = (3.0 * sigma).ceil() as isize;
+    let weights: Vec<f32> = (-radius..=radius).map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+
+    // Taps outside the image are left out and the rest renormalised
+    let pass = |src: &[Color], horizontal: bool| -> Vec<Color> {
+        (0..width * height)
+            .into_par_iter()
+            .map(|p| {
+                let (x, y) = ((p % width) as isize, (p / width) as isize);
+                let mut sum = Color::ZERO;
+                let mut total = 0.0;
+                for (k, w) in weights.iter().enumerate() {
+                    let offset = k as isize - radius;
+                    let (qx, qy) = if horizontal { (x + offset, y) } else { (x, y + offset) };
+                    if qx < 0 || qy < 0 || qx >= width as isize || qy >= height as isize {
+                        continue;
+                    }
+                    sum += src[qy as usize * width + qx as usize] * *w;
+                    total += w;
+                }
+                sum / total
+            })
+            .collect()
+    };
+    pass(&pass(pixels, true), false)
+}
+
+// Darkens towards the corners, strength 1 turns them black
+fn vignette(pixels: &mut [Color], width: usize, height: usize, strength: f32) {
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let corner_sq = cx * cx + cy * cy;
+    for (p, c) in pixels.iter_mut().enumerate() {
+        let (dx, dy) = ((p % width) as f32 + 0.5 - cx, (p / width) as f32 + 0.5 - cy);
+        let r_sq = (dx * dx + dy * dy) / corner_sq;
+        *c *= (1.0 - strength * r_sq).max(0.0);
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::renderer::film::Film;
+
+// State of a running render, handed out after every finished tile
+#[derive(Clone, Copy, Debug)]
+pub struct ProgressReport {
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub pixels_done: usize,
+    pub pixels_total: usize,
+    pub elapsed: Duration,
+}
+
+impl ProgressReport {
+    pub fn fraction(&self) -> f32 {
+        if self.pixels_total == 0 {
+            return 1.0;
+        }
+        self.pixels_done as f32 / self.pixels_total as f32
+    }
+
+    // Remaining time, extrapolated from the pixels done so far
+    pub fn eta(&self) -> Option<Duration> {
+        if self.pixels_done == 0 {
+            return None;
+        }
+        let remaining = (self.pixels_total - self.pixels_done) as f64 / self.pixels_done as f64;
+        Some(self.elapsed.mul_f64(remaining))
+    }
+}
+
+// Receives progress while a render runs. Called from the worker threads, one
+// report at a time and in order, so it should return quickly.
+pub trait Progress: Sync {
+    fn update(&self, report: &ProgressReport);
+
+    // The film with every tile merged so far, for showing the image as it
+    // builds up. Resolving it takes a while, so throttle that.
+    fn film_updated(&self, _film: &Film) {}
+}
+
+impl<F: Fn(&ProgressReport) + Sync> Progress for F {
+    fn update(&self, report: &ProgressReport) {
+        self(report)
+    }
+}
+
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn update(&self, _report: &ProgressReport) {}
+}
+
+// One line on stderr, rewritten in place
+pub struct StderrProgress;
+
+impl Progress for StderrProgress {
+    fn update(&self, report: &ProgressReport) {
+        let eta = report.eta().map_or(String::from("--"), |eta| format!("{}s", eta.as_secs()));
+        eprint!(
+            "\rTiles completed: {}/{} ({:.0}%), ETA {}   ",
+            report.tiles_done,
+            report.tiles_total,
+            report.fraction() * 100.0,
+            eta
+        );
+        std::io::stderr().flush().ok();
+    }
+}
+
+// Stops a render from another thread. Tiles already started are finished,
+// the rest of the image stays black.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -1,22 +1,43 @@
-use rayon::prelude::*;
-use std::fs::{File, rename};
-use std::io::{BufWriter, Write};
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::fs::create_dir_all; 
+use std::time::Instant;
 
-use crate::core::color::{self, Color};
-use crate::core::vec3::{self};
-use crate::core::ray::Ray;
+use anyhow::Result;
+
+use crate::core::color::Color;
 use crate::core::camera::Camera;
 use crate::core::common;
+use crate::core::sampler::{self, Sampler};
+use crate::core::spectrum::SampledWavelengths;
 
 use crate::objects::world::World;
 use crate::objects::hittable::HitRecord;
 
-const SAMPLES_PER_PIXEL: i32 = 20;
-const MAX_DEPTH: i32 = 10;
+use crate::renderer::adaptive::PixelStats;
+use crate::renderer::aov::{self, AovPixel, Aovs};
+use crate::renderer::checkpoint::Checkpoint;
+use crate::renderer::denoise;
+use crate::renderer::film::{Film, FilmTile, Filter};
+use crate::renderer::integrator::{self, Integrator};
+use crate::renderer::image::Image;
+use crate::renderer::progress::{CancelToken, NoProgress, Progress, ProgressReport};
+use crate::renderer::settings::{CropWindow, RenderSettings};
+
+// Edge length of the square blocks of pixels the workers take from the queue
+const TILE_SIZE: usize = 32;
+
+// Everything a render produced, top row first
+pub struct RenderOutput {
+    pub pixels: Vec<Color>,
+    // Samples every pixel took
+    pub samples: Vec<u32>,
+    // Only rendered when the settings ask for them or the denoiser needs them
+    pub aovs: Option<Aovs>,
+    // Stopped by a CancelToken before all tiles were done, the rest is black
+    pub cancelled: bool,
+}
 
 pub struct Renderer {
     pub camera: Camera,
@@ -26,165 +47,376 @@ pub struct Renderer {
 impl Renderer {
     pub fn new(camera: Camera, world: World) -> Self {
         Self {
-            camera: camera,
-            world: world,
+            camera,
+            world,
         }
     }
 
-    fn ray_color(r: &Ray, world: &World, depth: i32) -> Color {
-        if depth <= 0 {
-            return Color::new(0.0, 0.0, 0.0);
-        }
-
-        let mut rec = HitRecord::new();
-        if world.hit(r, 0.001, common::INFINITY, &mut rec) {
-            let mut direct_light = Color::new(0.0, 0.0, 0.0);
-
-            for light in &world.lights {
-                let light_dir = (light.position() - rec.p).normalize();
-                let light_distance = (light.position() - rec.p).length();
-                let shadow_ray = Ray::new(rec.p, light_dir);
-                let mut shadow_rec = HitRecord::new();
-                let in_shadow = world.hit(&shadow_ray, 0.001, light_distance, &mut shadow_rec);
-
-                if !in_shadow {
-                    // Diffuse shading (Lambert)
-                    let diffuse_intensity = vec3::dot(light_dir, rec.normal).max(0.0);
-                    let diffuse = rec.mat.as_ref().unwrap().albedo() * diffuse_intensity;
-
-                    // Specular
-                    let view_dir = -r.direction().normalize();
-                    let reflect_dir = vec3::reflect(-light_dir, rec.normal).normalize();
-                    let spec_strength = vec3::dot(reflect_dir, view_dir).max(0.0).powf(32.0);
-                    let specular_color = Color::new(1.0, 1.0, 1.0);
-                    let specular = specular_color * spec_strength;
-
-                    // Light attenuation
-                    let attenuation = 1.0 / (light_distance * light_distance + 1.0);
-                    let contribution = (diffuse + specular) * light.intensity() * attenuation;
-                    direct_light += contribution;
+    #[allow(clippy::too_many_arguments)]
+    fn sample_pixel(
+        &self,
+        integrator: &dyn Integrator,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        budget: i32,
+        tile: &mut FilmTile,
+        (i, j): (usize, usize),
+        (mut stats, mut aovs): (PixelStats, Option<AovPixel>),
+    ) -> (PixelStats, Option<AovPixel>) {
+        // A resumed pixel or a later pass carries on with the samples not taken yet
+        for index in stats.samples() as i32..budget {
+            if settings.adaptive && settings.ends_adaptive_round(index) && stats.converged(settings.noise_threshold) {
+                break;
+            }
+            sampler.start_pixel_sample(i, j, index as usize);
+            let (dx, dy) = sampler.get_2d();
+            let u = (i as f32 + dx) / (settings.width - 1) as f32;
+            let v = (j as f32 + dy) / (settings.height - 1) as f32;
+            let r = self.camera.get_ray(u, v);
+
+            // The integrators do not hand out their primary hit, so trace it again
+            let mut primary = HitRecord::new();
+            let primary_hit = aovs.is_some() && self.world.hit(&r, 0.001, common::INFINITY, &mut primary);
+            let distance = primary.t * r.direction().length();
+
+            let passes = if settings.spectral {
+                let wavelengths = SampledWavelengths::sample(sampler.get_1d());
+                let r = r.with_spectral(Some(wavelengths));
+                let passes = integrator.passes(&r, &self.world, settings.max_depth, sampler);
+                passes.map(|c| wavelengths.to_rgb(c))
+            } else {
+                integrator.passes(&r, &self.world, settings.max_depth, sampler)
+            };
+            let sample = passes.total();
+            tile.add_sample(i as f32 + dx, j as f32 + dy, sample);
+            stats.add(sample);
+
+            if let Some(aovs) = &mut aovs {
+                if primary_hit {
+                    aovs.add(passes, Some((&primary, distance)), aov::shadow(&self.world, &primary, sampler));
+                } else {
+                    aovs.add(passes, None, 1.0);
                 }
             }
+        }
+        (stats, aovs)
+    }
 
-            // Ambient Light (konstantes Grundlicht)
-            let ambient = rec.mat.as_ref().unwrap().albedo() * 0.1;
-
-            // Recursive scattering (reflection, refraction)
-            let mut indirect_light = Color::new(0.0, 0.0, 0.0);
-            let mut attenuation = Color::default();
-            let mut scattered = Ray::default();
-            if rec
-                .mat
-                .as_ref()
-                .unwrap()
-                .scatter(r, &rec, &mut attenuation, &mut scattered)
-            {
-                indirect_light += attenuation * Self::ray_color(&scattered, world, depth - 1);
+    // Every pixel of the tile in image order up to the budget, continuing from
+    // the given statistics and AOVs
+    #[allow(clippy::too_many_arguments)]
+    fn sample_tile(
+        &self,
+        integrator: &dyn Integrator,
+        settings: &RenderSettings,
+        sampler: &mut dyn Sampler,
+        budget: i32,
+        tile: &mut FilmTile,
+        (columns, rows): (&Range<usize>, &Range<usize>),
+        starts: Vec<(PixelStats, Option<AovPixel>)>,
+    ) -> Vec<(usize, PixelStats, Option<AovPixel>)> {
+        let (width, height) = (settings.width, settings.height);
+        let mut starts = starts.into_iter();
+        let mut pixels = Vec::with_capacity(columns.len() * rows.len());
+        for j in rows.clone().rev() {
+            for i in columns.clone() {
+                let start = starts
+                    .next()
+                    .unwrap_or_else(|| (PixelStats::default(), (settings.aovs || settings.denoise).then(AovPixel::default)));
+                let (stats, aovs) = self.sample_pixel(integrator, settings, sampler, budget, tile, (i, j), start);
+                pixels.push(((height - 1 - j) * width + i, stats, aovs));
             }
+        }
+        pixels
+    }
 
-            return ambient + direct_light + indirect_light * 0.8;
+    // Renders a single tile of tile_grid from scratch, e.g. on a remote worker,
+    // with the integrator of every pass. Returns the statistics of its pixels,
+    // top row first.
+    pub fn render_tile(
+        &self,
+        settings: &RenderSettings,
+        integrators: &[Box<dyn Integrator>],
+        tile: &mut FilmTile,
+        columns: &Range<usize>,
+        rows: &Range<usize>,
+    ) -> Vec<PixelStats> {
+        let mut sampler = sampler::create_in_rounds(settings.sampler, &settings.sample_rounds(), settings.seed);
+        let settings = RenderSettings { aovs: false, denoise: false, ..settings.clone() };
+        let mut starts = Vec::new();
+        for (pass, integrator) in integrators.iter().enumerate() {
+            let budget = settings.pass_budget(pass);
+            let pixels = self.sample_tile(integrator.as_ref(), &settings, sampler.as_mut(), budget, tile, (columns, rows), starts);
+            starts = pixels.into_iter().map(|(_, stats, aovs)| (stats, aovs)).collect();
         }
+        starts.into_iter().map(|(stats, _)| stats).collect()
+    }
+
+    // The finished image in memory. Nothing is written to disk, see Image for
+    // the save functions.
+    pub fn render(&self, settings: &RenderSettings) -> Result<Image> {
+        let output = self.render_with(settings, &NoProgress, &CancelToken::new())?;
+        Ok(Image::new(settings.width, settings.height, &output.pixels))
+    }
 
-        // Background gradient
-        let unit_direction = vec3::unit_vector(r.direction());
-        let t = 0.5 * (unit_direction.y() + 1.0);
-        (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+    // Averaged pixel colours, top row first. Every pixel sample draws from its
+    // own seeded sampler, so the result does not depend on rayon's scheduling.
+    pub fn render_pixels(&self, settings: &RenderSettings) -> Result<Vec<Color>> {
+        Ok(self.render_output(settings)?.pixels)
     }
 
-    pub fn render_scene(&self, width: usize, height: usize) {
-        let tmp_path = Path::new("output.tmp.ppm");
-        let final_path = Path::new("output.ppm");
-
-        let file = File::create(&tmp_path).expect("Failed to create temp file");
-        let mut writer = BufWriter::new(file);
-
-        writeln!(writer, "P3").expect("Failed to write PPM header");
-        writeln!(writer, "{} {}", width, height).expect("Failed to write dimensions");
-        writeln!(writer, "255").expect("Failed to write max color value");
-
-        let camera = Arc::new(self.camera.clone());
-        let world = Arc::new(self.world.clone());
-        let progress = Arc::new(AtomicUsize::new(0));
-
-        let pixel_data: Vec<String> = (0..height)
-            .into_par_iter()
-            .rev()
-            .map(|j| {
-                let progress = Arc::clone(&progress);
-                let mut scanline = Vec::with_capacity(width);
-                for i in 0..width {
-                    let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                    for _ in 0..SAMPLES_PER_PIXEL {
-                        let mod_x = i as f32 + common::random_double();
-                        let mod_y = j as f32 + common::random_double();
-                        let u = mod_x / (width - 1) as f32;
-                        let v = mod_y / (height - 1) as f32;
-                        let r = camera.get_ray(u, v);
-                        pixel_color += Self::ray_color(&r, &*world, MAX_DEPTH);
-                    }
-                    scanline.push(color::format_color(pixel_color, SAMPLES_PER_PIXEL));
+    // Like render_pixels, plus the number of samples every pixel took
+    pub fn render_with_sample_counts(&self, settings: &RenderSettings) -> Result<(Vec<Color>, Vec<u32>)> {
+        let output = self.render_output(settings)?;
+        Ok((output.pixels, output.samples))
+    }
+
+    // Pixel colours, sample counts and the AOVs if enabled
+    pub fn render_output(&self, settings: &RenderSettings) -> Result<RenderOutput> {
+        self.render_with(settings, &NoProgress, &CancelToken::new())
+    }
+
+    // Renders tiles pulled from a shared queue by one worker per thread.
+    // Finished tiles are merged into the film strictly in queue order, so the
+    // image stays bit-identical however the workers interleave. Photon mapping
+    // goes through the queue once per photon pass, with new photons every time.
+    pub fn render_with(&self, settings: &RenderSettings, progress: &dyn Progress, cancel: &CancelToken) -> Result<RenderOutput> {
+        settings.validate()?;
+        let (width, height) = (settings.width, settings.height);
+        let with_aovs = settings.aovs || settings.denoise;
+        let mut film = Film::new(width, height, Filter::new(settings.filter));
+        let mut stats = vec![PixelStats::default(); width * height];
+        let key = Checkpoint::key(settings);
+        if let (true, Some(path)) = (settings.resume, &settings.checkpoint) {
+            if path.exists() {
+                let checkpoint = Checkpoint::load(path, &key)?;
+                film.restore(checkpoint.film)?;
+                stats = checkpoint.stats;
+            }
+        }
+
+        let crop = settings.crop_window();
+        let tiles = tile_grid(settings);
+        let passes = settings.render_passes();
+        let pass_pixels: usize = tiles.iter().map(|(columns, rows)| columns.len() * rows.len()).sum();
+        let start = Instant::now();
+        // Held while a checkpoint is written, the workers skip theirs meanwhile
+        let saving = Mutex::new(());
+        let checkpoint_error = Mutex::new(None);
+        let tile_pixels = |columns: &Range<usize>, rows: &Range<usize>| -> Vec<usize> {
+            rows.clone().rev().flat_map(|j| columns.clone().map(move |i| (height - 1 - j) * width + i)).collect()
+        };
+
+        let mut merger = TileMerger {
+            film,
+            next: 0,
+            pending: BTreeMap::new(),
+            stats,
+            aovs: if with_aovs { vec![AovPixel::default(); width * height] } else { Vec::new() },
+            tiles_done: 0,
+            pixels_done: 0,
+            last_checkpoint: Instant::now(),
+        };
+
+        for pass in 0..passes {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let budget = settings.pass_budget(pass);
+            // A resumed render skips the passes its pixels are already through
+            let finished = tiles.iter().all(|(columns, rows)| {
+                tile_pixels(columns, rows).into_iter().all(|p| merger.stats[p].samples() as i32 >= budget)
+            });
+            if finished {
+                merger.tiles_done += tiles.len();
+                merger.pixels_done += pass_pixels;
+                continue;
+            }
+
+            let integrator = integrator::create(settings, &self.world, pass);
+            let next_tile = AtomicUsize::new(0);
+            merger.next = 0;
+            let state = Mutex::new(merger);
+
+            rayon::scope(|scope| {
+                for _ in 0..rayon::current_num_threads() {
+                    scope.spawn(|_| {
+                        let mut sampler = sampler::create_in_rounds(settings.sampler, &settings.sample_rounds(), settings.seed);
+                        while !cancel.is_cancelled() {
+                            let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                            let Some((columns, rows)) = tiles.get(index) else {
+                                break;
+                            };
+                            let (mut tile, before) = {
+                                let state = state.lock().unwrap();
+                                let before: Vec<(PixelStats, Option<AovPixel>)> = tile_pixels(columns, rows)
+                                    .into_iter()
+                                    .map(|p| (state.stats[p], with_aovs.then(|| state.aovs[p].clone())))
+                                    .collect();
+                                (state.film.tile(columns.clone(), rows.clone()), before)
+                            };
+                            let pixels = self.sample_tile(
+                                integrator.as_ref(),
+                                settings,
+                                sampler.as_mut(),
+                                budget,
+                                &mut tile,
+                                (columns, rows),
+                                before,
+                            );
+
+                            let mut state = state.lock().unwrap();
+                            state.pending.insert(index, FinishedTile { film: tile, pixels });
+                            state.merge_ready();
+                            state.tiles_done += 1;
+                            state.pixels_done += columns.len() * rows.len();
+                            // The film first, a tile reported done is in the preview
+                            progress.film_updated(&state.film);
+                            progress.update(&ProgressReport {
+                                tiles_done: state.tiles_done,
+                                tiles_total: tiles.len() * passes,
+                                pixels_done: state.pixels_done,
+                                pixels_total: width * height * passes,
+                                elapsed: start.elapsed(),
+                            });
+
+                            let Some(path) = &settings.checkpoint else {
+                                continue;
+                            };
+                            if state.last_checkpoint.elapsed() < settings.checkpoint_interval {
+                                continue;
+                            }
+                            let Ok(_saving) = saving.try_lock() else {
+                                continue;
+                            };
+                            state.last_checkpoint = Instant::now();
+                            let checkpoint = state.checkpoint(&key);
+                            drop(state);
+                            if let Err(err) = checkpoint.save(path) {
+                                checkpoint_error.lock().unwrap().get_or_insert(err);
+                            }
+                        }
+                    });
                 }
-                let completed = progress.fetch_add(1, Ordering::Relaxed);
-                eprint!("\rScanlines completed: {}/{}", completed + 1, height);
-                scanline
-            })
-            .flatten()
-            .collect();
+            });
+
+            merger = state.into_inner().unwrap();
+            merger.merge_rest();
+        }
+
+        let cancelled = merger.tiles_done < tiles.len() * passes;
+        if let Some(err) = checkpoint_error.into_inner().unwrap() {
+            return Err(err);
+        }
+        if let Some(path) = &settings.checkpoint {
+            merger.checkpoint(&key).save(path)?;
+        }
 
-        for line in &pixel_data {
-            writeln!(writer, "{}", line).expect("Failed to write pixel");
+        let mut samples: Vec<u32> = merger.stats.iter().map(|s| s.samples()).collect();
+        let outside: Vec<usize> = match settings.crop {
+            Some(_) => (0..width * height).filter(|p| !crop.contains(p % width, p / width)).collect(),
+            None => Vec::new(),
+        };
+        for &p in &outside {
+            samples[p] = 0;
+            if with_aovs {
+                merger.aovs[p] = AovPixel::default();
+            }
         }
 
-        writer.flush().expect("Failed to flush buffer");
-        rename(tmp_path, final_path).expect("Failed to rename temp file");
-        eprint!("\nDone. Image saved to output.ppm\n");
+        let mut pixels = merger.film.resolve();
+        let aovs = with_aovs.then(|| Aovs::resolve(width, height, &merger.aovs));
+        if let (true, Some(aovs)) = (settings.denoise, &aovs) {
+            pixels = denoise::denoise(&pixels, aovs);
+        }
+        for &p in &outside {
+            pixels[p] = Color::ZERO;
+        }
+        Ok(RenderOutput {
+            pixels,
+            samples,
+            aovs,
+            cancelled,
+        })
     }
+}
 
-    pub fn render_scene_to_file(&self, width: usize, height: usize, filename: &str) {
-        let output_dir = Path::new("animation");
-        create_dir_all(output_dir).expect("Failed to create output directory");
-
-        let tmp_path = output_dir.join(format!("{filename}.tmp"));
-        let final_path = output_dir.join(format!("{filename}.ppm"));
-
-        let file = File::create(&tmp_path).expect("Failed to create temp file");
-        let mut writer = BufWriter::new(file);
-
-        writeln!(writer, "P3").expect("Failed to write PPM header");
-        writeln!(writer, "{} {}", width, height).expect("Failed to write dimensions");
-        writeln!(writer, "255").expect("Failed to write max color value");
-
-        let camera = Arc::new(self.camera.clone());
-        let world = Arc::new(self.world.clone());
-
-        let pixel_data: Vec<String> = (0..height)
-            .into_par_iter()
-            .rev()
-            .map(|j| {
-                let mut scanline = Vec::with_capacity(width);
-                for i in 0..width {
-                    let mut pixel_color = Color::new(0.0, 0.0, 0.0);
-                    for _ in 0..SAMPLES_PER_PIXEL {
-                        let mod_x = i as f32 + common::random_double();
-                        let mod_y = j as f32 + common::random_double();
-                        let u = mod_x / (width - 1) as f32;
-                        let v = mod_y / (height - 1) as f32;
-                        let r = camera.get_ray(u, v);
-                        pixel_color += Self::ray_color(&r, &*world, MAX_DEPTH);
-                    }
-                    scanline.push(color::format_color(pixel_color, SAMPLES_PER_PIXEL));
-                }
-                scanline
-            })
-            .flatten()
-            .collect();
+struct FinishedTile {
+    film: FilmTile,
+    // Image index, statistics and AOVs of every pixel rendered
+    pixels: Vec<(usize, PixelStats, Option<AovPixel>)>,
+}
 
-        for line in &pixel_data {
-            writeln!(writer, "{}", line).expect("Failed to write pixel");
+// Tiles finished out of order wait here until all tiles before them are
+// merged, only that prefix of the queue ever goes into a checkpoint
+struct TileMerger {
+    film: Film,
+    next: usize,
+    pending: BTreeMap<usize, FinishedTile>,
+    stats: Vec<PixelStats>,
+    aovs: Vec<AovPixel>,
+    tiles_done: usize,
+    pixels_done: usize,
+    last_checkpoint: Instant,
+}
+
+impl TileMerger {
+    fn merge_ready(&mut self) {
+        while let Some(tile) = self.pending.remove(&self.next) {
+            self.merge(tile);
+            self.next += 1;
         }
+    }
 
-        writer.flush().expect("Failed to flush buffer");
-        std::fs::rename(tmp_path, &&final_path).expect("Failed to rename temp file");
+    // After a cancel the queue has gaps, the tiles behind them still merge in order
+    fn merge_rest(&mut self) {
+        for tile in std::mem::take(&mut self.pending).into_values() {
+            self.merge(tile);
+        }
+    }
+
+    fn merge(&mut self, tile: FinishedTile) {
+        self.film.merge(&tile.film);
+        for (p, stats, aovs) in tile.pixels {
+            self.stats[p] = stats;
+            if let Some(aovs) = aovs {
+                self.aovs[p] = aovs;
+            }
+        }
+    }
+
+    fn checkpoint(&self, key: &str) -> Checkpoint {
+        Checkpoint {
+            key: key.to_string(),
+            width: self.film.width(),
+            height: self.film.height(),
+            film: self.film.pixels().to_vec(),
+            stats: self.stats.clone(),
+        }
     }
 }
 
+// The tiles a render works through, in merge order. Pixels just outside the
+// crop window still splat into it, so they are rendered too.
+pub fn tile_grid(settings: &RenderSettings) -> Vec<(Range<usize>, Range<usize>)> {
+    let (width, height) = (settings.width, settings.height);
+    let margin = (Filter::new(settings.filter).radius() + 0.5).ceil() as usize;
+    tiles(width, height, settings.crop_window().expand(margin, width, height))
+}
+
+// Square tiles as column and film row ranges, from the top left of the image.
+// Tiles stay on the same grid when cut to a window, which keeps the pixels
+// inside bit-identical to a render of the whole frame.
+fn tiles(width: usize, height: usize, window: CropWindow) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut tiles = Vec::new();
+    for top in (0..height).step_by(TILE_SIZE) {
+        let (y0, y1) = (top.max(window.y0), (top + TILE_SIZE).min(window.y1));
+        for left in (0..width).step_by(TILE_SIZE) {
+            let (x0, x1) = (left.max(window.x0), (left + TILE_SIZE).min(window.x1));
+            if x0 < x1 && y0 < y1 {
+                tiles.push((x0..x1, height - y1..height - y0));
+            }
+        }
+    }
+    tiles
+}
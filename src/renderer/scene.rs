@@ -2,7 +2,7 @@
 
 use anyhow::{Result};
 use std::sync::Arc;
-use std::path::PathBuf;
+use std::path::Path;
 use async_trait::async_trait;
 
 use crate::io::obj;
@@ -11,9 +11,11 @@ use crate::core::camera::Camera;
 use crate::core::color::Color;
 use crate::core::vec3::{Point3, Vec3};
 
-use crate::material::material::{Lambertian, Glass, Metal, RoomMaterials}; 
+use crate::material::material::{Lambertian, Glass, Metal, RoomMaterials, Dispersion}; 
+use crate::material::conductor::Conductor;
 
 use crate::objects::world::World;
+use crate::objects::hittable::Hittable;
 use crate::objects::triangle::{self, Triangle};
 use crate::objects::sphere::Sphere;
 use crate::objects::light::Light;
@@ -22,11 +24,10 @@ use crate::objects::light::Light;
 pub trait Scene: Send + Sync {
     async fn setup(
         &self,
-        obj_path: &PathBuf,
+        obj_path: &Path,
         world: &mut World,
         angle: f32,
-        height: usize,
-        width: usize,
+        aspect_ratio: f32,
     ) -> Result<Camera>;
 }
 
@@ -38,18 +39,17 @@ pub struct MuseumScene;
 impl Scene for CustomScene {
     async fn setup(
         &self,
-        obj_path: &PathBuf,
+        obj_path: &Path,
         world: &mut World,
         angle: f32,
-        height: usize,
-        width: usize,
+        aspect_ratio: f32,
     ) -> Result<Camera> {
         let neutral = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 0.0)));
         let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
         let material_center = Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.3)));
         let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
         let metal = Arc::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.3));
-        let gold = Arc::new(Metal::new(Color::new(0.8, 0.5, 0.3), 0.8));
+        let gold = Arc::new(Metal::conductor(Conductor::GOLD, 0.8));
 
         // world sphere (ground)
         world.add_hittable(Box::new(Sphere::new(
@@ -66,9 +66,8 @@ impl Scene for CustomScene {
 
         // rotated cube
         let rotation = Vec3::new(std::f32::consts::FRAC_PI_4, 0.0, std::f32::consts::FRAC_PI_4);
-        for tri in triangle::cube(Point3::new(-1.0, 0.0, 1.0), 1.0, rotation, metal.clone()) {
-            world.add_hittable(Box::new(tri));
-        }
+        let cube = triangle::cube(Point3::new(-1.0, 0.0, 1.0), 1.0, rotation, metal.clone());
+        world.add_object(cube.into_iter().map(|tri| Box::new(tri) as Box<dyn Hittable>));
 
         // loaded object
         let rotation = Vec3::new(std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_3, 0.0);
@@ -80,7 +79,7 @@ impl Scene for CustomScene {
         let light = Light::from_bounds(min, max, light_color);
         world.add_light(light);
 
-        let camera = Camera::from_bounds(min, max, width as f32 / height as f32, angle, 0.5);
+        let camera = Camera::from_bounds(min, max, aspect_ratio, angle, 0.5);
 
         Ok(camera)
     }
@@ -90,11 +89,10 @@ impl Scene for CustomScene {
 impl Scene for MuseumScene {
     async fn setup(
         &self,
-        obj_path: &PathBuf,
+        obj_path: &Path,
         world: &mut World,
         _angle: f32,
-        height: usize,
-        width: usize,
+        aspect_ratio: f32,
     ) -> Result<Camera> {
         let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
         let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
@@ -116,7 +114,6 @@ impl Scene for MuseumScene {
         let light = Light::from_bounds(min, max, light_color);
         world.add_light(light);
 
-        let aspect_ratio = width as f32 / height as f32;
         let camera = Camera::from_bounds(min, max, aspect_ratio, 120.0, 0.5);
 
         Ok(camera)
@@ -131,13 +128,16 @@ impl Scene for MuseumScene {
 impl Scene for RequiredScene {
     async fn setup(
         &self,
-        obj_path: &PathBuf,
+        obj_path: &Path,
         world: &mut World,
         angle: f32,
-        height: usize,
-        width: usize,
+        aspect_ratio: f32,
     ) -> Result<Camera> {
-        let glass = Arc::new(Glass::new(Color::new(0.8, 0.9, 1.0), 1.5));
+        let glass = Arc::new(
+            Glass::new(Color::new(1.0, 1.0, 1.0), 1.5)
+                .with_absorption(Color::new(0.15, 0.06, 0.02))
+                .with_dispersion(Dispersion::SF11),
+        );
         let red = Arc::new(Lambertian::new(Color::new(0.9, 0.1, 0.1)));
         let green = Arc::new(Lambertian::new(Color::new(0.1, 0.9, 0.1)));
         let blue = Arc::new(Lambertian::new(Color::new(0.1, 0.1, 0.9)));
@@ -165,7 +165,6 @@ impl Scene for RequiredScene {
         let min = sphere_center - Vec3::new(radius, radius, radius);
         let max = sphere_center + Vec3::new(radius, radius, radius);
 
-        let aspect_ratio = width as f32 / height as f32;
         let height_offset_factor = 1.0;
 
         let camera = Camera::from_bounds(min, max, aspect_ratio, angle, height_offset_factor);
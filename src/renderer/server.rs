@@ -0,0 +1,295 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, ensure};
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+use crate::renderer::film::Film;
+use crate::renderer::image::Image;
+use crate::renderer::progress::{CancelToken, Progress, ProgressReport};
+use crate::renderer::renderer::Renderer;
+use crate::renderer::settings::RenderSettings;
+
+// How often a running job refreshes its progressive image
+const PREVIEW_INTERVAL: Duration = Duration::from_secs(1);
+
+// A render submitted over HTTP. The scene is built from either a model name
+// known to the server or an OBJ file on the server's disk.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobRequest {
+    #[serde(default = "default_scene")]
+    pub scene: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    pub angle: f32,
+    // Fields left out keep their defaults
+    #[serde(default)]
+    pub settings: RenderSettings,
+}
+
+fn default_scene() -> String {
+    String::from("required")
+}
+
+impl JobRequest {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.model.is_some() != self.file.is_some(), "A job needs either a model or a file");
+        ensure!(self.settings.checkpoint.is_none() && !self.settings.resume, "Jobs keep their images in memory, checkpoints are not supported");
+        self.settings.validate()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Rendering,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+// What the server reports about a job
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    // Share of the pixels rendered, from 0 to 1
+    pub progress: f32,
+    pub tiles_done: usize,
+    pub tiles_total: usize,
+    pub elapsed_seconds: f32,
+    pub eta_seconds: Option<f32>,
+    pub error: Option<String>,
+}
+
+struct Job {
+    request: JobRequest,
+    state: JobState,
+    report: Option<ProgressReport>,
+    error: Option<String>,
+    cancel: CancelToken,
+    // The finished image, or the film so far while rendering and after a cancel
+    image: Option<Image>,
+}
+
+impl Job {
+    fn status(&self, id: u64) -> JobStatus {
+        let report = self.report.as_ref();
+        JobStatus {
+            id,
+            state: self.state,
+            progress: report.map_or(0.0, |r| r.fraction()),
+            tiles_done: report.map_or(0, |r| r.tiles_done),
+            tiles_total: report.map_or(0, |r| r.tiles_total),
+            elapsed_seconds: report.map_or(0.0, |r| r.elapsed.as_secs_f32()),
+            eta_seconds: report.and_then(|r| r.eta()).map(|eta| eta.as_secs_f32()),
+            error: self.error.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+}
+
+#[derive(Clone)]
+struct AppState {
+    jobs: Arc<Mutex<Jobs>>,
+    queue: Sender<u64>,
+}
+
+// The HTTP API. Jobs render one after another on a thread of their own, build
+// turns a request into the scene right before its job starts:
+//   POST   /jobs                  submit a JobRequest, answers with its JobStatus
+//   GET    /jobs                  status of every job
+//   GET    /jobs/{id}             status of one job
+//   DELETE /jobs/{id}             cancel a queued or running job, forget a finished one
+//   GET    /jobs/{id}/image.png   the finished image, also as image.exr
+//   GET    /jobs/{id}/preview.png the image as far as it got, also as preview.exr
+pub fn router(build: impl FnMut(&JobRequest) -> Result<Renderer> + Send + 'static) -> Router {
+    let jobs = Arc::new(Mutex::new(Jobs::default()));
+    let (queue, receiver) = mpsc::channel();
+    let render_jobs = jobs.clone();
+    // Ends once the router and with it the queue is dropped
+    thread::spawn(move || render_queue(&render_jobs, receiver, build));
+
+    Router::new()
+        .route("/jobs", get(list).post(submit))
+        .route("/jobs/:id", get(status).delete(cancel))
+        .route("/jobs/:id/:file", get(file))
+        .with_state(AppState { jobs, queue })
+}
+
+pub async fn serve(listener: TcpListener, build: impl FnMut(&JobRequest) -> Result<Renderer> + Send + 'static) -> Result<()> {
+    axum::serve(listener, router(build)).await?;
+    Ok(())
+}
+
+fn render_queue(jobs: &Mutex<Jobs>, receiver: Receiver<u64>, mut build: impl FnMut(&JobRequest) -> Result<Renderer>) {
+    for id in receiver {
+        let (request, cancel) = {
+            let mut jobs = jobs.lock().unwrap();
+            // Cancelled or forgotten while it waited
+            let Some(job) = jobs.jobs.get_mut(&id).filter(|job| job.state == JobState::Queued) else {
+                continue;
+            };
+            job.state = JobState::Rendering;
+            (job.request.clone(), job.cancel.clone())
+        };
+
+        let progress = JobProgress {
+            jobs,
+            id,
+            last_preview: Mutex::new(None),
+        };
+        let settings = &request.settings;
+        let result = build(&request).and_then(|renderer| renderer.render_with(settings, &progress, &cancel));
+
+        let mut jobs = jobs.lock().unwrap();
+        let Some(job) = jobs.jobs.get_mut(&id) else {
+            continue;
+        };
+        match result {
+            Ok(output) => {
+                job.state = if output.cancelled { JobState::Cancelled } else { JobState::Done };
+                job.image = Some(Image::new(settings.width, settings.height, &output.pixels));
+            }
+            Err(err) => {
+                job.state = JobState::Failed;
+                job.error = Some(format!("{err:#}"));
+            }
+        }
+    }
+}
+
+struct JobProgress<'a> {
+    jobs: &'a Mutex<Jobs>,
+    id: u64,
+    last_preview: Mutex<Option<Instant>>,
+}
+
+impl Progress for JobProgress<'_> {
+    fn update(&self, report: &ProgressReport) {
+        if let Some(job) = self.jobs.lock().unwrap().jobs.get_mut(&self.id) {
+            job.report = Some(*report);
+        }
+    }
+
+    fn film_updated(&self, film: &Film) {
+        let mut last_preview = self.last_preview.lock().unwrap();
+        if last_preview.is_some_and(|last| last.elapsed() < PREVIEW_INTERVAL) {
+            return;
+        }
+        *last_preview = Some(Instant::now());
+        let image = Image::new(film.width(), film.height(), &film.resolve());
+        if let Some(job) = self.jobs.lock().unwrap().jobs.get_mut(&self.id) {
+            job.image = Some(image);
+        }
+    }
+}
+
+// Answered as {"error": message}
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn not_found(id: u64) -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, format!("No job {id}"))
+}
+
+async fn submit(State(state): State<AppState>, Json(request): Json<JobRequest>) -> Result<(StatusCode, Json<JobStatus>), ApiError> {
+    request.validate().map_err(|err| ApiError(StatusCode::BAD_REQUEST, format!("{err:#}")))?;
+    let mut jobs = state.jobs.lock().unwrap();
+    jobs.next_id += 1;
+    let id = jobs.next_id;
+    let job = Job {
+        request,
+        state: JobState::Queued,
+        report: None,
+        error: None,
+        cancel: CancelToken::new(),
+        image: None,
+    };
+    let status = job.status(id);
+    jobs.jobs.insert(id, job);
+    state.queue.send(id).map_err(|_| ApiError(StatusCode::SERVICE_UNAVAILABLE, String::from("The renderer has stopped")))?;
+    Ok((StatusCode::CREATED, Json(status)))
+}
+
+async fn list(State(state): State<AppState>) -> Json<Vec<JobStatus>> {
+    Json(state.jobs.lock().unwrap().jobs.iter().map(|(&id, job)| job.status(id)).collect())
+}
+
+async fn status(State(state): State<AppState>, Path(id): Path<u64>) -> Result<Json<JobStatus>, ApiError> {
+    let jobs = state.jobs.lock().unwrap();
+    let job = jobs.jobs.get(&id).ok_or_else(|| not_found(id))?;
+    Ok(Json(job.status(id)))
+}
+
+async fn cancel(State(state): State<AppState>, Path(id): Path<u64>) -> Result<Json<JobStatus>, ApiError> {
+    let mut jobs = state.jobs.lock().unwrap();
+    let job = jobs.jobs.get_mut(&id).ok_or_else(|| not_found(id))?;
+    let status = match job.state {
+        JobState::Queued => {
+            job.state = JobState::Cancelled;
+            job.status(id)
+        }
+        // Reported as cancelled once the tiles in flight are done
+        JobState::Rendering => {
+            job.cancel.cancel();
+            job.status(id)
+        }
+        _ => jobs.jobs.remove(&id).unwrap().status(id),
+    };
+    Ok(Json(status))
+}
+
+async fn file(State(state): State<AppState>, Path((id, file)): Path<(u64, String)>) -> Result<Response, ApiError> {
+    let (name, extension) = file.split_once('.').unwrap_or((&file, ""));
+    let content_type = match extension {
+        "png" => "image/png",
+        "exr" => "image/x-exr",
+        _ => return Err(ApiError(StatusCode::NOT_FOUND, format!("Unknown file {file}, use png or exr"))),
+    };
+    let (image, settings) = {
+        let jobs = state.jobs.lock().unwrap();
+        let job = jobs.jobs.get(&id).ok_or_else(|| not_found(id))?;
+        let image = match name {
+            "image" if job.state != JobState::Done => {
+                return Err(ApiError(StatusCode::CONFLICT, format!("Job {id} is {:?}, it has no final image", job.state)));
+            }
+            "image" | "preview" => job.image.clone(),
+            _ => return Err(ApiError(StatusCode::NOT_FOUND, format!("Unknown file {file}, use image or preview"))),
+        };
+        let image = image.ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("Job {id} has no {file} yet")))?;
+        (image, job.request.settings.clone())
+    };
+
+    // Encoding a large image takes a while, keep it off the async threads
+    let png = extension == "png";
+    let bytes = tokio::task::spawn_blocking(move || if png { image.to_png(&settings) } else { image.to_exr() })
+        .await
+        .map_err(|err| ApiError(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+        .map_err(|err| ApiError(StatusCode::INTERNAL_SERVER_ERROR, format!("{err:#}")))?;
+    Ok(([(header::CONTENT_TYPE, content_type)], bytes).into_response())
+}
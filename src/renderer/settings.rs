@@ -0,0 +1,302 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Result, bail, ensure};
+use clap::ValueEnum;
+use clap::builder::PossibleValue;
+use serde::{Deserialize, Serialize};
+
+use crate::core::sampler::SamplerKind;
+use crate::renderer::debug::DebugView;
+use crate::renderer::film::FilterKind;
+use crate::renderer::postprocess::{self, OutputTransform, ToneMapper};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntegratorKind {
+    #[default]
+    Classic,
+    // Classic shading plus caustics gathered from a progressive photon map
+    PhotonMap,
+    // Physically based unidirectional path tracing
+    Path,
+    // Bidirectional path tracing with multiple importance sampling
+    Bdpt,
+    // Unoccluded share of the hemisphere at the first hit, for clay renders
+    AmbientOcclusion,
+    // False colours showing normals, UVs, depth or BVH cost instead of light
+    Debug(DebugView),
+}
+
+// The debug views are named on their own, --integrator depth rather than debug-depth
+impl ValueEnum for IntegratorKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[
+            Self::Classic,
+            Self::PhotonMap,
+            Self::Path,
+            Self::Bdpt,
+            Self::AmbientOcclusion,
+            Self::Debug(DebugView::ShadingNormals),
+            Self::Debug(DebugView::GeometricNormals),
+            Self::Debug(DebugView::UvChecker),
+            Self::Debug(DebugView::Barycentrics),
+            Self::Debug(DebugView::Depth),
+            Self::Debug(DebugView::FrontFace),
+            Self::Debug(DebugView::MaterialId),
+            Self::Debug(DebugView::BvhCost),
+        ]
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(PossibleValue::new(match self {
+            Self::Classic => "classic",
+            Self::PhotonMap => "photon",
+            Self::Path => "path",
+            Self::Bdpt => "bdpt",
+            Self::AmbientOcclusion => "ao",
+            Self::Debug(view) => return view.to_possible_value(),
+        }))
+    }
+}
+
+// Rectangle of pixels, columns x0..x1 and rows y0..y1 counted from the top left
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CropWindow {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl CropWindow {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    pub fn full(width: usize, height: usize) -> Self {
+        Self::new(0, 0, width, height)
+    }
+
+    // From fractions of the image size, rounded outwards to whole pixels
+    pub fn normalized(x0: f32, y0: f32, x1: f32, y1: f32, width: usize, height: usize) -> Self {
+        let column = |t: f32, round: fn(f32) -> f32| (round(t.clamp(0.0, 1.0) * width as f32) as usize).min(width);
+        let row = |t: f32, round: fn(f32) -> f32| (round(t.clamp(0.0, 1.0) * height as f32) as usize).min(height);
+        Self::new(column(x0, f32::floor), row(y0, f32::floor), column(x1, f32::ceil), row(y1, f32::ceil))
+    }
+
+    // "x0,y0,x1,y1" in pixels, or in fractions of the image size as soon as
+    // one of the numbers has a decimal point
+    pub fn parse(text: &str, width: usize, height: usize) -> Result<Self> {
+        let parts: Vec<&str> = text.split(',').map(str::trim).collect();
+        let [x0, y0, x1, y1] = parts[..] else {
+            bail!("Crop window needs four numbers x0,y0,x1,y1, got '{text}'");
+        };
+        if parts.iter().any(|part| part.contains('.')) {
+            let [x0, y0, x1, y1] = [x0.parse()?, y0.parse()?, x1.parse()?, y1.parse()?];
+            Ok(Self::normalized(x0, y0, x1, y1, width, height))
+        } else {
+            Ok(Self::new(x0.parse()?, y0.parse()?, x1.parse()?, y1.parse()?))
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1.saturating_sub(self.x0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1.saturating_sub(self.y0)
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x0..self.x1).contains(&x) && (self.y0..self.y1).contains(&y)
+    }
+
+    // Grown by margin pixels on every side, within a width x height image
+    pub fn expand(&self, margin: usize, width: usize, height: usize) -> Self {
+        Self::new(
+            self.x0.saturating_sub(margin),
+            self.y0.saturating_sub(margin),
+            (self.x1 + margin).min(width),
+            (self.y1 + margin).min(height),
+        )
+    }
+}
+
+// Missing fields keep their defaults when deserialized
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    // Only render this part of the frame, the rest stays black
+    pub crop: Option<CropWindow>,
+    pub samples_per_pixel: i32,
+    pub max_depth: i32,
+    // Stop sampling a pixel once its relative noise drops below the threshold,
+    // taking between min_samples and max_samples instead of samples_per_pixel.
+    // Checked after min_samples and then every time the samples have doubled.
+    pub adaptive: bool,
+    pub noise_threshold: f32,
+    pub min_samples: i32,
+    pub max_samples: i32,
+    // Also write an image of the samples every pixel took
+    pub sample_heatmap: bool,
+    // Also write depth, normals, ids and the light passes to aovs.exr
+    pub aovs: bool,
+    // Filter the noise out of the finished image, guided by the AOVs
+    pub denoise: bool,
+    // Output pipeline, the render itself stays scene-linear. Exposure in EV
+    // stops, white balance as the colour temperature in Kelvin that turns white.
+    pub exposure: f32,
+    pub white_balance: f32,
+    pub tone_mapper: ToneMapper,
+    pub output_transform: OutputTransform,
+    // Strength of the glow around highlights and of the corner darkening, 0 is off
+    pub bloom: f32,
+    pub vignette: f32,
+    // Trace sampled wavelengths instead of RGB and convert via CIE XYZ
+    pub spectral: bool,
+    pub integrator: IntegratorKind,
+    pub sampler: SamplerKind,
+    // Reconstruction filter splatting every sample into the pixels around it
+    pub filter: FilterKind,
+    // Same seed and settings give the same image, whatever the thread count
+    pub seed: u64,
+    // Save the film there every checkpoint_interval and when the render ends
+    pub checkpoint: Option<PathBuf>,
+    pub checkpoint_interval: Duration,
+    // Tells scenes apart in the checkpoint key, e.g. their name, model and
+    // camera angle. Renders of different scenes must not continue each other.
+    pub scene: String,
+    // Continue from the checkpoint if there is one. Pixels that already have
+    // all their samples are skipped, raising the sample count refines them.
+    pub resume: bool,
+    // Photons traced per progressive pass. The passes take turns with the
+    // samples, every pixel gets its share of samples in each pass.
+    pub photons: usize,
+    pub photon_passes: usize,
+    // Gather radius of the first pass, later passes shrink it
+    pub photon_radius: f32,
+    // Occlusion rays per hit and how far they look for occluders
+    pub ao_samples: usize,
+    pub ao_distance: f32,
+    // Scale the classic integrator's constant ambient light by the occlusion
+    pub ambient_occlusion: bool,
+    // Let the classic integrator's shadow rays pass glass, tinted by its colour
+    pub transmissive_shadows: bool,
+}
+
+impl RenderSettings {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    pub fn crop_window(&self) -> CropWindow {
+        self.crop.unwrap_or(CropWindow::full(self.width, self.height))
+    }
+
+    // Rejects settings the renderer cannot work with
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.width >= 2 && self.height >= 2, "Image must be at least 2x2 pixels, got {}x{}", self.width, self.height);
+        ensure!(self.sample_budget() >= 1, "At least one sample per pixel is needed");
+        let crop = self.crop_window();
+        ensure!(
+            crop.width() > 0 && crop.height() > 0 && crop.x1 <= self.width && crop.y1 <= self.height,
+            "Crop window {crop:?} is empty or outside the {}x{} image",
+            self.width,
+            self.height
+        );
+        ensure!(!self.resume || self.checkpoint.is_some(), "Resuming needs a checkpoint path");
+        // The AOVs are not part of the checkpoint
+        ensure!(!(self.resume && (self.aovs || self.denoise)), "AOVs and denoising cannot be resumed from a checkpoint");
+        ensure!(self.ao_samples >= 1 && self.ao_distance > 0.0, "Ambient occlusion needs at least one ray and a positive distance");
+        Ok(())
+    }
+
+    // Most samples a single pixel can take
+    pub fn sample_budget(&self) -> i32 {
+        if self.adaptive {
+            self.max_samples
+        } else {
+            self.samples_per_pixel
+        }
+    }
+
+    // Passes the render is split into, only photon mapping takes more than one.
+    // No pass goes without samples.
+    pub fn render_passes(&self) -> usize {
+        match self.integrator {
+            IntegratorKind::PhotonMap => self.photon_passes.clamp(1, self.sample_budget().max(1) as usize),
+            _ => 1,
+        }
+    }
+
+    // Whether an adaptive render checks a pixel for convergence after this many
+    // samples: after min_samples and whenever they have doubled since
+    pub fn ends_adaptive_round(&self, samples: i32) -> bool {
+        let min = self.min_samples.max(1);
+        samples >= min && samples % min == 0 && (samples / min).count_ones() == 1
+    }
+
+    // Sample counts a pixel may stop at, the ends of the photon passes and the
+    // adaptive rounds. The samplers stratify each of these rounds on its own.
+    pub fn sample_rounds(&self) -> Vec<usize> {
+        let mut rounds: Vec<usize> = (0..self.render_passes()).map(|pass| self.pass_budget(pass) as usize).collect();
+        if self.adaptive {
+            rounds.extend((0..self.max_samples).filter(|&n| self.ends_adaptive_round(n)).map(|n| n as usize));
+        }
+        rounds.sort_unstable();
+        rounds.dedup();
+        rounds
+    }
+
+    // Samples a pixel has taken by the end of the pass
+    pub fn pass_budget(&self, pass: usize) -> i32 {
+        (self.sample_budget() as usize * (pass + 1)).div_ceil(self.render_passes()) as i32
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            crop: None,
+            samples_per_pixel: 20,
+            max_depth: 10,
+            adaptive: false,
+            noise_threshold: 0.01,
+            min_samples: 16,
+            max_samples: 256,
+            sample_heatmap: false,
+            aovs: false,
+            denoise: false,
+            exposure: 0.0,
+            white_balance: postprocess::NEUTRAL_TEMPERATURE,
+            tone_mapper: ToneMapper::Clamp,
+            output_transform: OutputTransform::Srgb,
+            bloom: 0.0,
+            vignette: 0.0,
+            spectral: false,
+            integrator: IntegratorKind::Classic,
+            sampler: SamplerKind::Sobol,
+            filter: FilterKind::Box,
+            seed: 0,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+            scene: String::new(),
+            resume: false,
+            photons: 200_000,
+            photon_passes: 8,
+            photon_radius: 0.1,
+            ao_samples: 4,
+            ao_distance: 1.0,
+            ambient_occlusion: false,
+            transmissive_shadows: false,
+        }
+    }
+}
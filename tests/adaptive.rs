@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 24;
+
+// Sky above, a glass sphere on a diffuse floor below
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let floor = Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, floor)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, glass)));
+    world.add_light(Light::new(Point3::new(1.0, 2.0, 0.0), Color::new(4.0, 4.0, 4.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 60.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn settings() -> RenderSettings {
+    RenderSettings {
+        integrator: IntegratorKind::Path,
+        adaptive: true,
+        noise_threshold: 0.02,
+        min_samples: 8,
+        max_samples: 128,
+        ..RenderSettings::new(SIZE, SIZE)
+    }
+}
+
+#[test]
+fn converged_pixels_stop_early_and_noisy_ones_get_more_samples() {
+    let (_, samples) = renderer().render_with_sample_counts(&settings()).unwrap();
+
+    // The top row only sees the noiseless sky
+    assert!(samples[..SIZE].iter().all(|&n| n == 8), "{:?}", &samples[..SIZE]);
+    // Silhouettes and the horizon need the full budget
+    assert!(samples.contains(&128));
+    // Pixels only stop at the end of a round
+    assert!(samples.iter().all(|n| [8, 16, 32, 64, 128].contains(n)), "{samples:?}");
+    // and most of the image much less
+    let total: u32 = samples.iter().sum();
+    assert!(total < 128 * (SIZE * SIZE) as u32 / 4, "{total} samples");
+}
+
+#[test]
+fn non_adaptive_renders_take_the_fixed_sample_count() {
+    let settings = RenderSettings {
+        adaptive: false,
+        samples_per_pixel: 5,
+        ..settings()
+    };
+    let (_, samples) = renderer().render_with_sample_counts(&settings).unwrap();
+    assert!(samples.iter().all(|&n| n == 5));
+}
+
+#[test]
+fn rounds_double_from_the_minimum_up_to_the_budget() {
+    assert_eq!(settings().sample_rounds(), [8, 16, 32, 64, 128]);
+    assert_eq!(RenderSettings { max_samples: 100, ..settings() }.sample_rounds(), [8, 16, 32, 64, 100]);
+    assert_eq!(RenderSettings { adaptive: false, samples_per_pixel: 5, ..settings() }.sample_rounds(), [5]);
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::{Rgba, RgbaImage};
+use raytracer::core::color::Color;
+use raytracer::core::ray::Ray;
+use raytracer::core::sampler::{self, IndependentSampler, Sampler, SamplerKind};
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::io::obj;
+use raytracer::material::material::{AlphaMode, Lambertian, Material, TexturedMaterial};
+use raytracer::material::texture::Texture;
+use raytracer::objects::hittable::{HitRecord, Hittable};
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::world::World;
+use raytracer::renderer::integrator;
+
+// Opaque on the right half (u > 0.5), transparent on the left
+fn cutout_texture() -> Texture {
+    Texture::new(RgbaImage::from_fn(8, 8, |x, _| Rgba([200, 120, 40, if x < 4 { 0 } else { 255 }])))
+}
+
+// A unit quad in the plane z = 0 with uvs following x and y
+fn quad(world: &mut World, z: f32, mat: Arc<dyn Material>) {
+    let p = |x: f32, y: f32| Point3::new(x, y, z);
+    let (a, b, c, d) = (p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0), p(0.0, 1.0));
+    let triangles = [
+        Triangle::new(a, b, c, (0.0, 0.0), (1.0, 0.0), (1.0, 1.0), mat.clone()),
+        Triangle::new(a, c, d, (0.0, 0.0), (1.0, 1.0), (0.0, 1.0), mat),
+    ];
+    world.add_object(triangles.map(|tri| Box::new(tri) as Box<dyn Hittable>));
+}
+
+// Distance to the first surface straight down the z axis at (x, y)
+fn hit_at(world: &World, x: f32, y: f32) -> Option<f32> {
+    let ray = Ray::new(Point3::new(x, y, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let mut rec = HitRecord::new();
+    world.hit(&ray, 0.001, f32::INFINITY, &mut rec).then_some(rec.t)
+}
+
+#[test]
+fn cut_away_parts_let_camera_and_shadow_rays_through() {
+    let mut world = World::new();
+    quad(&mut world, 0.0, Arc::new(TexturedMaterial::new(Arc::new(cutout_texture()))));
+    let floor = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    quad(&mut world, -1.0, floor);
+
+    assert_eq!(hit_at(&world, 0.75, 0.5), Some(5.0));
+    // Through the hole onto the floor below
+    assert_eq!(hit_at(&world, 0.25, 0.5), Some(6.0));
+
+    let light = Point3::new(0.5, 0.5, 3.0);
+    let sampler = &mut IndependentSampler::new(0);
+    assert!(integrator::visible(&world, Point3::new(0.1, 0.5, -1.0), light, sampler));
+    assert!(!integrator::visible(&world, Point3::new(0.9, 0.5, -1.0), light, sampler));
+}
+
+#[test]
+fn alpha_test_cutoff_and_opacity_maps() {
+    let texture = Arc::new(cutout_texture());
+    // An opacity map replaces the alpha channel, here opaque at the bottom only
+    let map = Texture::new(RgbaImage::from_fn(8, 8, |_, y| if y < 4 { Rgba([0, 0, 0, 255]) } else { Rgba([255; 4]) }));
+    let mut world = World::new();
+    quad(&mut world, 0.0, Arc::new(TexturedMaterial::new(texture.clone()).with_opacity_map(Arc::new(map))));
+    assert_eq!(hit_at(&world, 0.25, 0.25), Some(5.0));
+    assert_eq!(hit_at(&world, 0.75, 0.75), None);
+
+    // Half the opacity falls below the default cutoff everywhere, a lower cutoff keeps the right half
+    let faded = TexturedMaterial::new(texture).with_opacity(0.4);
+    let mut world = World::new();
+    quad(&mut world, 0.0, Arc::new(faded.with_alpha_mode(AlphaMode::Test(0.3))));
+    assert_eq!(hit_at(&world, 0.75, 0.5), Some(5.0));
+    assert_eq!(hit_at(&world, 0.25, 0.5), None);
+}
+
+// Share of the samples of one pixel whose ray down the z axis at (x, y) stops
+// at the first surface, in the plane z = 0
+fn blended_share(world: &World, (x, y): (f32, f32), kind: SamplerKind, samples: usize) -> f32 {
+    let mut sampler = sampler::create(kind, samples, 0);
+    let ray = Ray::new(Point3::new(x, y, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let hits = (0..samples)
+        .filter(|&index| {
+            sampler.start_pixel_sample(3, 4, index);
+            let mut rec = HitRecord::new();
+            world.hit_sampled(&ray, 0.001, f32::INFINITY, &mut rec, sampler.as_mut()) && rec.t == 5.0
+        })
+        .count();
+    hits as f32 / samples as f32
+}
+
+#[test]
+fn blending_hits_with_the_probability_of_the_opacity() {
+    let opaque = Arc::new(Texture::new(RgbaImage::from_pixel(1, 1, Rgba([255; 4]))));
+    let pane = || TexturedMaterial::new(opaque.clone()).with_opacity(0.3).with_alpha_mode(AlphaMode::Blend);
+    let mut world = World::new();
+    quad(&mut world, 0.0, Arc::new(pane()));
+
+    // The pixel's strata decide, so the share is exact
+    assert_eq!(blended_share(&world, (0.5, 0.5), SamplerKind::Stratified, 100), 0.3);
+    let share = blended_share(&world, (0.5, 0.5), SamplerKind::Independent, 4000);
+    assert!((share - 0.3).abs() < 0.03, "{share}");
+
+    // Every pane decides on its own, two of them let 0.7² through
+    quad(&mut world, -1.0, Arc::new(pane()));
+    let floor = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    quad(&mut world, -2.0, floor);
+    let mut sampler = IndependentSampler::new(0);
+    let ray = Ray::new(Point3::new(0.5, 0.5, 5.0), Vec3::new(0.0, 0.0, -1.0));
+    let through = (0..4000)
+        .filter(|&index| {
+            sampler.start_pixel_sample(0, 0, index);
+            let mut rec = HitRecord::new();
+            world.hit_sampled(&ray, 0.001, f32::INFINITY, &mut rec, &mut sampler) && rec.t == 7.0
+        })
+        .count();
+    let share = through as f32 / 4000.0;
+    assert!((share - 0.49).abs() < 0.03, "{share}");
+}
+
+// Loads a quad of the given MTL, centered on the origin
+async fn load_quad(dir: &Path, mtl: &str) -> World {
+    fs::write(
+        dir.join("leaf.obj"),
+        "mtllib leaf.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nusemtl leaf\nf 1/1 2/2 3/3\nf 1/1 3/3 4/4\n",
+    )
+    .unwrap();
+    fs::write(dir.join("leaf.mtl"), mtl).unwrap();
+    let mut world = World::new();
+    let fallback = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    obj::load_obj_from_path(&dir.join("leaf.obj"), &mut world, fallback, Vec3::ZERO, Vec3::ZERO, 1.0).await.unwrap();
+    world
+}
+
+#[tokio::test]
+async fn mtl_opacity_is_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    RgbaImage::from_pixel(8, 8, Rgba([40, 160, 40, 255])).save(dir.path().join("leaf.png")).unwrap();
+    // Opaque on the right half only
+    RgbaImage::from_fn(8, 8, |x, _| if x < 4 { Rgba([0, 0, 0, 255]) } else { Rgba([255; 4]) }).save(dir.path().join("mask.png")).unwrap();
+
+    for mtl in ["newmtl leaf\nmap_Kd leaf.png\nmap_d mask.png\n", "newmtl leaf\nmap_d mask.png\n"] {
+        let world = load_quad(dir.path(), mtl).await;
+        assert!(hit_at(&world, 0.25, 0.0).is_some(), "{mtl}");
+        assert!(hit_at(&world, -0.25, 0.0).is_none(), "{mtl}");
+    }
+
+    // d blends the material, rays hit it with that probability, with or without a texture
+    for d in [0.4, 0.7] {
+        for map_kd in ["map_Kd leaf.png\n", ""] {
+            let world = load_quad(dir.path(), &format!("newmtl leaf\n{map_kd}d {d}\n")).await;
+            for point in [(0.25, 0.0), (-0.25, 0.0)] {
+                let share = blended_share(&world, point, SamplerKind::Stratified, 100);
+                assert!((share - d).abs() < 0.011, "d {d} {map_kd}: {share}");
+            }
+        }
+    }
+}
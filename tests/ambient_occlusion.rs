@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Lambertian, Material};
+use raytracer::objects::hittable::Hittable;
+use raytracer::objects::light::Light;
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::world::World;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 32;
+
+fn add_quad(world: &mut World, p: [Point3; 4], mat: Arc<dyn Material>) {
+    let (a, b) = Triangle::make_quad(p[0], p[1], p[2], p[3], mat);
+    world.add_object([Box::new(a) as Box<dyn Hittable>, Box::new(b)]);
+}
+
+// A floor meeting a wall along y = 0, z = -1, seen from the front. The top rows
+// show the wall far above the crease, the bottom rows the floor far in front.
+fn corner() -> Renderer {
+    let grey: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    let p = |x: f32, y: f32, z: f32| Point3::new(x, y, z);
+    let mut world = World::new();
+    add_quad(&mut world, [p(-20.0, 0.0, -1.0), p(20.0, 0.0, -1.0), p(20.0, 0.0, 20.0), p(-20.0, 0.0, 20.0)], grey.clone());
+    add_quad(&mut world, [p(-20.0, 0.0, -1.0), p(20.0, 0.0, -1.0), p(20.0, 20.0, -1.0), p(-20.0, 20.0, -1.0)], grey);
+    world.add_light(Light::new(p(0.0, 3.0, 3.0), Color::new(4.0, 4.0, 4.0)));
+    let camera = Camera::perspective(p(0.0, 0.5, 1.5), p(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn settings(integrator: IntegratorKind) -> RenderSettings {
+    RenderSettings {
+        integrator,
+        samples_per_pixel: 16,
+        ao_samples: 8,
+        ao_distance: 0.5,
+        seed: 1,
+        ..RenderSettings::new(SIZE, SIZE)
+    }
+}
+
+// Mean of a row of pixels
+fn row(pixels: &[Color], y: usize) -> f32 {
+    pixels[y * SIZE..(y + 1) * SIZE].iter().map(|c| c.x()).sum::<f32>() / SIZE as f32
+}
+
+// The row where the floor meets the wall, the darkest one
+fn crease(pixels: &[Color]) -> usize {
+    (0..SIZE).min_by(|&a, &b| row(pixels, a).total_cmp(&row(pixels, b))).unwrap()
+}
+
+#[test]
+fn open_surfaces_are_white_and_creases_darker() {
+    let pixels = corner().render_pixels(&settings(IntegratorKind::AmbientOcclusion)).unwrap();
+    assert!(pixels.iter().all(|c| (0.0..=1.0).contains(&c.x()) && c.x() == c.y() && c.y() == c.z()));
+    assert!(row(&pixels, 0) > 0.99, "{}", row(&pixels, 0));
+    assert!(row(&pixels, SIZE - 1) > 0.99, "{}", row(&pixels, SIZE - 1));
+    let crease = crease(&pixels);
+    assert!(row(&pixels, crease) < 0.8, "{}", row(&pixels, crease));
+}
+
+#[test]
+fn occlusion_only_counts_occluders_within_the_distance() {
+    let short = corner().render_pixels(&RenderSettings { ao_distance: 0.01, ..settings(IntegratorKind::AmbientOcclusion) }).unwrap();
+    let long = corner().render_pixels(&RenderSettings { ao_distance: 5.0, ..settings(IntegratorKind::AmbientOcclusion) }).unwrap();
+    let mean = |pixels: &[Color]| pixels.iter().map(|c| c.x()).sum::<f32>() / pixels.len() as f32;
+    assert!(mean(&long) < mean(&short), "{} >= {}", mean(&long), mean(&short));
+}
+
+#[test]
+fn classic_ambient_is_darkened_only_when_asked() {
+    let renderer = corner();
+    let plain = settings(IntegratorKind::Classic);
+    let unchanged = renderer.render_pixels(&RenderSettings { ao_samples: 2, ..plain.clone() }).unwrap();
+    assert!(renderer.render_pixels(&plain).unwrap().iter().zip(&unchanged).all(|(a, b)| (*a - *b).length() == 0.0));
+
+    let occluded = renderer.render_pixels(&RenderSettings { ambient_occlusion: true, ..plain.clone() }).unwrap();
+    let plain = renderer.render_pixels(&plain).unwrap();
+    let crease = crease(&occluded);
+    assert!(row(&occluded, crease) < row(&plain, crease), "{} >= {}", row(&occluded, crease), row(&plain, crease));
+    let mean = |pixels: &[Color]| pixels.iter().map(|c| c.x()).sum::<f32>() / pixels.len() as f32;
+    assert!(mean(&occluded) < mean(&plain), "{} >= {}", mean(&occluded), mean(&plain));
+}
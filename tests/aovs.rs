@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Lambertian, Material, Metal};
+use raytracer::objects::hittable::Hittable;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::world::World;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 24;
+
+// Sky, a floor, a metal sphere and a two triangle quad sharing the floor's material
+fn renderer() -> Renderer {
+    let grey: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    let metal: Arc<dyn Material> = Arc::new(Metal::new(Color::new(0.9, 0.8, 0.7), 0.1));
+
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, Arc::clone(&grey))));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(-0.5, 0.0, -1.0), 0.4, metal)));
+    let (t1, t2) = Triangle::make_quad(
+        Point3::new(0.2, -0.4, -1.2),
+        Point3::new(0.9, -0.4, -1.2),
+        Point3::new(0.9, 0.3, -1.2),
+        Point3::new(0.2, 0.3, -1.2),
+        grey,
+    );
+    world.add_object([Box::new(t1) as Box<dyn Hittable>, Box::new(t2)]);
+    world.add_light(Light::new(Point3::new(1.0, 2.0, 0.0), Color::new(4.0, 4.0, 4.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 60.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn settings(integrator: IntegratorKind) -> RenderSettings {
+    RenderSettings {
+        integrator,
+        samples_per_pixel: 4,
+        max_depth: 4,
+        aovs: true,
+        ..RenderSettings::new(SIZE, SIZE)
+    }
+}
+
+#[test]
+fn light_passes_add_up_to_the_beauty_image() {
+    for kind in [IntegratorKind::Classic, IntegratorKind::Path, IntegratorKind::Bdpt] {
+        let output = renderer().render_output(&settings(kind)).unwrap();
+        let aovs = output.aovs.unwrap();
+        for (k, (pixel, passes)) in output.pixels.iter().zip(&aovs.passes).enumerate() {
+            let difference = (*pixel - passes.total()).length();
+            assert!(difference <= 1e-4 * pixel.length().max(1.0), "{kind:?} pixel {k}: {pixel} vs {}", passes.total());
+        }
+
+        // The top row only sees the sky, everything else none of it
+        assert!(aovs.passes[..SIZE].iter().all(|p| p.total().length() == p.emission.length()));
+        let centre = &aovs.passes[SIZE * (SIZE / 2) + SIZE / 2 - 6];
+        assert_eq!(centre.emission.length(), 0.0, "{kind:?}");
+        assert!(centre.direct_specular.length() + centre.indirect_specular.length() > 0.0, "{kind:?}");
+    }
+}
+
+#[test]
+fn geometry_and_ids_describe_the_visible_surfaces() {
+    let aovs = renderer().render_output(&settings(IntegratorKind::Classic)).unwrap().aovs.unwrap();
+
+    // Sky at the top, nothing hit
+    assert!(aovs.depth[..SIZE].iter().all(|d| d.is_infinite()));
+    assert!(aovs.object_id[..SIZE].iter().all(|&id| id == 0));
+    assert!(aovs.material_id[..SIZE].iter().all(|&id| id == 0));
+    assert!(aovs.shadow[..SIZE].iter().all(|&s| s == 1.0));
+
+    // Floor, sphere and the quad as a single object
+    let mut objects = aovs.object_id.clone();
+    objects.sort();
+    objects.dedup();
+    assert_eq!(objects, [0, 1, 2, 3]);
+
+    // The quad reuses the floor's material, numbered in scan order
+    let mut materials = aovs.material_id.clone();
+    materials.sort();
+    materials.dedup();
+    assert_eq!(materials, [0, 1, 2]);
+
+    // The quad faces the camera 2.2 units away, its centre covers this pixel
+    let quad = SIZE * (SIZE / 2) + SIZE / 2 + 5;
+    assert_eq!(aovs.object_id[quad], 3);
+    assert!((aovs.normal[quad] - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-3);
+    assert!(aovs.depth[quad] > 2.2 && aovs.depth[quad] < 2.4, "{}", aovs.depth[quad]);
+    assert!((aovs.albedo[quad] - Color::new(0.6, 0.6, 0.6)).length() < 1e-5);
+    assert!(aovs.shadow.iter().all(|s| (0.0..=1.0).contains(s)));
+}
+
+#[test]
+fn aovs_are_only_rendered_on_request() {
+    let settings = RenderSettings {
+        aovs: false,
+        ..settings(IntegratorKind::Classic)
+    };
+    assert!(renderer().render_output(&settings).unwrap().aovs.is_none());
+}
+
+#[test]
+fn exr_holds_every_aov_as_a_channel() {
+    let output = renderer().render_output(&settings(IntegratorKind::Path)).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("aovs.exr");
+    output.aovs.unwrap().write_exr(&path, &output.pixels).unwrap();
+
+    let image = exr::prelude::read_all_flat_layers_from_file(&path).unwrap();
+    let layer = &image.layer_data[0];
+    assert_eq!((layer.size.0, layer.size.1), (SIZE, SIZE));
+    let names: Vec<String> = layer.channel_data.list.iter().map(|c| c.name.to_string()).collect();
+    for name in [
+        "R", "G", "B", "Z", "N.X", "N.Y", "N.Z", "albedo.R", "uv.U", "uv.V", "objectId", "materialId", "shadow.Y",
+        "emission.R", "diffuse_direct.G", "diffuse_indirect.B", "specular_direct.R", "specular_indirect.G",
+    ] {
+        assert!(names.iter().any(|n| n == name), "missing {name} in {names:?}");
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::sampler::IndependentSampler;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian, Material};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::world::World;
+use raytracer::renderer::integrator;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 16;
+const BLOCKS: usize = 4;
+
+fn wall(world: &mut World, corners: [Point3; 4], material: &Arc<dyn Material>) {
+    let (t1, t2) = Triangle::make_quad(corners[0], corners[1], corners[2], corners[3], Arc::clone(material));
+    world.add_hittable(Box::new(t1));
+    world.add_hittable(Box::new(t2));
+}
+
+// Closed Cornell box with a point light below the ceiling, so every path ends
+// on a light and both integrators estimate the same integral. Neither can light
+// the box through a glass ball, which leaves out the same caustic paths.
+fn cornell_box(ball: Arc<dyn Material>) -> (World, Camera) {
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let red: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let green: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+
+    let a = 1.0;
+    let p = |x: f32, y: f32, z: f32| Point3::new(x * a, y * a, z * a);
+
+    let mut world = World::new();
+    wall(&mut world, [p(-1.0, -1.0, -1.0), p(1.0, -1.0, -1.0), p(1.0, -1.0, 1.0), p(-1.0, -1.0, 1.0)], &white);
+    wall(&mut world, [p(-1.0, 1.0, -1.0), p(1.0, 1.0, -1.0), p(1.0, 1.0, 1.0), p(-1.0, 1.0, 1.0)], &white);
+    wall(&mut world, [p(-1.0, -1.0, -1.0), p(1.0, -1.0, -1.0), p(1.0, 1.0, -1.0), p(-1.0, 1.0, -1.0)], &white);
+    wall(&mut world, [p(-1.0, -1.0, 1.0), p(1.0, -1.0, 1.0), p(1.0, 1.0, 1.0), p(-1.0, 1.0, 1.0)], &white);
+    wall(&mut world, [p(-1.0, -1.0, -1.0), p(-1.0, -1.0, 1.0), p(-1.0, 1.0, 1.0), p(-1.0, 1.0, -1.0)], &red);
+    wall(&mut world, [p(1.0, -1.0, -1.0), p(1.0, -1.0, 1.0), p(1.0, 1.0, 1.0), p(1.0, 1.0, -1.0)], &green);
+    world.add_hittable(Box::new(Sphere::new(p(0.3, -0.6, -0.3), 0.4, ball)));
+    world.add_light(Light::new(p(0.0, 0.8, 0.0), Color::new(1.5, 1.5, 1.5)));
+
+    let camera = Camera::perspective(p(0.0, 0.0, 0.95), p(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 70.0, 1.0);
+    (world, camera)
+}
+
+// Block averages of a small render, which the two estimators must agree on
+fn render_blocks(kind: IntegratorKind, samples: usize, ball: &Arc<dyn Material>) -> Vec<Color> {
+    let (world, camera) = cornell_box(Arc::clone(ball));
+    let settings = RenderSettings {
+        integrator: kind,
+        ..RenderSettings::new(SIZE, SIZE)
+    };
+    let integrator = integrator::create(&settings, &world, 0);
+    let mut sampler = IndependentSampler::new(0);
+
+    let block = SIZE / BLOCKS;
+    let mut blocks = vec![Color::ZERO; BLOCKS * BLOCKS];
+    for j in 0..SIZE {
+        for i in 0..SIZE {
+            for s in 0..samples {
+                // Fixed jitter pattern keeps the test independent of the pixel sampler
+                let du = (s % 8) as f32 / 8.0 + 1.0 / 16.0;
+                let dv = (s / 8 % 8) as f32 / 8.0 + 1.0 / 16.0;
+                let r = camera.get_ray((i as f32 + du) / SIZE as f32, (j as f32 + dv) / SIZE as f32);
+                blocks[(j / block) * BLOCKS + i / block] += integrator.li(&r, &world, 4, &mut sampler);
+            }
+        }
+    }
+
+    let per_block = (block * block * samples) as f32;
+    blocks.into_iter().map(|c| c / per_block).collect()
+}
+
+fn mean(blocks: &[Color]) -> Color {
+    blocks.iter().fold(Color::ZERO, |acc, &c| acc + c) / blocks.len() as f32
+}
+
+fn assert_bdpt_matches_path_tracer(ball: Arc<dyn Material>) {
+    let reference = render_blocks(IntegratorKind::Path, 256, &ball);
+    let bdpt = render_blocks(IntegratorKind::Bdpt, 256, &ball);
+
+    let (expected, actual) = (mean(&reference), mean(&bdpt));
+    let channels = [(expected.x(), actual.x()), (expected.y(), actual.y()), (expected.z(), actual.z())];
+    for (c, (expected, actual)) in channels.into_iter().enumerate() {
+        let relative = (actual - expected).abs() / expected;
+        assert!(relative < 0.03, "channel {c}: bdpt {actual} vs path {expected}");
+    }
+
+    for (k, (b, r)) in bdpt.iter().zip(&reference).enumerate() {
+        let (b, r) = (b.length(), r.length());
+        assert!((b - r).abs() / r < 0.1, "block {k}: bdpt {b} vs path {r}");
+    }
+}
+
+#[test]
+fn bdpt_matches_path_tracer_on_cornell_box() {
+    assert_bdpt_matches_path_tracer(Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73))));
+}
+
+#[test]
+fn bdpt_matches_path_tracer_through_glass() {
+    assert_bdpt_matches_path_tracer(Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5)));
+}
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use raytracer::core::color::Color;
+use raytracer::core::ray::Ray;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::Lambertian;
+use raytracer::objects::hittable::{HitRecord, Hittable};
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::world::World;
+use raytracer::renderer::benchmark::{self, BenchmarkReport, SCENES};
+use raytracer::renderer::settings::RenderSettings;
+
+fn random_point(rng: &mut SmallRng, size: f32) -> Point3 {
+    Point3::new(rng.random_range(-size..size), rng.random_range(-size..size), rng.random_range(-size..size))
+}
+
+#[test]
+fn bvh_finds_the_same_hits_as_a_linear_scan() {
+    let mut rng = SmallRng::seed_from_u64(7);
+    let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+    for _ in 0..300 {
+        let center = random_point(&mut rng, 5.0);
+        objects.push(Box::new(Sphere::new(center, rng.random_range(0.05..0.5), mat.clone())));
+        let (a, b) = (random_point(&mut rng, 0.6), random_point(&mut rng, 0.6));
+        objects.push(Box::new(Triangle::new_untextured(center, center + a, center + b, mat.clone())));
+    }
+    let mut world = World::new();
+    for object in &objects {
+        world.add_hittable(object.clone());
+    }
+
+    for _ in 0..2000 {
+        let ray = Ray::new(random_point(&mut rng, 8.0), random_point(&mut rng, 1.0));
+        let mut expected = None;
+        let mut closest = f32::INFINITY;
+        for object in &objects {
+            let mut rec = HitRecord::new();
+            if object.hit(&ray, 0.001, closest, &mut rec) {
+                closest = rec.t;
+                expected = Some(rec.t);
+            }
+        }
+
+        let mut rec = HitRecord::new();
+        let found = world.hit(&ray, 0.001, f32::INFINITY, &mut rec).then_some(rec.t);
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn bvh_is_rebuilt_when_objects_are_added() {
+    let mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -5.0), 1.0, mat.clone())));
+    let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+    let mut rec = HitRecord::new();
+    assert!(world.hit(&ray, 0.001, f32::INFINITY, &mut rec));
+    assert_eq!(rec.t, 4.0);
+
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, mat)));
+    assert!(world.hit(&ray, 0.001, f32::INFINITY, &mut rec));
+    assert_eq!((rec.t, rec.object_id), (1.5, 2));
+}
+
+#[test]
+fn builtin_scenes_report_every_phase() {
+    let mut report = BenchmarkReport {
+        threads: rayon::current_num_threads(),
+        scenes: Vec::new(),
+    };
+    for scene in &SCENES {
+        let settings = RenderSettings { samples_per_pixel: 1, ..scene.settings(16, 12) };
+        let result = benchmark::run(scene, &settings, 2).unwrap();
+        assert_eq!(result.timings.render_runs_seconds.len(), 2);
+        assert!(result.rays >= 16 * 12, "{}: {} rays", scene.name, result.rays);
+        assert!(result.rays_per_second > 0.0);
+        assert!(result.primitives > 0 && result.bvh_nodes > 0 && result.memory.bvh_bytes > 0);
+        report.scenes.push(result);
+    }
+    assert!(report.scenes.iter().any(|scene| scene.lights >= 64));
+    assert!(report.scenes.iter().any(|scene| scene.primitives >= 100_000));
+
+    let json = serde_json::to_string(&report).unwrap();
+    let parsed: BenchmarkReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.scenes.len(), SCENES.len());
+    assert!(benchmark::scene_by_name("teapot").is_err());
+}
@@ -0,0 +1,159 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::checkpoint::Checkpoint;
+use raytracer::renderer::film::FilterKind;
+use raytracer::renderer::progress::{CancelToken, NoProgress, ProgressReport};
+use raytracer::renderer::renderer::{RenderOutput, Renderer};
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.4)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, glass)));
+    world.add_light(Light::new(Point3::new(1.0, 3.0, 1.0), Color::new(6.0, 6.0, 6.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.3, 1.5), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.4);
+    Renderer::new(camera, world)
+}
+
+// Six tiles, three in each row
+fn settings(checkpoint: &Path, samples_per_pixel: i32, resume: bool) -> RenderSettings {
+    RenderSettings {
+        samples_per_pixel,
+        max_depth: 4,
+        integrator: IntegratorKind::Path,
+        filter: FilterKind::Mitchell,
+        checkpoint: Some(checkpoint.to_path_buf()),
+        resume,
+        ..RenderSettings::new(70, 50)
+    }
+}
+
+fn bits(output: &RenderOutput) -> Vec<u32> {
+    output.pixels.iter().flat_map(|c| [c.x().to_bits(), c.y().to_bits(), c.z().to_bits()]).collect()
+}
+
+fn in_pool<T: Send>(threads: usize, f: impl FnOnce() -> T + Send) -> T {
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap().install(f)
+}
+
+#[test]
+fn resumed_render_matches_an_uninterrupted_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("render.ckpt");
+    let renderer = renderer();
+    let uninterrupted = renderer.render_output(&RenderSettings { checkpoint: None, ..settings(&path, 3, false) }).unwrap();
+
+    let cancel = CancelToken::new();
+    let stop_after_two = |report: &ProgressReport| {
+        if report.tiles_done == 2 {
+            cancel.cancel();
+        }
+    };
+    let interrupted = in_pool(1, || renderer.render_with(&settings(&path, 3, false), &stop_after_two, &cancel)).unwrap();
+    assert!(interrupted.cancelled);
+    assert_eq!(interrupted.samples.iter().filter(|&&n| n == 3).count(), 2 * 32 * 32);
+
+    let resumed = in_pool(4, || renderer.render_with(&settings(&path, 3, true), &NoProgress, &CancelToken::new())).unwrap();
+    assert!(!resumed.cancelled);
+    assert!(resumed.samples.iter().all(|&n| n == 3));
+    assert_eq!(bits(&resumed), bits(&uninterrupted));
+}
+
+#[test]
+fn more_samples_refine_a_finished_render() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("render.ckpt");
+    let renderer = renderer();
+    let first = renderer.render_output(&settings(&path, 4, false)).unwrap();
+    let refined = renderer.render_output(&settings(&path, 8, true)).unwrap();
+    assert!(refined.samples.iter().all(|&n| n == 8));
+    assert_ne!(bits(&refined), bits(&first));
+
+    // Close to rendering all eight at once, only the summation order differs
+    let fresh = renderer.render_output(&RenderSettings { checkpoint: None, ..settings(&path, 8, false) }).unwrap();
+    let difference: f32 = refined.pixels.iter().zip(&fresh.pixels).map(|(a, b)| (*a - *b).length()).sum::<f32>() / fresh.pixels.len() as f32;
+    assert!(difference < 0.05, "{difference}");
+
+    // Nothing is left to do for a finished render
+    let again = renderer.render_output(&settings(&path, 8, true)).unwrap();
+    assert_eq!(bits(&again), bits(&refined));
+}
+
+#[test]
+fn checkpoints_are_written_while_rendering() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("render.ckpt");
+    let settings = RenderSettings {
+        checkpoint_interval: Duration::ZERO,
+        ..settings(&path, 2, false)
+    };
+
+    let seen = std::sync::Mutex::new(None);
+    let inspect = |report: &ProgressReport| {
+        // Written after the previous tile
+        if report.tiles_done == 3 {
+            let checkpoint = Checkpoint::load(&path, &Checkpoint::key(&settings)).unwrap();
+            *seen.lock().unwrap() = Some(checkpoint.stats.iter().filter(|s| s.samples() == 2).count());
+        }
+    };
+    in_pool(1, || renderer().render_with(&settings, &inspect, &CancelToken::new())).unwrap();
+    assert_eq!(seen.into_inner().unwrap(), Some(2 * 32 * 32));
+}
+
+#[test]
+fn checkpoints_of_other_settings_are_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("render.ckpt");
+    let renderer = renderer();
+    renderer.render_with(&settings(&path, 1, false), &NoProgress, &CancelToken::new()).unwrap();
+
+    let other_seed = RenderSettings { seed: 5, ..settings(&path, 1, true) };
+    assert!(renderer.render_with(&other_seed, &NoProgress, &CancelToken::new()).is_err());
+    let with_aovs = RenderSettings { aovs: true, ..settings(&path, 1, true) };
+    assert!(renderer.render_with(&with_aovs, &NoProgress, &CancelToken::new()).is_err());
+
+    fs::write(&path, b"not a checkpoint").unwrap();
+    assert!(renderer.render_with(&settings(&path, 1, true), &NoProgress, &CancelToken::new()).is_err());
+    // Another scene with the same settings
+    let elsewhere = RenderSettings { scene: "museum".to_string(), ..settings(&path, 1, true) };
+    assert_ne!(Checkpoint::key(&elsewhere), Checkpoint::key(&settings(&path, 1, true)));
+}
+
+#[test]
+fn checkpoint_headers_are_checked_before_reading_the_pixels() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("render.ckpt");
+    let settings = settings(&path, 1, false);
+    let key = Checkpoint::key(&settings);
+    renderer().render_with(&settings, &NoProgress, &CancelToken::new()).unwrap();
+    assert!(Checkpoint::load(&path, &key).is_ok());
+    assert!(Checkpoint::load(&path, "other settings").is_err());
+
+    // A header claiming a huge film fails instead of allocating it
+    let mut forged = b"RTCKPT01".to_vec();
+    forged.extend((key.len() as u32).to_le_bytes());
+    forged.extend(key.as_bytes());
+    forged.extend(u32::MAX.to_le_bytes());
+    forged.extend(u32::MAX.to_le_bytes());
+    fs::write(&path, &forged).unwrap();
+    assert!(Checkpoint::load(&path, &key).is_err());
+
+    // So does one claiming a huge key
+    let mut forged = b"RTCKPT01".to_vec();
+    forged.extend(u32::MAX.to_le_bytes());
+    fs::write(&path, &forged).unwrap();
+    assert!(Checkpoint::load(&path, &key).is_err());
+}
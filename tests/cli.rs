@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command as Process;
+
+use clap::{CommandFactory, Parser};
+
+// The arguments live in the binary, so the tests build their own copy
+#[allow(dead_code)]
+#[path = "../src/app/args.rs"]
+mod args;
+
+use args::{Cli, Command};
+use raytracer::renderer::debug::DebugView;
+use raytracer::renderer::postprocess::ToneMapper;
+use raytracer::renderer::settings::IntegratorKind;
+
+fn parse(args: &[&str]) -> Command {
+    match Cli::try_parse_from([&["raytracer"], args].concat()) {
+        Ok(cli) => cli.command,
+        Err(err) => panic!("{args:?}: {err}"),
+    }
+}
+
+#[test]
+fn every_subcommand_parses() {
+    Cli::command().debug_assert();
+    let Command::Render(render) = parse(&["render", "-m", "suzanne", "--integrator", "path", "--spp", "64", "-o", "out.png"]) else {
+        panic!("not a render");
+    };
+    assert_eq!((render.scene.model.as_str(), render.scene.scene.as_str()), ("suzanne", "required"));
+    assert_eq!((render.settings.integrator, render.settings.spp), (IntegratorKind::Path, 64));
+    assert_eq!(render.output, PathBuf::from("out.png"));
+    assert!(render.checkpoint.is_none());
+
+    let Command::Animate(animate) = parse(&["animate", "--frames", "12", "--scene", "museum"]) else {
+        panic!("not an animation");
+    };
+    assert_eq!((animate.frames, animate.scene.scene.as_str()), (12, "museum"));
+
+    // The debug views are integrators of their own
+    let Command::Render(render) = parse(&["render", "--integrator", "bvh-cost", "--tonemap", "agx"]) else {
+        panic!("not a render");
+    };
+    assert_eq!(render.settings.integrator, IntegratorKind::Debug(DebugView::BvhCost));
+    assert_eq!(render.settings.tonemap, ToneMapper::AgX);
+
+    assert!(matches!(parse(&["list-models"]), Command::ListModels));
+    assert!(matches!(parse(&["inspect", "lucy"]), Command::Inspect { model } if model == "lucy"));
+    let Command::Bench(bench) = parse(&["bench", "--scene", "glass", "--scene", "mesh", "--spp", "4"]) else {
+        panic!("not a benchmark");
+    };
+    assert_eq!((bench.scene, bench.spp, bench.runs), (vec!["glass".to_string(), "mesh".to_string()], Some(4), 3));
+    assert!(matches!(parse(&["serve"]), Command::Serve { addr } if addr == "127.0.0.1:8080"));
+    assert!(matches!(parse(&["worker", "10.0.0.2:7000"]), Command::Worker { addr } if addr == "10.0.0.2:7000"));
+}
+
+#[test]
+fn checkpoints_are_opt_in_with_an_optional_path() {
+    let checkpoint = |args: &[&str]| match parse(&[&["render"], args].concat()) {
+        Command::Render(render) => render.checkpoint,
+        _ => panic!("not a render"),
+    };
+    assert_eq!(checkpoint(&[]), None);
+    assert_eq!(checkpoint(&["--checkpoint"]), Some(None));
+    assert_eq!(checkpoint(&["--checkpoint", "a.ckpt"]), Some(Some(PathBuf::from("a.ckpt"))));
+}
+
+#[test]
+fn malformed_command_lines_are_rejected() {
+    for args in [
+        &["render", "--spp", "many"][..],
+        &["render", "--no-such-flag"],
+        &["render", "--integrator", "debug"],
+        &["render", "--filter", "blackman"],
+        &["worker"],
+        &["inspect"],
+        &["frobnicate"],
+    ] {
+        assert!(Cli::try_parse_from([&["raytracer"], args].concat()).is_err(), "{args:?}");
+    }
+}
+
+// Runs a render against a config with a single model. The names are checked
+// before the model is downloaded.
+fn render_error(args: &[&str]) -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    fs::write(&config, "[models]\ncube = \"http://127.0.0.1:9/cube.obj\"\n").unwrap();
+    let output = Process::new(env!("CARGO_BIN_EXE_raytracer"))
+        .current_dir(dir.path())
+        .args(["-c", config.to_str().unwrap(), "render", "-r", "8"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{args:?} succeeded");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+#[test]
+fn unknown_names_are_reported() {
+    assert!(render_error(&["-m", "teapot"]).contains("Model 'teapot' not found"));
+    assert!(render_error(&["-m", "cube", "--scene", "moon"]).contains("Unknown scene 'moon'"));
+    assert!(render_error(&["-m", "cube", "--integrator", "radiosity"]).contains("invalid value 'radiosity' for '--integrator"));
+    assert!(render_error(&["-m", "cube", "--sampler", "sobel"]).contains("invalid value 'sobel' for '--sampler"));
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::film::FilterKind;
+use raytracer::renderer::image;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{CropWindow, IntegratorKind, RenderSettings};
+
+const WIDTH: usize = 70;
+const HEIGHT: usize = 50;
+
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.4)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, glass)));
+    world.add_light(Light::new(Point3::new(1.0, 3.0, 1.0), Color::new(6.0, 6.0, 6.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.3, 1.5), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.4);
+    Renderer::new(camera, world)
+}
+
+fn settings(crop: Option<CropWindow>) -> RenderSettings {
+    RenderSettings {
+        samples_per_pixel: 2,
+        max_depth: 4,
+        integrator: IntegratorKind::Path,
+        // Reaches across the crop border
+        filter: FilterKind::Mitchell,
+        crop,
+        ..RenderSettings::new(WIDTH, HEIGHT)
+    }
+}
+
+#[test]
+fn cropped_pixels_match_the_full_render_exactly() {
+    let renderer = renderer();
+    // Straddles tile borders on both axes
+    let window = CropWindow::new(27, 20, 41, 37);
+    let full = renderer.render_output(&settings(None)).unwrap();
+    let cropped = renderer.render_output(&settings(Some(window))).unwrap();
+
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let p = y * WIDTH + x;
+            let (a, b) = (full.pixels[p], cropped.pixels[p]);
+            if window.contains(x, y) {
+                assert_eq!([a.x(), a.y(), a.z()].map(f32::to_bits), [b.x(), b.y(), b.z()].map(f32::to_bits), "pixel ({x}, {y})");
+                assert_eq!(cropped.samples[p], 2);
+            } else {
+                assert_eq!(b.length(), 0.0, "pixel ({x}, {y}) outside the window");
+                assert_eq!(cropped.samples[p], 0);
+            }
+        }
+    }
+}
+
+#[test]
+fn crop_windows_parse_from_pixels_or_fractions() {
+    assert_eq!(CropWindow::parse("10,20,30,40", 100, 50).unwrap(), CropWindow::new(10, 20, 30, 40));
+    // Rounded outwards to whole pixels
+    assert_eq!(CropWindow::parse("0.25, 0.5, 0.755, 1.0", 100, 50).unwrap(), CropWindow::new(25, 25, 76, 50));
+    assert!(CropWindow::parse("1,2,3", 100, 50).is_err());
+    assert!(CropWindow::parse("a,2,3,4", 100, 50).is_err());
+
+    assert!(settings(Some(CropWindow::new(10, 10, 10, 20))).validate().is_err());
+    assert!(settings(Some(CropWindow::new(0, 0, WIDTH + 1, 10))).validate().is_err());
+    assert!(settings(Some(CropWindow::new(0, 0, WIDTH, HEIGHT))).validate().is_ok());
+}
+
+#[test]
+fn regions_are_written_over_the_previous_render_or_on_their_own() {
+    let dir = tempfile::tempdir().unwrap();
+    let renderer = renderer();
+    let window = CropWindow::new(5, 10, 40, 30);
+    let full_settings = settings(None);
+    let crop_settings = RenderSettings {
+        seed: 9,
+        ..settings(Some(window))
+    };
+    let full = renderer.render(&full_settings).unwrap();
+    let crop = renderer.render(&crop_settings).unwrap();
+    let read = |path: &std::path::Path| ::image::open(path).unwrap().to_rgb8();
+
+    for name in ["frame.ppm", "frame.png"] {
+        let path = dir.path().join(name);
+        // Without an earlier image the rest of the frame is black
+        crop.save_region(&path, &crop_settings, &window).unwrap();
+        assert_eq!(read(&path).get_pixel(0, 0).0, [0, 0, 0]);
+
+        full.save(&path, &full_settings).unwrap();
+        crop.save_region(&path, &crop_settings, &window).unwrap();
+        let (merged, before, after) = (read(&path), full.display(&full_settings), crop.display(&crop_settings));
+        let byte = |c: Color| [c.x(), c.y(), c.z()].map(|v| (256.0 * v.clamp(0.0, 0.999)) as u8);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let expected = if window.contains(x, y) { after[y * WIDTH + x] } else { before[y * WIDTH + x] };
+                assert_eq!(merged.get_pixel(x as u32, y as u32).0, byte(expected), "{name} ({x}, {y})");
+            }
+        }
+    }
+
+    let exr = dir.path().join("frame.exr");
+    full.save_exr(&exr).unwrap();
+    crop.save_region(&exr, &crop_settings, &window).unwrap();
+    let merged = image::read_exr(&exr).unwrap();
+    assert_eq!(merged.pixel(20, 20), crop.pixel(20, 20));
+    assert_eq!(merged.pixel(60, 45), full.pixel(60, 45));
+
+    // A previous image of another size is not silently replaced
+    let small = dir.path().join("small.ppm");
+    renderer.render(&RenderSettings::new(8, 8)).unwrap().save(&small, &full_settings).unwrap();
+    assert!(crop.save_region(&small, &crop_settings, &window).is_err());
+
+    let sub = dir.path().join("sub.png");
+    crop.save_crop(&sub, &crop_settings, &window).unwrap();
+    assert_eq!(read(&sub).dimensions(), (35, 20));
+    assert_eq!(crop.crop(&window).pixel(0, 0), crop.pixel(5, 10));
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+}
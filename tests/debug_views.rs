@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::ray::Ray;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Lambertian, Material};
+use raytracer::objects::hittable::{HitRecord, Hittable};
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::Triangle;
+use raytracer::objects::world::World;
+use raytracer::renderer::debug::DebugView;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 24;
+// Where the sphere and the quad fill the pixels
+const CENTER: usize = (SIZE / 2) * SIZE + SIZE / 2;
+const QUAD: usize = (SIZE / 2) * SIZE + SIZE - 6;
+
+// A sphere in front of the camera and a two triangle quad to its right
+fn renderer() -> Renderer {
+    let red: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.2)));
+    let grey: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -0.5), 0.5, red)));
+    let (t1, t2) = Triangle::make_quad(
+        Point3::new(0.5, -0.5, -1.0),
+        Point3::new(1.5, -0.5, -1.0),
+        Point3::new(1.5, 0.5, -1.0),
+        Point3::new(0.5, 0.5, -1.0),
+        grey,
+    );
+    world.add_object([Box::new(t1) as Box<dyn Hittable>, Box::new(t2)]);
+    Renderer::new(camera(), world)
+}
+
+fn camera() -> Camera {
+    Camera::perspective(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 90.0, 1.0)
+}
+
+fn render(renderer: &Renderer, view: DebugView) -> Vec<Color> {
+    let settings = RenderSettings {
+        integrator: IntegratorKind::Debug(view),
+        samples_per_pixel: 1,
+        ..RenderSettings::new(SIZE, SIZE)
+    };
+    renderer.render_pixels(&settings).unwrap()
+}
+
+fn assert_close(actual: Color, expected: Color, tolerance: f32) {
+    assert!((actual - expected).length() < tolerance, "{actual} is not close to {expected}");
+}
+
+#[test]
+fn normals_depth_and_faces_of_the_first_hit() {
+    let renderer = renderer();
+    // Straight ahead the sphere faces the camera at a distance of 1
+    assert_close(render(&renderer, DebugView::ShadingNormals)[CENTER], Color::new(0.5, 0.5, 1.0), 0.25);
+    assert_close(render(&renderer, DebugView::Depth)[CENTER], Color::new(0.5, 0.5, 0.5), 0.05);
+    assert_close(render(&renderer, DebugView::FrontFace)[CENTER], Color::new(0.0, 1.0, 0.0), 1e-6);
+
+    // From inside a sphere every hit is a back face, whose outward normal points away
+    let mut world = World::new();
+    let grey = Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, 1.0), 5.0, grey)));
+    let inside = Renderer::new(camera(), world);
+    assert!(render(&inside, DebugView::FrontFace).iter().all(|&c| c.x() == 1.0 && c.y() == 0.0));
+    assert_close(render(&inside, DebugView::ShadingNormals)[CENTER], Color::new(0.5, 0.5, 1.0), 0.25);
+    assert_close(render(&inside, DebugView::GeometricNormals)[CENTER], Color::new(0.5, 0.5, 0.0), 0.25);
+}
+
+#[test]
+fn barycentrics_uvs_and_materials() {
+    let renderer = renderer();
+    let weights = render(&renderer, DebugView::Barycentrics)[QUAD];
+    assert!(weights.x() >= 0.0 && weights.y() >= 0.0 && weights.z() >= 0.0);
+    assert!((weights.x() + weights.y() + weights.z() - 1.0).abs() < 1e-4);
+
+    // The quad has no texture coordinates
+    let uv = render(&renderer, DebugView::UvChecker);
+    assert_eq!((uv[QUAD].x(), uv[QUAD].y()), (0.0, 0.0));
+    assert!(uv[QUAD].z() > 0.0);
+
+    let materials = render(&renderer, DebugView::MaterialId);
+    assert_close(materials[CENTER], materials[CENTER + 1], 1e-6);
+    assert!((materials[CENTER] - materials[QUAD]).length() > 0.0);
+    assert_close(materials[0], Color::ZERO, 1e-6);
+}
+
+#[test]
+fn bvh_cost_grows_with_the_geometry_a_ray_passes() {
+    let grey = Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    let mut world = World::new();
+    for i in 0..1000 {
+        let center = Point3::new((i % 10) as f32, (i / 10 % 10) as f32, -((i / 100) as f32) - 2.0);
+        world.add_hittable(Box::new(Sphere::new(center, 0.3, grey.clone())));
+    }
+    let cost = |direction: Vec3| world.hit_with_cost(&Ray::new(Point3::new(4.5, 4.5, 0.0), direction), 0.001, f32::INFINITY, &mut HitRecord::new()).1;
+    let (miss, hit) = (cost(Vec3::new(0.0, 0.0, 1.0)), cost(Vec3::new(0.0, 0.0, -1.0)));
+    assert!(miss < hit, "{miss} >= {hit}");
+    // Far below a test against every sphere
+    assert!(hit < 100, "{hit}");
+
+    let heatmap = render(&Renderer::new(camera(), world), DebugView::BvhCost);
+    assert!(heatmap.iter().all(|&c| c.length() > 0.0));
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::Lambertian;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::aov::{Aovs, LightPasses};
+use raytracer::renderer::denoise;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 64;
+
+// Two diffuse spheres of different colour on a floor, lit from the side
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let floor = Arc::new(Lambertian::new(Color::new(0.6, 0.6, 0.6)));
+    let red = Arc::new(Lambertian::new(Color::new(0.7, 0.2, 0.2)));
+    let blue = Arc::new(Lambertian::new(Color::new(0.2, 0.3, 0.8)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, floor)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(-0.5, 0.0, -1.0), 0.45, red)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.5, 0.0, -1.0), 0.45, blue)));
+    world.add_light(Light::new(Point3::new(1.0, 2.0, 0.0), Color::new(4.0, 4.0, 4.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 60.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn render(samples_per_pixel: i32, denoise: bool) -> Vec<Color> {
+    let settings = RenderSettings {
+        integrator: IntegratorKind::Path,
+        samples_per_pixel,
+        max_depth: 4,
+        denoise,
+        ..RenderSettings::new(SIZE, SIZE)
+    };
+    renderer().render_pixels(&settings).unwrap()
+}
+
+fn mean_squared_error(image: &[Color], reference: &[Color]) -> f32 {
+    image.iter().zip(reference).map(|(a, b)| (*a - *b).length_squared()).sum::<f32>() / image.len() as f32
+}
+
+#[test]
+fn denoised_low_sample_render_is_closer_to_the_reference() {
+    let reference = render(256, false);
+    let noisy = render(4, false);
+    let denoised = render(4, true);
+
+    let (noisy_error, denoised_error) = (mean_squared_error(&noisy, &reference), mean_squared_error(&denoised, &reference));
+    assert!(denoised_error < noisy_error * 0.5, "denoised {denoised_error} vs noisy {noisy_error}");
+}
+
+// Noisy left and right halves with different albedos
+fn halves(width: usize, height: usize) -> (Vec<Color>, Aovs) {
+    let mut pixels = Vec::new();
+    let mut albedo = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            // Uniform in [-0.2, 0.2)
+            let hash = ((x * 73_856_093) ^ (y * 19_349_663)) as u32;
+            let noise = (hash.wrapping_mul(0x9e37_79b1) >> 8) as f32 / (1 << 24) as f32 * 0.4 - 0.2;
+            let (value, a) = if x < width / 2 { (0.3, 0.3) } else { (0.9, 0.9) };
+            pixels.push(Color::new(value + noise, value + noise, value + noise));
+            albedo.push(Color::new(a, a, a));
+        }
+    }
+
+    let n = width * height;
+    let aovs = Aovs {
+        width,
+        height,
+        passes: vec![LightPasses::default(); n],
+        depth: vec![1.0; n],
+        normal: vec![Vec3::new(0.0, 0.0, 1.0); n],
+        albedo,
+        uv: vec![(0.0, 0.0); n],
+        shadow: vec![1.0; n],
+        object_id: vec![1; n],
+        material_id: vec![1; n],
+        variance: vec![0.0133; n],
+    };
+    (pixels, aovs)
+}
+
+#[test]
+fn noise_is_removed_without_blurring_across_albedo_edges() {
+    let (width, height) = (16, 8);
+    let (pixels, aovs) = halves(width, height);
+    let denoised = denoise::denoise(&pixels, &aovs);
+
+    for y in 0..height {
+        for x in 0..width {
+            let expected = if x < width / 2 { 0.3 } else { 0.9 };
+            let actual = denoised[y * width + x].x();
+            assert!((actual - expected).abs() < 0.08, "pixel ({x}, {y}): {actual} instead of {expected}");
+        }
+    }
+}
+
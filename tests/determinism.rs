@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::sampler::SamplerKind;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Dispersion, Glass, Lambertian, Metal};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::film::FilterKind;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+fn scene() -> Renderer {
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.4)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5).with_dispersion(Dispersion::SF11));
+    let metal = Arc::new(Metal::new(Color::new(0.8, 0.7, 0.6), 0.3));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(-0.5, 0.0, -1.0), 0.5, glass)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.6, 0.0, -1.2), 0.5, metal)));
+    world.add_light(Light::new(Point3::new(1.0, 3.0, 1.0), Color::new(6.0, 6.0, 6.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.3, 1.5), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn render(settings: &RenderSettings, threads: usize) -> Vec<u32> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+    let pixels = pool.install(|| scene().render_pixels(settings).unwrap());
+    pixels.iter().flat_map(|c| [c.x().to_bits(), c.y().to_bits(), c.z().to_bits()]).collect()
+}
+
+fn settings(integrator: IntegratorKind, sampler: SamplerKind, spectral: bool, seed: u64) -> RenderSettings {
+    RenderSettings {
+        samples_per_pixel: 4,
+        max_depth: 6,
+        integrator,
+        sampler,
+        spectral,
+        seed,
+        // Reaches across tile borders, so merging order matters
+        filter: FilterKind::Mitchell,
+        photons: 2_000,
+        photon_passes: 2,
+        ..RenderSettings::new(24, 40)
+    }
+}
+
+#[test]
+fn images_are_bit_identical_across_thread_counts() {
+    let samplers = [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol];
+    let integrators = [IntegratorKind::Classic, IntegratorKind::PhotonMap, IntegratorKind::Path, IntegratorKind::Bdpt];
+
+    for (sampler, integrator) in samplers.into_iter().zip(integrators) {
+        for spectral in [false, true] {
+            let settings = settings(integrator, sampler, spectral, 7);
+            assert_eq!(render(&settings, 1), render(&settings, 4), "{integrator:?} with {sampler:?}, spectral {spectral}");
+        }
+    }
+}
+
+#[test]
+fn seed_changes_the_noise() {
+    let a = render(&settings(IntegratorKind::Path, SamplerKind::Sobol, false, 1), 2);
+    let b = render(&settings(IntegratorKind::Path, SamplerKind::Sobol, false, 2), 2);
+    assert_ne!(a, b);
+}
@@ -0,0 +1,153 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::bail;
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::distributed::{self, Coordinator, Job};
+use raytracer::renderer::film::FilterKind;
+use raytracer::renderer::progress::{CancelToken, NoProgress};
+use raytracer::renderer::renderer::{RenderOutput, Renderer};
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+// The camera circles the scene with the angle, like animation frames
+fn scene(angle: f32) -> Renderer {
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.4)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, glass)));
+    world.add_light(Light::new(Point3::new(1.0, 3.0, 1.0), Color::new(6.0, 6.0, 6.0)));
+
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let from = Point3::new(1.5 * sin, 0.3, -1.0 + 1.5 * cos);
+    let camera = Camera::perspective(from, Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.4);
+    Renderer::new(camera, world)
+}
+
+fn job(angle: f32, seed: u64) -> Job {
+    Job {
+        scene: "spheres".to_string(),
+        model: String::new(),
+        angle,
+        settings: RenderSettings {
+            samples_per_pixel: 2,
+            max_depth: 4,
+            integrator: IntegratorKind::Path,
+            filter: FilterKind::Mitchell,
+            seed,
+            ..RenderSettings::new(70, 50)
+        },
+    }
+}
+
+fn worker(addr: SocketAddr) -> thread::JoinHandle<anyhow::Result<()>> {
+    thread::spawn(move || distributed::run_worker(addr, |job: &Job| Ok(scene(job.angle))))
+}
+
+fn bits(output: &RenderOutput) -> Vec<u32> {
+    output.pixels.iter().flat_map(|c| [c.x().to_bits(), c.y().to_bits(), c.z().to_bits()]).collect()
+}
+
+fn local(job: &Job) -> RenderOutput {
+    scene(job.angle).render_output(&job.settings).unwrap()
+}
+
+#[test]
+fn workers_render_the_same_image_as_one_machine() {
+    let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+    let workers = [worker(coordinator.local_addr().unwrap()), worker(coordinator.local_addr().unwrap())];
+
+    // The same workers stay connected for the next frame
+    for (angle, seed) in [(0.0, 1), (40.0, 2)] {
+        let job = job(angle, seed);
+        let output = coordinator.render(&job, &NoProgress, &CancelToken::new()).unwrap();
+        let expected = local(&job);
+        assert!(!output.cancelled);
+        assert_eq!(output.samples, expected.samples);
+        assert_eq!(bits(&output), bits(&expected), "frame at {angle} degrees");
+    }
+
+    drop(coordinator);
+    for worker in workers {
+        worker.join().unwrap().unwrap();
+    }
+}
+
+// Takes one tile and disconnects without answering
+fn dying_worker(addr: SocketAddr) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let message = |stream: &mut TcpStream| {
+            let mut header = [0u8; 5];
+            stream.read_exact(&mut header).unwrap();
+            let mut payload = vec![0u8; u32::from_le_bytes(header[1..].try_into().unwrap()) as usize];
+            stream.read_exact(&mut payload).unwrap();
+            header[0]
+        };
+        assert_eq!(message(&mut stream), 1);
+        assert_eq!(message(&mut stream), 2);
+    })
+}
+
+#[test]
+fn tiles_of_a_lost_worker_are_rendered_elsewhere() {
+    let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+    let addr = coordinator.local_addr().unwrap();
+    let dying = [dying_worker(addr), dying_worker(addr)];
+    // Joins only once the dying workers took their tiles
+    let healthy = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        distributed::run_worker(addr, |job: &Job| Ok(scene(job.angle)))
+    });
+
+    let job = job(10.0, 3);
+    let output = coordinator.render(&job, &NoProgress, &CancelToken::new()).unwrap();
+    assert_eq!(bits(&output), bits(&local(&job)));
+
+    drop(coordinator);
+    healthy.join().unwrap().unwrap();
+    for worker in dying {
+        worker.join().unwrap();
+    }
+}
+
+#[test]
+fn a_scene_the_workers_cannot_build_fails_the_render() {
+    let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+    let addr = coordinator.local_addr().unwrap();
+    let broken = thread::spawn(move || distributed::run_worker(addr, |job: &Job| bail!("no model {}", job.scene)));
+
+    let result = coordinator.render(&job(0.0, 0), &NoProgress, &CancelToken::new());
+    let message = format!("{:#}", result.err().unwrap());
+    assert!(message.contains("no model spheres"), "{message}");
+    assert!(broken.join().unwrap().is_err());
+
+    // Only what a tile can carry is supported
+    let with_aovs = Job {
+        settings: RenderSettings { aovs: true, ..job(0.0, 0).settings },
+        ..job(0.0, 0)
+    };
+    assert!(coordinator.render(&with_aovs, &NoProgress, &CancelToken::new()).is_err());
+}
+
+#[test]
+fn oversized_messages_are_refused() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let worker = thread::spawn(move || distributed::run_worker(addr, |job: &Job| Ok(scene(job.angle))));
+    // A job header claiming 4 GiB of payload
+    let (mut stream, _) = listener.accept().unwrap();
+    stream.write_all(&[1, 0xff, 0xff, 0xff, 0xff]).unwrap();
+
+    let err = worker.join().unwrap().unwrap_err();
+    assert_eq!(err.downcast_ref::<io::Error>().map(io::Error::kind), Some(io::ErrorKind::InvalidData), "{err:#}");
+}
@@ -0,0 +1,91 @@
+use raytracer::core::color::Color;
+use raytracer::renderer::film::{Film, Filter, FilterKind};
+
+const FILTERS: [FilterKind; 6] = [
+    FilterKind::Box,
+    FilterKind::Tent,
+    FilterKind::Gaussian,
+    FilterKind::Mitchell,
+    FilterKind::Lanczos,
+    FilterKind::BlackmanHarris,
+];
+
+// Samples on a jittered grid of n x n per pixel, splatted tile by tile
+fn splat(film: &mut Film, width: usize, height: usize, n: usize, color: impl Fn(f32, f32) -> Color) {
+    for tile_y in (0..height).step_by(4) {
+        for tile_x in (0..width).step_by(3) {
+            let (columns, rows) = (tile_x..(tile_x + 3).min(width), tile_y..(tile_y + 4).min(height));
+            let mut tile = film.tile(columns.clone(), rows.clone());
+            for j in rows {
+                for i in columns.clone() {
+                    for s in 0..n * n {
+                        let x = i as f32 + ((s % n) as f32 + 0.37) / n as f32;
+                        let y = j as f32 + ((s / n) as f32 + 0.61) / n as f32;
+                        tile.add_sample(x, y, color(x, y));
+                    }
+                }
+            }
+            film.merge(&tile);
+        }
+    }
+}
+
+#[test]
+fn filters_peak_at_the_centre_and_vanish_at_the_radius() {
+    for kind in FILTERS {
+        let filter = Filter::new(kind);
+        let r = filter.radius();
+        assert!(filter.evaluate(0.0, 0.0) > 0.0, "{kind:?}");
+        assert!(filter.evaluate(0.0, 0.0) >= filter.evaluate(0.25, 0.1), "{kind:?}");
+        assert!(filter.evaluate(r + 0.01, 0.0).abs() < 1e-3, "{kind:?}");
+        assert!(filter.evaluate(0.0, -r - 0.01).abs() < 1e-3, "{kind:?}");
+    }
+}
+
+#[test]
+fn constant_images_stay_constant_for_every_filter() {
+    let (width, height) = (10, 9);
+    let c = Color::new(0.25, 0.5, 0.75);
+    for kind in FILTERS {
+        let mut film = Film::new(width, height, Filter::new(kind));
+        splat(&mut film, width, height, 4, |_, _| c);
+        for (k, p) in film.resolve().iter().enumerate() {
+            assert!((*p - c).length() < 1e-4, "{kind:?} pixel {k}: {p:?}");
+        }
+    }
+}
+
+#[test]
+fn box_filter_keeps_samples_in_their_pixel() {
+    let (width, height) = (6, 5);
+    let mut film = Film::new(width, height, Filter::new(FilterKind::Box));
+    // Brightness encodes the pixel a sample was taken in
+    splat(&mut film, width, height, 3, |x, y| Color::new(x.floor(), y.floor(), 0.0));
+
+    let pixels = film.resolve();
+    for row in 0..height {
+        for i in 0..width {
+            let p = pixels[row * width + i];
+            assert_eq!((p.x(), p.y()), (i as f32, (height - 1 - row) as f32));
+        }
+    }
+}
+
+#[test]
+fn wide_filters_blur_a_single_bright_pixel_into_its_neighbours() {
+    let (width, height) = (7, 7);
+    let mut film = Film::new(width, height, Filter::new(FilterKind::Gaussian));
+    splat(&mut film, width, height, 4, |x, y| {
+        if x.floor() == 3.0 && y.floor() == 3.0 {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            Color::ZERO
+        }
+    });
+
+    let pixels = film.resolve();
+    let centre = pixels[3 * width + 3].x();
+    let neighbour = pixels[3 * width + 4].x();
+    assert!(centre > neighbour && neighbour > 0.0, "{centre} {neighbour}");
+    assert_eq!(pixels[0].x(), 0.0);
+}
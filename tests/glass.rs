@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use raytracer::core::color::Color;
+use raytracer::core::medium::{Dispersion, Medium, MediumStack};
+use raytracer::core::ray::Ray;
+use raytracer::core::sampler::IndependentSampler;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::Lambertian;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::integrator::{Integrator, PathIntegrator};
+
+fn assert_close(actual: Color, expected: Color, tolerance: f32) {
+    assert!((actual - expected).length() < tolerance, "{actual} is not close to {expected}");
+}
+
+fn absorbing() -> Medium {
+    let mut medium = Medium::new(1.5);
+    medium.absorption = Color::new(0.5, 1.0, 2.0);
+    medium
+}
+
+#[test]
+fn beer_lambert_transmittance_over_a_known_distance() {
+    let expected = Color::new((-1.0f32).exp(), (-2.0f32).exp(), (-4.0f32).exp());
+    assert_close(absorbing().transmittance(2.0), expected, 1e-6);
+
+    // Distances along a ray count in units of its direction's length
+    let ray = Ray::new(Point3::ZERO, Vec3::new(0.0, 0.0, -4.0)).with_media(MediumStack::new().entered(absorbing()));
+    assert_close(ray.transmittance(0.5), expected, 1e-6);
+    assert_close(Ray::new(Point3::ZERO, Vec3::new(0.0, 0.0, -1.0)).transmittance(10.0), Color::new(1.0, 1.0, 1.0), 1e-6);
+}
+
+#[test]
+fn dispersion_ior_at_known_wavelengths() {
+    let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+    assert!((cauchy.ior(500.0) - 1.54).abs() < 1e-5);
+    assert!((cauchy.ior(1000.0) - 1.51).abs() < 1e-5);
+
+    // Catalogue indices at the Fraunhofer F, d and C lines
+    for (lambda, n) in [(486.13, 1.52238), (587.56, 1.51680), (656.27, 1.51432)] {
+        assert!((Dispersion::BK7.ior(lambda) - n).abs() < 1e-4, "BK7 at {lambda} nm");
+    }
+    assert!((Dispersion::SF11.ior(587.56) - 1.78472).abs() < 1e-4);
+}
+
+// A diffuse floor lit from above, seen by a ray that may start inside a medium
+fn direct_light_on_floor(media: MediumStack) -> Color {
+    let mut world = World::new();
+    let floor = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, floor)));
+    world.add_light(Light::new(Point3::new(0.0, 3.0, 0.0), Color::new(10.0, 10.0, 10.0)));
+    let ray = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0)).with_media(media);
+    // One bounce, only the direct light at the floor
+    PathIntegrator.passes(&ray, &world, 1, &mut IndependentSampler::new(0)).total()
+}
+
+#[test]
+fn paths_ending_on_a_diffuse_surface_inside_a_medium_are_absorbed() {
+    let clear = direct_light_on_floor(MediumStack::new());
+    let inside = direct_light_on_floor(MediumStack::new().entered(absorbing()));
+    assert!(clear.length() > 0.0);
+    let expected = Color::new(clear.x() * (-1.0f32).exp(), clear.y() * (-2.0f32).exp(), clear.z() * (-4.0f32).exp());
+    assert_close(inside, expected, 1e-4);
+}
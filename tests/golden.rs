@@ -0,0 +1,173 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use image::{Rgba, RgbaImage};
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian, Material, TexturedMaterial};
+use raytracer::material::texture::Texture;
+use raytracer::objects::hittable::Hittable;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::triangle::{self, Triangle};
+use raytracer::objects::world::World;
+use raytracer::renderer::compare;
+use raytracer::renderer::image as image_io;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+// Renders are deterministic, the margin only absorbs floating point differences
+// between platforms and compilers
+const MIN_SSIM: f32 = 0.98;
+const SIZE: usize = 64;
+
+// Renders the scene and compares it with tests/golden/{name}.png. A failure
+// leaves the render and a diff image in the target directory. Run with
+// UPDATE_GOLDEN=1 to write the references after an intended change.
+fn check(name: &str, renderer: Renderer, settings: RenderSettings) {
+    let image = renderer.render(&settings).unwrap();
+    let reference = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{name}.png"));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save_png(&reference, &settings).unwrap();
+        return;
+    }
+    assert!(reference.exists(), "No reference {}, create it with UPDATE_GOLDEN=1", reference.display());
+
+    let (width, height, expected) = image_io::read_display(&reference).unwrap();
+    assert_eq!((width, height), (image.width, image.height), "{name} changed its size");
+    // Through the PNG, so both sides are quantized the same way
+    let actual = decode(&image.to_png(&settings).unwrap());
+    let similarity = compare::ssim(width, height, &expected, &actual);
+    if similarity.ssim < MIN_SSIM {
+        let out = failures_dir();
+        image.save_png(&out.join(format!("{name}.png")), &settings).unwrap();
+        image_io::write_png(&out.join(format!("{name}.diff.png")), width, height, &similarity.diff_image()).unwrap();
+        panic!("{name} differs from its reference, SSIM {:.4} < {MIN_SSIM}, see {}", similarity.ssim, out.display());
+    }
+}
+
+fn decode(png: &[u8]) -> Vec<Color> {
+    let image = image::load_from_memory(png).unwrap().to_rgb8();
+    image.pixels().map(|rgb| rgb.0.map(|v| (v as f32 + 0.5) / 256.0)).map(|[r, g, b]| Color::new(r, g, b)).collect()
+}
+
+fn failures_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn add_quad(world: &mut World, p: [Point3; 4], mat: Arc<dyn Material>) {
+    let (a, b) = Triangle::make_quad(p[0], p[1], p[2], p[3], mat);
+    world.add_object([Box::new(a) as Box<dyn Hittable>, Box::new(b)]);
+}
+
+fn camera(lookfrom: Point3, lookat: Point3, vfov: f32) -> Camera {
+    Camera::perspective(lookfrom, lookat, Vec3::new(0.0, 1.0, 0.0), vfov, 1.0)
+}
+
+fn settings(integrator: IntegratorKind, samples_per_pixel: i32) -> RenderSettings {
+    RenderSettings {
+        integrator,
+        samples_per_pixel,
+        seed: 1,
+        ..RenderSettings::new(SIZE, SIZE)
+    }
+}
+
+// Red and green side walls, two boxes, lit from below the ceiling
+fn cornell_box() -> Renderer {
+    let mut world = World::new();
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+    let p = |x: f32, y: f32, z: f32| Point3::new(x, y, z);
+    add_quad(&mut world, [p(-1.0, -1.0, -1.0), p(1.0, -1.0, -1.0), p(1.0, -1.0, 1.0), p(-1.0, -1.0, 1.0)], white.clone());
+    add_quad(&mut world, [p(-1.0, 1.0, -1.0), p(1.0, 1.0, -1.0), p(1.0, 1.0, 1.0), p(-1.0, 1.0, 1.0)], white.clone());
+    add_quad(&mut world, [p(-1.0, -1.0, -1.0), p(1.0, -1.0, -1.0), p(1.0, 1.0, -1.0), p(-1.0, 1.0, -1.0)], white.clone());
+    add_quad(&mut world, [p(-1.0, -1.0, -1.0), p(-1.0, -1.0, 1.0), p(-1.0, 1.0, 1.0), p(-1.0, 1.0, -1.0)], red);
+    add_quad(&mut world, [p(1.0, -1.0, -1.0), p(1.0, -1.0, 1.0), p(1.0, 1.0, 1.0), p(1.0, 1.0, -1.0)], green);
+    let tall = triangle::cube(p(-0.35, -0.4, -0.35), 0.6, Vec3::new(0.0, 0.3, 0.0), white.clone());
+    let short = triangle::cube(p(0.4, -0.7, 0.3), 0.6, Vec3::new(0.0, -0.3, 0.0), white);
+    for cube in [tall, short] {
+        world.add_object(cube.into_iter().map(|tri| Box::new(tri) as Box<dyn Hittable>));
+    }
+    world.add_light(Light::new(p(0.0, 0.9, 0.0), Color::new(1.5, 1.5, 1.5)));
+    Renderer::new(camera(p(0.0, 0.0, 3.4), p(0.0, 0.0, 0.0), 40.0), world)
+}
+
+fn glass_sphere() -> Renderer {
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.4)));
+    let back = Arc::new(Lambertian::new(Color::new(0.6, 0.3, 0.2)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.7, 0.0, -2.5), 0.5, back)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, glass)));
+    world.add_light(Light::new(Point3::new(1.0, 3.0, 1.0), Color::new(6.0, 6.0, 6.0)));
+    Renderer::new(camera(Point3::new(0.0, 0.3, 1.0), Point3::new(0.0, 0.0, -1.0), 50.0), world)
+}
+
+// A checkerboard on every face of a cube, through its texture coordinates
+fn textured_cube() -> Renderer {
+    let checker = RgbaImage::from_fn(8, 8, |x, y| {
+        if (x + y) % 2 == 0 { Rgba([230, 200, 40, 255]) } else { Rgba([30, 60, 160, 255]) }
+    });
+    let textured: Arc<dyn Material> = Arc::new(TexturedMaterial::new(Arc::new(Texture::new(checker))));
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, 0.0), 100.0, ground)));
+
+    let p = |x: f32, y: f32, z: f32| Point3::new(x, y, z) * 0.5;
+    let faces = [
+        [p(-1.0, -1.0, 1.0), p(1.0, -1.0, 1.0), p(1.0, 1.0, 1.0), p(-1.0, 1.0, 1.0)],
+        [p(1.0, -1.0, 1.0), p(1.0, -1.0, -1.0), p(1.0, 1.0, -1.0), p(1.0, 1.0, 1.0)],
+        [p(-1.0, 1.0, 1.0), p(1.0, 1.0, 1.0), p(1.0, 1.0, -1.0), p(-1.0, 1.0, -1.0)],
+        [p(-1.0, -1.0, -1.0), p(-1.0, -1.0, 1.0), p(-1.0, 1.0, 1.0), p(-1.0, 1.0, -1.0)],
+    ];
+    let uv = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    let mut triangles = Vec::new();
+    for [a, b, c, d] in faces {
+        triangles.push(Triangle::new(a, b, c, uv[0], uv[1], uv[2], textured.clone()));
+        triangles.push(Triangle::new(a, c, d, uv[0], uv[2], uv[3], textured.clone()));
+    }
+    world.add_object(triangles.into_iter().map(|tri| Box::new(tri) as Box<dyn Hittable>));
+    world.add_light(Light::new(Point3::new(2.0, 3.0, 3.0), Color::new(12.0, 12.0, 12.0)));
+    Renderer::new(camera(Point3::new(1.6, 1.3, 2.2), Point3::new(0.0, 0.0, 0.0), 40.0), world)
+}
+
+#[test]
+fn cornell_box_matches_its_reference() {
+    check("cornell_box", cornell_box(), settings(IntegratorKind::Path, 32));
+}
+
+#[test]
+fn glass_sphere_matches_its_reference() {
+    check("glass_sphere", glass_sphere(), settings(IntegratorKind::Classic, 16));
+}
+
+#[test]
+fn textured_cube_matches_its_reference() {
+    check("textured_cube", textured_cube(), settings(IntegratorKind::Classic, 8));
+}
+
+#[test]
+fn ssim_tells_similar_images_from_changed_ones() {
+    let settings = settings(IntegratorKind::Classic, 4);
+    let render = |renderer: Renderer| renderer.render(&settings).unwrap().display(&settings);
+    let base = render(glass_sphere());
+    assert_eq!(compare::ssim(SIZE, SIZE, &base, &base).ssim, 1.0);
+
+    let brighter: Vec<Color> = base.iter().map(|&c| c * 1.02).collect();
+    assert!(compare::ssim(SIZE, SIZE, &base, &brighter).ssim > MIN_SSIM);
+
+    // The same scene with the light moved to the other side
+    let mut moved = glass_sphere();
+    moved.world.lights[0] = Light::new(Point3::new(-2.0, 1.0, 1.0), Color::new(6.0, 6.0, 6.0));
+    let similarity = compare::ssim(SIZE, SIZE, &base, &render(moved));
+    assert!(similarity.ssim < MIN_SSIM, "SSIM {}", similarity.ssim);
+    assert!(similarity.diff_image().iter().any(|c| c.x() > 0.5));
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::Lambertian;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::image::Image;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::RenderSettings;
+
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let red = Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.2)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, red)));
+    world.add_light(Light::new(Point3::new(1.0, 2.0, 0.0), Color::new(4.0, 4.0, 4.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 60.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn settings() -> RenderSettings {
+    RenderSettings {
+        samples_per_pixel: 2,
+        ..RenderSettings::new(12, 8)
+    }
+}
+
+#[test]
+fn renders_into_a_float_rgba_buffer() {
+    let renderer = renderer();
+    let image = renderer.render(&settings()).unwrap();
+    assert_eq!((image.width, image.height, image.pixels.len()), (12, 8, 96));
+    assert!(image.pixels.iter().all(|p| p[3] == 1.0));
+
+    let pixels = renderer.render_pixels(&settings()).unwrap();
+    for (p, c) in image.pixels.iter().zip(&pixels) {
+        assert_eq!(p[..3], [c.x(), c.y(), c.z()]);
+    }
+    // The sphere fills the centre and is red
+    let [r, g, _, _] = image.pixel(6, 4);
+    assert!(r > g, "{r} {g}");
+}
+
+#[test]
+fn invalid_settings_are_errors() {
+    assert!(renderer().render(&RenderSettings::new(1, 8)).is_err());
+    let no_samples = RenderSettings {
+        samples_per_pixel: 0,
+        ..settings()
+    };
+    assert!(renderer().render(&no_samples).is_err());
+    assert!(renderer().render_pixels(&no_samples).is_err());
+    assert!(renderer().render_output(&no_samples).is_err());
+    assert!(renderer().render_with_sample_counts(&no_samples).is_err());
+}
+
+#[test]
+fn saves_every_format_to_the_given_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = settings();
+    let image = renderer().render(&settings).unwrap();
+
+    let ppm = dir.path().join("out.ppm");
+    image.save(&ppm, &settings).unwrap();
+    let text = fs::read_to_string(&ppm).unwrap();
+    assert!(text.starts_with("P3\n12 8\n255\n"));
+    assert_eq!(text.lines().count(), 3 + 96);
+
+    let png = dir.path().join("out.png");
+    image.save(&png, &settings).unwrap();
+    let decoded = image::open(&png).unwrap().to_rgb8();
+    assert_eq!(decoded.dimensions(), (12, 8));
+    // Same 8-bit values as the PPM
+    let first: Vec<u8> = text.lines().nth(3).unwrap().split(' ').map(|v| v.parse().unwrap()).collect();
+    assert_eq!(decoded.get_pixel(0, 0).0.to_vec(), first);
+
+    let exr = dir.path().join("out.exr");
+    image.save(&exr, &settings).unwrap();
+    let read = exr::prelude::read_first_rgba_layer_from_file(
+        &exr,
+        |resolution, _| vec![[0.0f32; 4]; resolution.width() * resolution.height()],
+        |pixels, position, (r, g, b, a): (f32, f32, f32, f32)| pixels[position.y() * 12 + position.x()] = [r, g, b, a],
+    )
+    .unwrap();
+    assert_eq!(read.layer_data.channel_data.pixels, image.pixels);
+
+    // No temporary files are left behind
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+}
+
+#[test]
+fn save_failures_are_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let image = Image::new(2, 2, &[Color::new(0.5, 0.5, 0.5); 4]);
+    let settings = RenderSettings::new(2, 2);
+
+    assert!(image.save(&dir.path().join("out.bmp"), &settings).is_err());
+    assert!(image.save(&dir.path().join("missing").join("out.ppm"), &settings).is_err());
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+}
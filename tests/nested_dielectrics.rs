@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use raytracer::core::color::Color;
+use raytracer::core::ray::Ray;
+use raytracer::core::sampler::IndependentSampler;
+use raytracer::core::vec3::{self, Point3, Vec3};
+use raytracer::material::material::Glass;
+use raytracer::objects::hittable::HitRecord;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::integrator::{ClassicIntegrator, Integrator};
+
+const EPS: f32 = 1.0e-4;
+
+struct Crossing {
+    ior_before: f32,
+    ior_after: f32,
+    passed_through: bool,
+    incoming: Vec3,
+    outgoing: Vec3,
+    normal: Vec3,
+}
+
+// Follows a ray through the world, retrying Fresnel reflections so that only
+// the transmitted branch is recorded at every surface.
+fn trace_transmitted(world: &World, mut ray: Ray) -> Vec<Crossing> {
+    let mut crossings = Vec::new();
+    let mut rec = HitRecord::new();
+
+    while world.hit(&ray, 0.001, f32::INFINITY, &mut rec) {
+        let mat = rec.mat.clone().unwrap();
+        let passed_through = mat.passes_through(&ray, &rec);
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+
+        loop {
+            assert!(mat.scatter(&ray, &rec, &mut attenuation, &mut scattered, &mut IndependentSampler::new(0)));
+            if vec3::dot(scattered.direction(), rec.normal) < 0.0 {
+                break;
+            }
+        }
+
+        crossings.push(Crossing {
+            ior_before: ray.current_ior(),
+            ior_after: scattered.current_ior(),
+            passed_through,
+            incoming: vec3::unit_vector(ray.direction()),
+            outgoing: vec3::unit_vector(scattered.direction()),
+            normal: rec.normal,
+        });
+        ray = scattered;
+    }
+
+    crossings
+}
+
+fn assert_ior(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < EPS, "ior {actual} != {expected}");
+}
+
+#[test]
+fn air_bubble_in_glass() {
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    let air = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.0).with_priority(1));
+
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 2.0, glass)));
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 1.0, air)));
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let crossings = trace_transmitted(&world, ray);
+
+    let expected = [(1.0, 1.5), (1.5, 1.0), (1.0, 1.5), (1.5, 1.0)];
+    assert_eq!(crossings.len(), expected.len());
+    for (crossing, (before, after)) in crossings.iter().zip(expected) {
+        assert!(!crossing.passed_through);
+        assert_ior(crossing.ior_before, before);
+        assert_ior(crossing.ior_after, after);
+    }
+}
+
+#[test]
+fn water_in_glass_overlap_is_resolved_by_priority() {
+    // Hollow glass shell (negative radius flips the inner wall's normals);
+    // the water slightly overlaps the inner wall so there is no air gap
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5).with_priority(2));
+    let water = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.33).with_priority(1));
+
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 2.0, glass.clone())));
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, -1.5, glass)));
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 1.6, water)));
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+    let crossings = trace_transmitted(&world, ray);
+
+    // outer glass, water inside glass wall, inner glass wall, ... mirrored
+    let expected = [
+        (1.0, 1.5, false),
+        (1.5, 1.5, true),
+        (1.5, 1.33, false),
+        (1.33, 1.5, false),
+        (1.5, 1.5, true),
+        (1.5, 1.0, false),
+    ];
+    assert_eq!(crossings.len(), expected.len());
+    for (crossing, (before, after, passed_through)) in crossings.iter().zip(expected) {
+        assert_eq!(crossing.passed_through, passed_through);
+        assert_ior(crossing.ior_before, before);
+        assert_ior(crossing.ior_after, after);
+    }
+}
+
+#[test]
+fn refraction_into_water_uses_the_enclosing_glass_ior() {
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5).with_priority(2));
+    let water = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.33).with_priority(1));
+
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 2.0, glass.clone())));
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, -1.5, glass)));
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 1.6, water)));
+
+    // Off-axis ray so every real boundary bends it
+    let origin = Point3::new(0.0, 0.7, -5.0);
+    let ray = Ray::new(origin, Vec3::new(0.0, 0.0, 1.0));
+    let crossings = trace_transmitted(&world, ray);
+    assert!(crossings.len() >= 3);
+
+    // Snell's law at the glass to water boundary: n1 sin θ1 = n2 sin θ2
+    let crossing = crossings
+        .iter()
+        .find(|c| !c.passed_through && (c.ior_after - 1.33).abs() < EPS)
+        .expect("ray never entered the water");
+    assert_ior(crossing.ior_before, 1.5);
+    let sin_in = vec3::cross(crossing.incoming, crossing.normal).length();
+    let sin_out = vec3::cross(crossing.outgoing, crossing.normal).length();
+    assert!(sin_in > 0.1);
+    assert!((1.5 * sin_in - 1.33 * sin_out).abs() < 1.0e-3);
+}
+
+#[test]
+fn crossings_of_overridden_boundaries_count_against_the_depth() {
+    // Thousands of water drops in a row inside higher priority glass, the ray
+    // passes straight through all of them
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5).with_priority(2));
+    let water = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.33).with_priority(1));
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::ZERO, 150.0, glass)));
+    for i in 0..20_000 {
+        let center = Point3::new(0.0, 0.0, i as f32 * 0.01 - 100.0);
+        world.add_hittable(Box::new(Sphere::new(center, 0.004, water.clone())));
+    }
+
+    let ray = Ray::new(Point3::new(0.0, 0.0, -200.0), Vec3::new(0.0, 0.0, 1.0));
+    let passes = ClassicIntegrator::new().passes(&ray, &world, 8, &mut IndependentSampler::new(0));
+    assert!(passes.total().length().is_finite());
+}
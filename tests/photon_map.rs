@@ -0,0 +1,87 @@
+use std::sync::Arc;
+
+use raytracer::core::color::Color;
+use raytracer::core::sampler::{IndependentSampler, Sampler};
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian, Material};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::photon_map::{Caustics, Photon, PhotonMap};
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+#[test]
+fn kd_tree_radius_queries_match_a_brute_force_scan() {
+    let mut sampler = IndependentSampler::new(3);
+    sampler.start_pixel_sample(0, 0, 0);
+    let mut point = || {
+        let (x, y) = sampler.get_2d();
+        Point3::new(x * 4.0 - 2.0, y * 4.0 - 2.0, sampler.get_1d() * 4.0 - 2.0)
+    };
+    // The power's red channel numbers the photons
+    let photons: Vec<Photon> = (0..2000)
+        .map(|i| Photon { position: point(), direction: Vec3::new(0.0, -1.0, 0.0), power: Color::new(i as f32, 0.0, 0.0) })
+        .collect();
+    let map = PhotonMap::new(photons.clone());
+    assert_eq!(map.len(), photons.len());
+
+    for _ in 0..50 {
+        let p = point();
+        for radius in [0.05, 0.3, 1.0, 5.0] {
+            let mut found = Vec::new();
+            map.for_each_within(p, radius, |photon| found.push(photon.power.x() as usize));
+            found.sort_unstable();
+            let expected: Vec<usize> =
+                (0..photons.len()).filter(|&i| (photons[i].position - p).length_squared() <= radius * radius).collect();
+            assert_eq!(found, expected, "within {radius} of {p}");
+        }
+    }
+}
+
+// A sphere above a white floor with the light straight above. A glass ball
+// focuses the light on the floor right below its centre.
+fn caustics_under(ball: Arc<dyn Material>, pass: usize) -> Caustics {
+    let floor = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, floor)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.5, ball)));
+    world.add_light(Light::new(Point3::new(0.0, 4.0, 0.0), Color::new(20.0, 20.0, 20.0)));
+    let settings = RenderSettings { photons: 20_000, ..RenderSettings::new(16, 16) };
+    Caustics::build(&world, &settings, pass)
+}
+
+#[test]
+fn glass_focuses_caustic_photons_and_opaque_spheres_cast_none() {
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    let below = Point3::new(0.0, 0.0, 0.0);
+    let glass = caustics_under(Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5)), 0);
+    // Brighter than the direct light would be without the ball, 20 / 4²
+    let focused = glass.irradiance(below, up);
+    assert!(focused.x() > 20.0 / 16.0, "{focused}");
+    // Nothing lands beside the caustic or arrives from below
+    assert_eq!(glass.irradiance(Point3::new(2.0, 0.0, 0.0), up).x(), 0.0);
+    assert_eq!(glass.irradiance(below, -up).x(), 0.0);
+
+    let opaque = caustics_under(Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8))), 0);
+    assert_eq!(opaque.irradiance(below, up).x(), 0.0);
+}
+
+#[test]
+fn passes_shrink_the_radius_and_share_the_samples() {
+    let glass: Arc<dyn Material> = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    let radii: Vec<f32> = (0..4).map(|pass| caustics_under(glass.clone(), pass).radius()).collect();
+    assert_eq!(radii[0], RenderSettings::default().photon_radius);
+    assert!(radii.windows(2).all(|w| w[1] < w[0]), "{radii:?}");
+
+    let settings = RenderSettings {
+        integrator: IntegratorKind::PhotonMap,
+        samples_per_pixel: 10,
+        photon_passes: 4,
+        ..RenderSettings::new(16, 16)
+    };
+    assert_eq!(settings.render_passes(), 4);
+    assert_eq!((0..4).map(|pass| settings.pass_budget(pass)).collect::<Vec<_>>(), [3, 5, 8, 10]);
+    // Never more passes than samples, other integrators take a single pass
+    assert_eq!(RenderSettings { samples_per_pixel: 2, ..settings.clone() }.render_passes(), 2);
+    assert_eq!(RenderSettings { integrator: IntegratorKind::Path, ..settings }.render_passes(), 1);
+}
@@ -0,0 +1,113 @@
+use raytracer::core::color::Color;
+use raytracer::renderer::postprocess::{self, OutputTransform, ToneMapper};
+use raytracer::renderer::settings::RenderSettings;
+
+const MAPPERS: [ToneMapper; 6] = [
+    ToneMapper::Clamp,
+    ToneMapper::Reinhard,
+    ToneMapper::Filmic,
+    ToneMapper::Aces,
+    ToneMapper::AgX,
+    ToneMapper::PbrNeutral,
+];
+
+fn grey(v: f32) -> Color {
+    Color::new(v, v, v)
+}
+
+fn single_pixel(c: Color, settings: RenderSettings) -> Color {
+    postprocess::apply(&[c], &RenderSettings { width: 1, height: 1, ..settings })[0]
+}
+
+#[test]
+fn srgb_oetf_matches_reference_values() {
+    let encode = |x: f32| postprocess::encode(OutputTransform::Srgb, grey(x)).x();
+    assert_eq!(encode(0.0), 0.0);
+    assert!((encode(1.0) - 1.0).abs() < 1e-6);
+    assert!((encode(0.18) - 0.461_356).abs() < 1e-4);
+    assert!((encode(0.002) - 0.025_84).abs() < 1e-6);
+    // Both pieces meet at the break point
+    assert!((encode(0.003_130_8) - encode(0.003_130_9)).abs() < 1e-5);
+}
+
+#[test]
+fn tone_mappers_are_monotonic_and_keep_grey_neutral() {
+    for mapper in MAPPERS {
+        let mut previous = -1.0;
+        for i in 0..=200 {
+            let x = i as f32 * 0.1;
+            let c = postprocess::tone_map(mapper, grey(x));
+            assert!((c.x() - c.y()).abs() < 2e-3 && (c.y() - c.z()).abs() < 2e-3, "{mapper:?} tints grey {x}: {c}");
+            let y = c.y().clamp(0.0, 1.0);
+            assert!(y >= previous - 1e-6, "{mapper:?} decreases at {x}");
+            previous = y;
+        }
+    }
+}
+
+#[test]
+fn only_clamping_clips_bright_highlights() {
+    for mapper in [ToneMapper::Reinhard, ToneMapper::Filmic, ToneMapper::AgX, ToneMapper::PbrNeutral] {
+        let at_one = postprocess::tone_map(mapper, grey(1.0)).y();
+        // Filmic reaches white at 5.6
+        let at_five = postprocess::tone_map(mapper, grey(5.0)).y();
+        assert!(at_five > at_one && at_five <= 1.0, "{mapper:?}: {at_one} and {at_five}");
+    }
+    let clamped = |x: f32| single_pixel(grey(x), RenderSettings::default());
+    assert_eq!(clamped(1.0).y(), clamped(15.0).y());
+}
+
+#[test]
+fn exposure_scales_in_stops_before_the_output_transform() {
+    let linear = RenderSettings {
+        output_transform: OutputTransform::Linear,
+        ..RenderSettings::default()
+    };
+    let base = single_pixel(grey(0.1), linear.clone());
+    let brighter = single_pixel(grey(0.1), RenderSettings { exposure: 1.0, ..linear.clone() });
+    let darker = single_pixel(grey(0.1), RenderSettings { exposure: -2.0, ..linear });
+    assert!((base.x() - 0.1).abs() < 1e-6);
+    assert!((brighter.x() - 0.2).abs() < 1e-6);
+    assert!((darker.x() - 0.025).abs() < 1e-6);
+}
+
+#[test]
+fn white_balance_neutralises_the_chosen_temperature() {
+    let linear = RenderSettings {
+        output_transform: OutputTransform::Linear,
+        ..RenderSettings::default()
+    };
+    let neutral = single_pixel(grey(0.5), linear.clone());
+    assert_eq!((neutral.x(), neutral.y(), neutral.z()), (0.5, 0.5, 0.5));
+
+    // Balancing for tungsten light cools the picture, for shade warms it
+    let tungsten = single_pixel(grey(0.5), RenderSettings { white_balance: 3200.0, ..linear.clone() });
+    assert!(tungsten.z() > tungsten.x(), "{tungsten}");
+    let shade = single_pixel(grey(0.5), RenderSettings { white_balance: 10000.0, ..linear });
+    assert!(shade.x() > shade.z(), "{shade}");
+}
+
+#[test]
+fn bloom_spreads_highlights_and_vignette_darkens_corners() {
+    let (width, height) = (21, 21);
+    let settings = RenderSettings {
+        output_transform: OutputTransform::Linear,
+        ..RenderSettings::new(width, height)
+    };
+
+    let mut pixels = vec![grey(0.1); width * height];
+    pixels[10 * width + 10] = grey(50.0);
+    let plain = postprocess::apply(&pixels, &settings);
+    let bloomed = postprocess::apply(&pixels, &RenderSettings { bloom: 1.0, ..settings.clone() });
+    let neighbour = 10 * width + 12;
+    assert_eq!(plain[neighbour].x(), 0.1);
+    assert!(bloomed[neighbour].x() > 0.1);
+    // A dim image has nothing to bloom
+    let dim = vec![grey(0.5); width * height];
+    assert!(postprocess::apply(&dim, &RenderSettings { bloom: 1.0, ..settings.clone() }).iter().all(|c| c.x() == 0.5));
+
+    let vignetted = postprocess::apply(&dim, &RenderSettings { vignette: 0.5, ..settings });
+    let centre = vignetted[10 * width + 10].x();
+    assert!((centre - 0.5).abs() < 1e-6);
+    assert!(vignetted[0].x() < 0.3 && vignetted[0].x() > 0.2);
+}
@@ -0,0 +1,68 @@
+use std::sync::{Arc, Mutex};
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::Lambertian;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::progress::{CancelToken, ProgressReport};
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::RenderSettings;
+
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let grey = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, grey.clone())));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, grey)));
+    world.add_light(Light::new(Point3::new(1.0, 2.0, 0.0), Color::new(4.0, 4.0, 4.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 60.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn settings() -> RenderSettings {
+    RenderSettings {
+        samples_per_pixel: 2,
+        // Three columns and two rows of tiles, the last ones cut short
+        ..RenderSettings::new(80, 40)
+    }
+}
+
+#[test]
+fn progress_counts_up_to_every_tile_and_pixel() {
+    let reports = Mutex::new(Vec::new());
+    let record = |report: &ProgressReport| reports.lock().unwrap().push(*report);
+    let output = renderer().render_with(&settings(), &record, &CancelToken::new()).unwrap();
+    assert!(!output.cancelled);
+
+    let reports = reports.into_inner().unwrap();
+    assert_eq!(reports.len(), 6);
+    for (k, report) in reports.iter().enumerate() {
+        assert_eq!(report.tiles_done, k + 1);
+        assert_eq!(report.tiles_total, 6);
+        assert!(report.eta().is_some());
+    }
+    assert!(reports.windows(2).all(|w| w[0].pixels_done < w[1].pixels_done && w[0].elapsed <= w[1].elapsed));
+
+    let last = reports.last().unwrap();
+    assert_eq!(last.pixels_done, 80 * 40);
+    assert_eq!(last.fraction(), 1.0);
+    assert_eq!(last.eta(), Some(std::time::Duration::ZERO));
+}
+
+#[test]
+fn cancelling_stops_before_the_remaining_tiles() {
+    let cancel = CancelToken::new();
+    let stop_after_first = |_: &ProgressReport| cancel.cancel();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let output = pool.install(|| renderer().render_with(&settings(), &stop_after_first, &cancel)).unwrap();
+
+    assert!(output.cancelled);
+    // Only the first tile, the top left one, was rendered
+    let rendered = output.samples.iter().filter(|&&n| n > 0).count();
+    assert_eq!(rendered, 32 * 32);
+    assert!(output.samples[0] > 0 && output.samples[79] == 0);
+    assert_eq!(output.pixels[80 * 40 - 1].length(), 0.0);
+}
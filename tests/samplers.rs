@@ -0,0 +1,82 @@
+use raytracer::core::sampler::{self, SamplerKind};
+
+const SAMPLES: usize = 16;
+const PIXELS: usize = 256;
+
+// Integrand with a known mean over the unit square and a soft edge, roughly
+// what a pixel filter sees across a shading boundary
+fn f(x: f32, y: f32) -> f32 {
+    let edge = if x + 0.5 * y < 0.7 { 1.0 } else { 0.2 };
+    edge * (1.0 + (3.0 * y).sin())
+}
+
+fn reference() -> f64 {
+    let n = 2000;
+    let mut sum = 0.0;
+    for j in 0..n {
+        for i in 0..n {
+            sum += f((i as f32 + 0.5) / n as f32, (j as f32 + 0.5) / n as f32) as f64;
+        }
+    }
+    sum / (n * n) as f64
+}
+
+// Root mean square error of per-pixel estimates of f, using the dimensions a
+// path would consume after a few bounces
+fn rms_error(kind: SamplerKind, expected: f64) -> f64 {
+    let mut sampler = sampler::create(kind, SAMPLES, 0);
+    let mut squared = 0.0;
+    for pixel in 0..PIXELS {
+        let mut sum = 0.0;
+        for index in 0..SAMPLES {
+            sampler.start_pixel_sample(pixel % 16, pixel / 16, index);
+            for _ in 0..3 {
+                sampler.get_2d();
+                sampler.get_1d();
+            }
+            let (x, y) = sampler.get_2d();
+            assert!((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y));
+            sum += f(x, y) as f64;
+        }
+        squared += (sum / SAMPLES as f64 - expected).powi(2);
+    }
+    (squared / PIXELS as f64).sqrt()
+}
+
+#[test]
+fn low_discrepancy_samplers_beat_independent_sampling() {
+    let expected = reference();
+    let independent = rms_error(SamplerKind::Independent, expected);
+    for kind in [SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+        let error = rms_error(kind, expected);
+        assert!(error < 0.9 * independent, "{kind:?}: {error} vs independent {independent}");
+    }
+}
+
+#[test]
+fn stratified_sampler_covers_every_stratum() {
+    let mut sampler = sampler::create(SamplerKind::Stratified, SAMPLES, 0);
+    let mut strata = [false; SAMPLES];
+    for index in 0..SAMPLES {
+        sampler.start_pixel_sample(3, 5, index);
+        strata[(sampler.get_1d() * SAMPLES as f32) as usize] = true;
+    }
+    assert!(strata.iter().all(|&hit| hit));
+}
+
+#[test]
+fn stratified_rounds_cover_every_stratum_on_their_own() {
+    let rounds = [4, 8, 16, 20];
+    let mut sampler = sampler::create_in_rounds(SamplerKind::Stratified, &rounds, 0);
+    let mut start = 0;
+    for end in rounds {
+        let n = end - start;
+        let mut strata = vec![false; n];
+        for index in start..end {
+            sampler.start_pixel_sample(3, 5, index);
+            strata[(sampler.get_1d() * n as f32) as usize] = true;
+        }
+        assert!(strata.iter().all(|&hit| hit), "round ending at {end}");
+        start = end;
+    }
+}
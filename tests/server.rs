@@ -0,0 +1,125 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian};
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::image::Image;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::server::{self, JobRequest, JobState, JobStatus};
+use raytracer::renderer::settings::RenderSettings;
+use reqwest::StatusCode;
+use serde_json::json;
+
+fn renderer() -> Renderer {
+    let mut world = World::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.6, 0.4)));
+    let glass = Arc::new(Glass::new(Color::new(1.0, 1.0, 1.0), 1.5));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, glass)));
+    world.add_light(Light::new(Point3::new(1.0, 3.0, 1.0), Color::new(6.0, 6.0, 6.0)));
+
+    let camera = Camera::perspective(Point3::new(0.0, 0.3, 1.5), Point3::new(0.0, 0.0, -1.0), Vec3::new(0.0, 1.0, 0.0), 50.0, 1.4);
+    Renderer::new(camera, world)
+}
+
+// Serves on a free port, only the model "spheres" exists
+async fn start() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(server::serve(listener, |request: &JobRequest| match request.model.as_deref() {
+        Some("spheres") => Ok(renderer()),
+        model => bail!("Model {model:?} not found"),
+    }));
+    url
+}
+
+async fn submit(client: &reqwest::Client, url: &str, job: serde_json::Value) -> JobStatus {
+    let response = client.post(format!("{url}/jobs")).json(&job).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json().await.unwrap()
+}
+
+async fn wait_for(client: &reqwest::Client, url: &str, id: u64, done: impl Fn(&JobStatus) -> bool) -> JobStatus {
+    loop {
+        let status: JobStatus = client.get(format!("{url}/jobs/{id}")).send().await.unwrap().json().await.unwrap();
+        if done(&status) {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn finished_jobs_serve_the_same_image_as_a_local_render() {
+    let (url, client) = (start().await, reqwest::Client::new());
+    let job = json!({ "model": "spheres", "settings": { "width": 40, "height": 30, "samples_per_pixel": 2, "integrator": "Path" } });
+    let status = submit(&client, &url, job.clone()).await;
+    let status = wait_for(&client, &url, status.id, |s| s.state == JobState::Done).await;
+    assert_eq!(status.progress, 1.0);
+    assert_eq!(status.tiles_done, status.tiles_total);
+
+    let request: JobRequest = serde_json::from_value(job).unwrap();
+    let settings = &request.settings;
+    let local = Image::new(settings.width, settings.height, &renderer().render_output(settings).unwrap().pixels);
+    let exr = client.get(format!("{url}/jobs/{}/image.exr", status.id)).send().await.unwrap();
+    assert_eq!(exr.headers()["content-type"], "image/x-exr");
+    assert_eq!(exr.bytes().await.unwrap(), local.to_exr().unwrap());
+
+    let png = client.get(format!("{url}/jobs/{}/image.png", status.id)).send().await.unwrap().bytes().await.unwrap();
+    let png = image::load_from_memory(&png).unwrap();
+    assert_eq!((png.width(), png.height()), (40, 30));
+
+    let jobs: Vec<JobStatus> = client.get(format!("{url}/jobs")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(jobs.len(), 1);
+    // Deleting a finished job forgets it
+    client.delete(format!("{url}/jobs/{}", status.id)).send().await.unwrap();
+    let response = client.get(format!("{url}/jobs/{}", status.id)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn running_jobs_show_progress_and_can_be_cancelled() {
+    let (url, client) = (start().await, reqwest::Client::new());
+    let slow = json!({ "model": "spheres", "settings": { "width": 256, "height": 256, "samples_per_pixel": 512, "integrator": "Path" } });
+    let running = submit(&client, &url, slow.clone()).await;
+    let queued = submit(&client, &url, slow).await;
+
+    wait_for(&client, &url, running.id, |s| s.tiles_done > 0).await;
+    let preview = client.get(format!("{url}/jobs/{}/preview.png", running.id)).send().await.unwrap();
+    assert_eq!(preview.status(), StatusCode::OK);
+    let image = client.get(format!("{url}/jobs/{}/image.png", running.id)).send().await.unwrap();
+    assert_eq!(image.status(), StatusCode::CONFLICT);
+    let status: JobStatus = client.get(format!("{url}/jobs/{}", queued.id)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(status.state, JobState::Queued);
+
+    for id in [queued.id, running.id] {
+        client.delete(format!("{url}/jobs/{id}")).send().await.unwrap();
+        let status = wait_for(&client, &url, id, |s| s.state != JobState::Queued && s.state != JobState::Rendering).await;
+        assert_eq!(status.state, JobState::Cancelled);
+    }
+    assert!(wait_for(&client, &url, running.id, |_| true).await.progress < 1.0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_jobs_are_rejected_or_fail() {
+    let (url, client) = (start().await, reqwest::Client::new());
+    for job in [
+        json!({ "settings": { "width": 40, "height": 30 } }),
+        json!({ "model": "spheres", "settings": { "width": 1, "height": 30 } }),
+        json!({ "model": "spheres", "settings": { "resume": true, "checkpoint": "render.ckpt" } }),
+    ] {
+        let response = client.post(format!("{url}/jobs")).json(&job).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{job}");
+    }
+
+    // Unknown models only show up once the job starts
+    let status = submit(&client, &url, json!({ "model": "teapot", "settings": RenderSettings::new(40, 30) })).await;
+    let status = wait_for(&client, &url, status.id, |s| s.state == JobState::Failed).await;
+    assert!(status.error.unwrap().contains("teapot"));
+}
@@ -0,0 +1,47 @@
+use raytracer::core::color::Color;
+use raytracer::core::spectrum::{self, SampledWavelengths};
+use raytracer::material::conductor::Conductor;
+
+// Average of the hero-wavelength estimates over stratified wavelengths
+fn round_trip(rgb: Color) -> Color {
+    let steps = 1000;
+    let mut sum = Color::ZERO;
+    for i in 0..steps {
+        let wavelengths = SampledWavelengths::sample((i as f32 + 0.5) / steps as f32);
+        sum += wavelengths.to_rgb(wavelengths.map(|l| spectrum::rgb_to_spectrum(rgb, l)));
+    }
+    sum / steps as f32
+}
+
+fn assert_close(actual: Color, expected: Color, tolerance: f32) {
+    assert!((actual - expected).length() < tolerance, "{actual} is not close to {expected}");
+}
+
+#[test]
+fn white_comes_back_white() {
+    assert_close(round_trip(Color::new(1.0, 1.0, 1.0)), Color::new(1.0, 1.0, 1.0), 0.01);
+    // Light intensities lie outside the range of Smits' fit
+    assert_close(round_trip(Color::new(10.0, 10.0, 10.0)), Color::new(10.0, 10.0, 10.0), 0.1);
+    let bright = Color::new(6.0, 3.0, 1.5);
+    for lambda in [400.0, 500.0, 600.0, 700.0] {
+        let expected = 6.0 * spectrum::rgb_to_spectrum(bright / 6.0, lambda);
+        assert!((spectrum::rgb_to_spectrum(bright, lambda) - expected).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn gold_reflects_by_its_complex_ior() {
+    // At normal incidence R = ((n - 1)² + k²) / ((n + 1)² + k²)
+    for (lambda, n, k) in [(400.0f32, 1.47f32, 1.95f32), (500.0, 0.97, 1.87), (600.0, 0.25, 2.98), (700.0, 0.16, 3.95)] {
+        let expected = ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+        let reflectance = Conductor::GOLD.fresnel(1.0, lambda);
+        assert!((reflectance - expected).abs() < 1e-4, "{reflectance} != {expected} at {lambda} nm");
+    }
+
+    // Yellow: red and green reflect far more than blue
+    let rgb = Conductor::GOLD.fresnel_rgb(1.0);
+    assert!(rgb.x() > rgb.y() && rgb.y() > rgb.z(), "{rgb}");
+    assert!(rgb.z() < 0.6 * rgb.x(), "{rgb}");
+    // Grazing light is reflected almost completely
+    assert!(Conductor::GOLD.fresnel(0.01, 450.0) > 0.9);
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use raytracer::core::camera::Camera;
+use raytracer::core::color::Color;
+use raytracer::core::ray::Ray;
+use raytracer::core::sampler::IndependentSampler;
+use raytracer::core::vec3::{Point3, Vec3};
+use raytracer::material::material::{Glass, Lambertian, Material};
+use raytracer::objects::hittable::HitRecord;
+use raytracer::objects::light::Light;
+use raytracer::objects::sphere::Sphere;
+use raytracer::objects::world::World;
+use raytracer::renderer::integrator;
+use raytracer::renderer::renderer::Renderer;
+use raytracer::renderer::settings::{IntegratorKind, RenderSettings};
+
+const SIZE: usize = 32;
+
+// A sphere floating above a white floor with the light straight above, so its
+// shadow falls right below it. The camera looks at the shadow from low over the
+// floor, the right edge of the image is out of the shadow.
+fn scene(ball: Arc<dyn Material>) -> Renderer {
+    let floor = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)));
+    let mut world = World::new();
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, floor)));
+    world.add_hittable(Box::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 0.5, ball)));
+    world.add_light(Light::new(Point3::new(0.0, 4.0, 0.0), Color::new(20.0, 20.0, 20.0)));
+    let camera = Camera::perspective(Point3::new(0.0, 0.3, 3.0), Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 40.0, 1.0);
+    Renderer::new(camera, world)
+}
+
+fn green_glass() -> Glass {
+    Glass::new(Color::new(0.3, 0.9, 0.4), 1.5)
+}
+
+// Light reaching the floor under the sphere, seen from beside the sphere
+fn under(renderer: &Renderer) -> Color {
+    let world = &renderer.world;
+    let ray = Ray::new(Point3::new(2.0, 2.0, 0.0), Vec3::new(-1.0, -1.0, 0.0));
+    let mut rec = HitRecord::new();
+    assert!(world.hit(&ray, 0.001, f32::INFINITY, &mut rec));
+    let sampler = &mut IndependentSampler::new(0);
+    integrator::shadow_transmittance(world, &ray, &rec, world.lights[0].position(), sampler)
+}
+
+#[test]
+fn glass_tints_the_light_and_opaque_surfaces_block_it() {
+    let glass = under(&scene(Arc::new(green_glass())));
+    // Two crossings of the glass surface
+    let expected = Color::new(0.3 * 0.3, 0.9 * 0.9, 0.4 * 0.4);
+    assert!((glass - expected).length() < 1e-5, "{glass}");
+
+    let opaque = under(&scene(Arc::new(Lambertian::new(Color::new(0.3, 0.9, 0.4)))));
+    assert_eq!((opaque.x(), opaque.y(), opaque.z()), (0.0, 0.0, 0.0));
+
+    let absorbing = under(&scene(Arc::new(green_glass().with_absorption(Color::new(1.0, 1.0, 1.0)))));
+    // One unit of glass between the two surfaces
+    assert!((absorbing - expected * (-1.0f32).exp()).length() < 1e-3, "{absorbing}");
+}
+
+#[test]
+fn shadow_rays_leave_the_glass_they_start_in() {
+    let renderer = scene(Arc::new(green_glass().with_absorption(Color::new(1.0, 1.0, 1.0))));
+    let world = &renderer.world;
+    // Into the ball from below, up to the inside of its top
+    let mut rec = HitRecord::new();
+    let ray = Ray::new(Point3::new(0.0, 0.2, 0.0), Vec3::new(0.0, 1.0, 0.0));
+    assert!(world.hit(&ray, 0.001, f32::INFINITY, &mut rec));
+    let (mut attenuation, mut inside) = (Color::default(), Ray::default());
+    assert!(rec.mat.clone().unwrap().transmit_shadow(&ray, &rec, &mut attenuation, &mut inside));
+    assert!(world.hit(&inside, 0.001, f32::INFINITY, &mut rec) && !rec.front_face);
+
+    // Out through the top once, no absorption between the ball and the light
+    let sampler = &mut IndependentSampler::new(0);
+    let light = integrator::shadow_transmittance(world, &inside, &rec, world.lights[0].position(), sampler);
+    assert!((light - Color::new(0.3, 0.9, 0.4)).length() < 1e-5, "{light}");
+}
+
+#[test]
+fn classic_shadows_of_glass_are_tinted_only_when_asked() {
+    let renderer = scene(Arc::new(green_glass()));
+    let settings = RenderSettings {
+        integrator: IntegratorKind::Classic,
+        samples_per_pixel: 1,
+        ..RenderSettings::new(SIZE, SIZE)
+    };
+    let shadow = |settings: &RenderSettings| {
+        let pixels = renderer.render_pixels(settings).unwrap();
+        let row = (SIZE / 2) * SIZE;
+        (pixels[row + SIZE - 1], pixels[row + SIZE / 2])
+    };
+
+    let (_, opaque) = shadow(&settings);
+    let (lit, tinted) = shadow(&RenderSettings { transmissive_shadows: true, ..settings.clone() });
+    assert!(tinted.y() > opaque.y(), "{tinted} is no brighter than {opaque}");
+    assert!(tinted.y() > tinted.x() && tinted.y() > tinted.z(), "{tinted} is not green");
+    assert!(lit.y() > tinted.y(), "{lit} is no brighter than {tinted}");
+}